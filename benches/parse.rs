@@ -0,0 +1,68 @@
+// Times scanning and parsing a generated file of 10,000 statements, with `cargo bench`. There's no
+// benchmarking crate, so this is a plain program that parses the file a number of times and reports
+// the fastest and the median.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use rlox_treewalk::errors::ErrorLoggable;
+use rlox_treewalk::parser::Parser;
+use rlox_treewalk::scanner::Scanner;
+
+const STATEMENTS: usize = 10_000;
+const RUNS: usize = 20;
+
+// A mix of the statements real programs are made of, so that no one rule dominates.
+fn generate_source() -> String {
+    let mut source = String::new();
+    for i in 0..STATEMENTS {
+        let statement = match i % 5 {
+            0 => format!("var v{} = {} * (2 + {}) - \"s\";\n", i, i, i % 7),
+            1 => format!(
+                "if (v{} > {} and true) print v{}; else v{} = nil;\n",
+                i - 1,
+                i,
+                i - 1,
+                i - 1
+            ),
+            2 => format!("fun f{}(a, b) {{ return a + b * {}; }}\n", i, i),
+            3 => format!(
+                "while (v{} < {}) {{ v{} = v{} + 1; }}\n",
+                i - 3,
+                i,
+                i - 3,
+                i - 3
+            ),
+            _ => format!("print f{}(v{}, [1, 2, {}]);\n", i - 2, i - 4, i),
+        };
+        source.push_str(&statement);
+    }
+    source
+}
+
+fn parse(source: &str) -> Duration {
+    let start = Instant::now();
+    let scanner = Scanner::from_source(source.to_string());
+    let mut parser = Parser::new(scanner.tokens());
+    let program = black_box(parser.parse());
+    let elapsed = start.elapsed();
+    assert_eq!(program.statements.len(), STATEMENTS);
+    assert!(parser.error_log().is_empty());
+    elapsed
+}
+
+fn main() {
+    let source = generate_source();
+    // The first run warms things up, and isn't counted.
+    parse(&source);
+    let mut times: Vec<Duration> = (0..RUNS).map(|_| parse(&source)).collect();
+    times.sort();
+    println!(
+        "parsing {} statements ({} bytes): fastest {:?}, median {:?} over {} runs",
+        STATEMENTS,
+        source.len(),
+        times[0],
+        times[RUNS / 2],
+        RUNS
+    );
+}
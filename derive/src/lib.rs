@@ -0,0 +1,88 @@
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident};
+
+// -----| AstNode |-----
+
+/// Derives a visitor for an enum of AST nodes. For an enum `Expr`, this generates an `ExprVisitor`
+/// trait with a `visit_*` method per variant (`Expr::Binary` becomes `visit_binary`), and an
+/// `accept` method on `Expr` that dispatches to the right one.
+///
+/// Every variant has to hold exactly one unnamed field, which is what the visit method receives.
+#[proc_macro_derive(AstNode)]
+pub fn derive_ast_node(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let node_name = &input.ident;
+    let visibility = &input.vis;
+    let visitor_name = format_ident!("{}Visitor", node_name);
+
+    let variants = match &input.data {
+        Data::Enum(data) => &data.variants,
+        _ => {
+            return syn::Error::new_spanned(node_name, "AstNode can only be derived for enums")
+                .to_compile_error()
+                .into()
+        }
+    };
+
+    let mut visit_methods = Vec::new();
+    let mut accept_arms = Vec::new();
+    for variant in variants {
+        let variant_name = &variant.ident;
+        let field_type = match &variant.fields {
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => &fields.unnamed[0].ty,
+            _ => {
+                return syn::Error::new_spanned(
+                    variant,
+                    "AstNode variants must hold exactly one unnamed field",
+                )
+                .to_compile_error()
+                .into()
+            }
+        };
+        let method_name = Ident::new(
+            &format!("visit_{}", to_snake_case(&variant_name.to_string())),
+            Span::call_site(),
+        );
+        visit_methods.push(quote! {
+            fn #method_name(&mut self, node: &#field_type) -> Self::Output;
+        });
+        accept_arms.push(quote! {
+            #node_name::#variant_name(node) => visitor.#method_name(node),
+        });
+    }
+
+    let expanded = quote! {
+        #visibility trait #visitor_name {
+            type Output;
+            #(#visit_methods)*
+        }
+
+        impl #node_name {
+            #visibility fn accept<V: #visitor_name>(&self, visitor: &mut V) -> V::Output {
+                match self {
+                    #(#accept_arms)*
+                }
+            }
+        }
+    };
+    expanded.into()
+}
+
+// --- Utilities ---
+
+fn to_snake_case(name: &str) -> String {
+    let mut ret = String::new();
+    for (index, character) in name.chars().enumerate() {
+        if character.is_uppercase() {
+            if index > 0 {
+                ret.push('_');
+            }
+            ret.extend(character.to_lowercase());
+        } else {
+            ret.push(character);
+        }
+    }
+    ret
+}
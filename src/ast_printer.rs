@@ -1,60 +1,368 @@
+use std::collections::HashMap;
+use std::fmt;
+
 use crate::parser;
+use crate::scanner;
 
-pub fn expr_to_ast_string(expression: &parser::Expr) -> String {
-    let ret = match expression {
-        parser::Expr::Binary(expr) => {
-            format!(
-                "({} {} {})",
-                expr.operator,
-                expr_to_ast_string(&expr.left),
-                expr_to_ast_string(&expr.right)
-            )
-        }
-        parser::Expr::Ternary(expr) => {
-            format!(
-                "({} ? {} : {})",
-                expr_to_ast_string(&expr.condition),
-                expr_to_ast_string(&expr.left_result),
-                expr_to_ast_string(&expr.right_result),
-            )
-        }
-        parser::Expr::Grouping(expr) => {
-            format!("(group {})", expr_to_ast_string(&expr))
-        }
-        parser::Expr::Literal(kind) => match kind {
-            parser::LiteralKind::Number(number) => number.to_string(),
-            parser::LiteralKind::String(string) => string.to_string(),
-            parser::LiteralKind::Boolean(boolean) => boolean.to_string(),
-            parser::LiteralKind::Nil => String::from("nil"),
-        },
-        parser::Expr::Unary(expr) => {
-            format!("({} {})", expr.operator, expr_to_ast_string(&expr.right))
-        } // parser::Expr::Variable(expr) => {
+pub fn expr_to_ast_string(expression: parser::ExprId, expressions: &parser::ExprArena) -> String {
+    AstPrinter::new(expressions, None).print(expression)
+}
 
-          // }
-    };
-    ret
+struct AstPrinter<'a> {
+    expressions: &'a parser::ExprArena,
+    /// Only there when disassembling, in which case variables are printed with how far out they
+    /// were resolved to, and calls with how many arguments they're given.
+    locals: Option<&'a HashMap<parser::ExprId, usize>>,
+    /// The expression being printed, since the visitor is only given what's inside it.
+    current: Option<parser::ExprId>,
+}
+
+impl<'a> AstPrinter<'a> {
+    fn new(
+        expressions: &'a parser::ExprArena,
+        locals: Option<&'a HashMap<parser::ExprId, usize>>,
+    ) -> Self {
+        AstPrinter {
+            expressions,
+            locals,
+            current: None,
+        }
+    }
+    fn print(&mut self, expression: parser::ExprId) -> String {
+        self.current = Some(expression);
+        self.expressions.get(expression).expr.accept(self)
+    }
+    // Has to be asked for before anything inside the current expression is printed.
+    fn resolution_note(&self) -> String {
+        match self.locals {
+            Some(locals) => match self.current.and_then(|current| locals.get(&current)) {
+                Some(depth) => format!(" @depth={}", depth),
+                None => String::from(" @global"),
+            },
+            None => String::new(),
+        }
+    }
+}
+
+// The visitor trait is derived from `Expr`, so every new kind of expression (like lambdas, once
+// there are any) can't compile until it's given a form here.
+impl parser::ExprVisitor for AstPrinter<'_> {
+    type Output = String;
+
+    // Long chains like `a + b + c + ...` lean left as deep as they are long, so they're built up
+    // with a loop rather than by recursing down them. All of the opening parentheses go first, then
+    // each right operand from the innermost out, so that the string is only ever appended to.
+    fn visit_binary(&mut self, expr: &parser::BinaryExpr) -> String {
+        let mut chain = vec![expr];
+        let mut leftmost = expr.left;
+        while let parser::Expr::Binary(inner) = &self.expressions.get(leftmost).expr {
+            chain.push(inner);
+            leftmost = inner.left;
+        }
+        let mut ret = String::new();
+        for binary in chain.iter() {
+            ret.push_str(&format!("({} ", binary.operator));
+        }
+        ret.push_str(&self.print(leftmost));
+        for binary in chain.into_iter().rev() {
+            ret.push_str(&format!(" {})", self.print(binary.right)));
+        }
+        ret
+    }
+    fn visit_ternary(&mut self, expr: &parser::TernaryExpr) -> String {
+        format!(
+            "({} ? {} : {})",
+            self.print(expr.condition),
+            self.print(expr.left_result),
+            self.print(expr.right_result),
+        )
+    }
+    // The same as binaries, so that `a or b or c or ...` doesn't recurse either.
+    fn visit_logical(&mut self, expr: &parser::LogicalExpr) -> String {
+        let mut chain = vec![expr];
+        let mut leftmost = expr.left;
+        while let parser::Expr::Logical(inner) = &self.expressions.get(leftmost).expr {
+            chain.push(inner);
+            leftmost = inner.left;
+        }
+        let mut ret = String::new();
+        for logical in chain.iter() {
+            ret.push_str(&format!("({} ", logical.operator));
+        }
+        ret.push_str(&self.print(leftmost));
+        for logical in chain.into_iter().rev() {
+            ret.push_str(&format!(" {})", self.print(logical.right)));
+        }
+        ret
+    }
+    fn visit_grouping(&mut self, expr: &parser::ExprId) -> String {
+        format!("(group {})", self.print(*expr))
+    }
+    fn visit_unary(&mut self, expr: &parser::UnaryExpr) -> String {
+        format!("({} {})", expr.operator, self.print(expr.right))
+    }
+    fn visit_typeof(&mut self, expr: &parser::ExprId) -> String {
+        format!("(typeof {})", self.print(*expr))
+    }
+    fn visit_literal(&mut self, kind: &parser::LiteralKind) -> String {
+        kind.to_string()
+    }
+    fn visit_list(&mut self, expr: &parser::ListExpr) -> String {
+        let mut list_string = String::from("(list");
+        for element in expr.elements.iter() {
+            list_string.push_str(&format!(" {}", self.print(*element)));
+        }
+        list_string.push(')');
+        list_string
+    }
+    fn visit_tuple(&mut self, expr: &parser::TupleExpr) -> String {
+        let mut tuple_string = String::from("(tuple");
+        for element in expr.elements.iter() {
+            tuple_string.push_str(&format!(" {}", self.print(*element)));
+        }
+        tuple_string.push(')');
+        tuple_string
+    }
+    fn visit_map(&mut self, expr: &parser::MapExpr) -> String {
+        let mut map_string = String::from("(map");
+        for (key, value) in expr.entries.iter() {
+            map_string.push_str(&format!(" ({:?} {})", key, self.print(*value)));
+        }
+        map_string.push(')');
+        map_string
+    }
+    // Spelled out, since string literals are printed unquoted and `x` alone could be either.
+    fn visit_variable(&mut self, name: &scanner::Identifier) -> String {
+        format!("(var {}{})", name, self.resolution_note())
+    }
+    fn visit_assign(&mut self, expr: &parser::AssignExpr) -> String {
+        let note = self.resolution_note();
+        format!("(assign {}{} {})", expr.name, note, self.print(expr.value))
+    }
+    fn visit_get(&mut self, expr: &parser::GetExpr) -> String {
+        format!("(. {} {})", self.print(expr.object), expr.name)
+    }
+    fn visit_call(&mut self, expr: &parser::CallExpr) -> String {
+        let mut call_string = format!("(call {}", self.print(expr.callee));
+        for argument in expr.arguments.iter() {
+            call_string.push_str(&format!(" {}", self.print(*argument)));
+        }
+        if self.locals.is_some() {
+            call_string.push_str(&format!(" @arity={}", expr.arguments.len()));
+        }
+        call_string.push(')');
+        call_string
+    }
+    fn visit_this(&mut self, _: &parser::ThisExpr) -> String {
+        format!("(this{})", self.resolution_note())
+    }
+    fn visit_super(&mut self, expr: &parser::SuperExpr) -> String {
+        format!("(super{} {})", self.resolution_note(), expr.method)
+    }
+    fn visit_set(&mut self, expr: &parser::SetExpr) -> String {
+        format!(
+            "(assign (. {} {}) {})",
+            self.print(expr.object),
+            expr.name,
+            self.print(expr.value)
+        )
+    }
+}
+
+// Nested statements are indented by this many spaces per level.
+const INDENT_WIDTH: usize = 2;
+
+pub fn stmt_to_ast_string(statement: &parser::Stmt, expressions: &parser::ExprArena) -> String {
+    stmt_to_indented_ast_string(statement, expressions, 0)
+}
+
+/// Only the lines *after* the first are indented, the first is assumed to already be in place.
+pub fn stmt_to_indented_ast_string(
+    statement: &parser::Stmt,
+    expressions: &parser::ExprArena,
+    indent_level: usize,
+) -> String {
+    stmt_string(
+        statement,
+        &mut AstPrinter::new(expressions, None),
+        indent_level,
+    )
 }
 
-pub fn stmt_to_ast_string(statement: &parser::Stmt) -> String {
+fn stmt_string(statement: &parser::Stmt, printer: &mut AstPrinter, indent_level: usize) -> String {
     let ret = match statement {
         parser::Stmt::Expression(stmt) => {
-            format!(
-                "Expression Statement: {}",
-                expr_to_ast_string(&stmt.expression)
-            )
+            format!("Expression Statement: {}", printer.print(stmt.expression))
         }
         parser::Stmt::Print(stmt) => {
-            format!("Print Statement: {}", expr_to_ast_string(&stmt.expression),)
+            format!("Print Statement: {}", printer.print(stmt.expression),)
         }
         parser::Stmt::Var(stmt) => {
             let initilizer_string = if let Some(initializer) = &stmt.initializer {
-                format!(" = {}", expr_to_ast_string(initializer))
+                format!(" = {}", printer.print(*initializer))
             } else {
                 String::from("")
             };
             format!("Variable Statement: {}{}", stmt.name, initilizer_string)
         }
+        parser::Stmt::If(stmt) => {
+            let inner_indent = " ".repeat((indent_level + 1) * INDENT_WIDTH);
+            let mut if_string = format!(
+                "If Statement: {}\n{}Then: {}",
+                printer.print(stmt.condition),
+                inner_indent,
+                stmt_string(&stmt.then_branch, printer, indent_level + 1)
+            );
+            if let Some(else_branch) = &stmt.else_branch {
+                if_string.push_str(&format!(
+                    "\n{}Else: {}",
+                    inner_indent,
+                    stmt_string(else_branch, printer, indent_level + 1)
+                ));
+            }
+            if_string
+        }
+        parser::Stmt::While(stmt) => {
+            let inner_indent = " ".repeat((indent_level + 1) * INDENT_WIDTH);
+            let mut while_string = format!(
+                "While Statement: {}\n{}Body: {}",
+                printer.print(stmt.condition),
+                inner_indent,
+                stmt_string(&stmt.body, printer, indent_level + 1)
+            );
+            if let Some(increment) = stmt.increment {
+                while_string.push_str(&format!(
+                    "\n{}Increment: {}",
+                    inner_indent,
+                    printer.print(increment)
+                ));
+            }
+            while_string
+        }
+        parser::Stmt::Continue(_) => String::from("Continue Statement"),
+        parser::Stmt::Return(stmt) => match stmt.value {
+            Some(value) => format!("Return Statement: {}", printer.print(value)),
+            None => String::from("Return Statement"),
+        },
+        parser::Stmt::Function(stmt) => format!(
+            "Function Statement: {}",
+            function_string(stmt, printer, indent_level)
+        ),
+        parser::Stmt::Class(stmt) => {
+            let inner_indent = " ".repeat((indent_level + 1) * INDENT_WIDTH);
+            let mut class_string = format!("Class Statement: {}", stmt.name);
+            if let Some(superclass) = stmt.superclass {
+                class_string.push_str(&format!(" < {}", printer.print(superclass)));
+            }
+            class_string.push_str(" {\n");
+            for method in stmt.methods.iter() {
+                class_string.push_str(&format!(
+                    "{}Method: {}\n",
+                    inner_indent,
+                    function_string(method, printer, indent_level + 1)
+                ));
+            }
+            class_string.push_str(&format!("{}}}", " ".repeat(indent_level * INDENT_WIDTH)));
+            class_string
+        }
+        parser::Stmt::Block(stmt) => {
+            let inner_indent = " ".repeat((indent_level + 1) * INDENT_WIDTH);
+            let mut block_string = String::from("Block Statement: {\n");
+            for inner_statement in stmt.statements.iter() {
+                block_string.push_str(&format!(
+                    "{}{}\n",
+                    inner_indent,
+                    stmt_string(inner_statement, printer, indent_level + 1)
+                ));
+            }
+            block_string.push_str(&format!("{}}}", " ".repeat(indent_level * INDENT_WIDTH)));
+            block_string
+        }
     };
     ret
 }
+
+// Shared by functions and methods, which are the same but for the `fun`.
+fn function_string(
+    function: &parser::FunctionStmt,
+    printer: &mut AstPrinter,
+    indent_level: usize,
+) -> String {
+    let inner_indent = " ".repeat((indent_level + 1) * INDENT_WIDTH);
+    let parameters: Vec<&str> = function
+        .parameters
+        .iter()
+        .map(|parameter| parameter.name.as_str())
+        .collect();
+    let mut function_string = format!("{}({}) {{\n", function.name, parameters.join(", "));
+    for inner_statement in function.body.iter() {
+        function_string.push_str(&format!(
+            "{}{}\n",
+            inner_indent,
+            stmt_string(inner_statement, printer, indent_level + 1)
+        ));
+    }
+    function_string.push_str(&format!("{}}}", " ".repeat(indent_level * INDENT_WIDTH)));
+    function_string
+}
+
+// -----| Display |-----
+
+// Neither a statement nor an expression id means anything without the arena that its expressions
+// were parsed into, so they're displayed paired with it.
+pub struct InArena<'a, T> {
+    pub node: T,
+    pub expressions: &'a parser::ExprArena,
+}
+
+impl fmt::Display for InArena<'_, &parser::Stmt> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", stmt_to_ast_string(self.node, self.expressions))
+    }
+}
+
+impl fmt::Display for InArena<'_, parser::ExprId> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", expr_to_ast_string(self.node, self.expressions))
+    }
+}
+
+/// One statement per line.
+impl fmt::Display for parser::Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<String> = self
+            .statements
+            .iter()
+            .map(|statement| {
+                InArena {
+                    node: statement,
+                    expressions: &self.expressions,
+                }
+                .to_string()
+            })
+            .collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// A program printed the same as it is with `Display`, except that each variable shows how many
+/// scopes out the resolver found it (or that it's global), and each call how many arguments it's
+/// given. Mostly for working out why a variable isn't the one that was expected.
+pub struct Disassembler<'a> {
+    pub program: &'a parser::Program,
+    /// As found by the resolver.
+    pub locals: &'a HashMap<parser::ExprId, usize>,
+}
+
+impl fmt::Display for Disassembler<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut printer = AstPrinter::new(&self.program.expressions, Some(self.locals));
+        let lines: Vec<String> = self
+            .program
+            .statements
+            .iter()
+            .map(|statement| stmt_string(statement, &mut printer, 0))
+            .collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
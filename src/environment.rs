@@ -0,0 +1,450 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::error_codes::ErrorCode;
+use crate::errors;
+use crate::language_utilities;
+use crate::scanner::Identifier;
+use crate::source_file;
+use crate::value::Value;
+
+// -----| Environment |-----
+
+/// Refers to a scope by its position in the `Environment` it belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScopeId(usize);
+
+/// Where variables live. Each block gets its own scope, which is pushed on entry and popped on
+/// exit, and lookups fall through to the enclosing scopes until the global one is reached.
+///
+/// Every scope lives in an arena, and refers to the one enclosing it by id, the same way
+/// expressions do. That way a function can hold on to the scope it was declared in by id, long
+/// after that scope has been popped, without any `Rc` cycles to be careful about. A popped scope is
+/// freed for reuse straight away unless a function captured it (or a scope inside it). Captured
+/// scopes are freed by `collect_garbage()` instead, once nothing can reach them any more.
+// Lists and maps are shared by reference counting rather than living here, which means one that
+// contains itself is never freed.
+pub struct Environment {
+    scopes: Vec<Scope>,
+    current: ScopeId,
+    /// Scopes that have been popped and can be handed out again.
+    free: Vec<ScopeId>,
+    /// What was current before each call in progress, innermost last, which the collector has to
+    /// treat as being in use too.
+    suspended: Vec<ScopeId>,
+    /// How many scopes can be in use before it's worth collecting, see `wants_collection()`.
+    next_collection: usize,
+}
+
+struct Scope {
+    /// Variables declared without an initializer have no value at all until they're assigned,
+    /// which isn't the same as being nil.
+    values: HashMap<Identifier, Option<Value>>,
+    enclosing: Option<ScopeId>,
+    /// Whether a function might still need this scope after it's popped.
+    captured: bool,
+}
+
+// The global scope is the first one made, and it's never popped.
+const GLOBAL_SCOPE: ScopeId = ScopeId(0);
+
+// Small enough that a loop declaring a function each time around stays small, and big enough that
+// ordinary programs never collect at all.
+const FIRST_COLLECTION: usize = 1024;
+
+impl Environment {
+    pub fn new() -> Self {
+        Environment {
+            scopes: vec![Scope {
+                values: HashMap::new(),
+                enclosing: None,
+                captured: true,
+            }],
+            current: GLOBAL_SCOPE,
+            free: Vec::new(),
+            suspended: Vec::new(),
+            next_collection: FIRST_COLLECTION,
+        }
+    }
+    // --- Scopes ---
+    pub fn push_scope(&mut self) {
+        let scope = Scope {
+            values: HashMap::new(),
+            enclosing: Some(self.current),
+            captured: false,
+        };
+        self.current = match self.free.pop() {
+            Some(id) => {
+                self.scopes[id.0] = scope;
+                id
+            }
+            None => {
+                self.scopes.push(scope);
+                ScopeId(self.scopes.len() - 1)
+            }
+        };
+    }
+    // Note that popping the global scope does nothing, there's nowhere further out to go.
+    pub fn pop_scope(&mut self) {
+        let popped = self.current;
+        let scope = &mut self.scopes[popped.0];
+        if let Some(enclosing) = scope.enclosing {
+            self.current = enclosing;
+            if !scope.captured {
+                // Dropping the values now rather than when the scope's reused, since they could be
+                // holding on to something big.
+                scope.values = HashMap::new();
+                self.free.push(popped);
+            }
+        }
+    }
+    /// Keeps the current scope (and every one enclosing it) alive after it's popped, and hands
+    /// back its id, for a function declared here to see its variables from wherever it's called.
+    pub fn capture(&mut self) -> ScopeId {
+        let mut id = Some(self.current);
+        while let Some(scope) = id.map(|id| &mut self.scopes[id.0]) {
+            scope.captured = true;
+            id = scope.enclosing;
+        }
+        self.current
+    }
+    /// Pushes a new scope inside a captured one, for the body of a function to run in. Whatever
+    /// was current before is handed back, for `leave()` to return to.
+    pub fn enter(&mut self, closure: ScopeId) {
+        let previous = std::mem::replace(&mut self.current, closure);
+        self.suspended.push(previous);
+        self.push_scope();
+    }
+    /// Goes back to wherever was current before the matching `enter()`.
+    pub fn leave(&mut self) {
+        self.pop_scope();
+        if let Some(previous) = self.suspended.pop() {
+            self.current = previous;
+        }
+    }
+    /// The current scope and then each one enclosing it, out to the global scope.
+    fn ancestors(&self) -> impl Iterator<Item = ScopeId> + '_ {
+        std::iter::successors(Some(self.current), |id| self.scopes[id.0].enclosing)
+    }
+    /// How many scopes the current one is nested in, so 0 for the global scope.
+    pub fn depth(&self) -> usize {
+        self.ancestors().count() - 1
+    }
+    /// Every variable in every scope, innermost first, for debugging. Variables within a scope are
+    /// sorted by name, since they're kept in no particular order.
+    pub fn dump(&self) -> String {
+        let mut lines = Vec::new();
+        let depth = self.depth();
+        for (outwards, id) in self.ancestors().enumerate() {
+            lines.push(format!("scope {}:", depth - outwards));
+            let mut variables: Vec<_> = self.scopes[id.0].values.iter().collect();
+            variables.sort_by_key(|(name, _)| *name);
+            for (name, value) in variables {
+                match value {
+                    Some(value) => lines.push(format!("  {} = {}", name, value)),
+                    None => lines.push(format!("  {} (unassigned)", name)),
+                }
+            }
+        }
+        lines.join("\n")
+    }
+    // --- Garbage Collection ---
+    /// How many scopes are in use, whether or not anything can still reach them.
+    pub fn scope_count(&self) -> usize {
+        self.scopes.len() - self.free.len()
+    }
+    /// Whether enough scopes have piled up since the last collection to be worth another. The
+    /// threshold doubles with whatever survived the last one, so that programs keeping plenty of
+    /// closures around on purpose aren't collected over and over for nothing.
+    pub fn wants_collection(&self) -> bool {
+        self.scope_count() >= self.next_collection
+    }
+    /// Frees every scope that can't be reached any more, and returns how many that was. A scope
+    /// can be reached if it's current, or was when a call in progress was made, or if it encloses
+    /// one that can, or if a function in a value that can be reached closes over it.
+    ///
+    /// The environment can't see values that the interpreter is only holding on to partway through
+    /// evaluating something, like the arguments of a call, so those have to be passed in as `held`.
+    /// Anything left out might have its scopes freed from under it.
+    pub fn collect_garbage(&mut self, held: &[Value]) -> usize {
+        let mut marker = Marker {
+            reached: vec![false; self.scopes.len()],
+            scopes: vec![GLOBAL_SCOPE, self.current],
+            values: Vec::new(),
+            shared: HashSet::new(),
+        };
+        marker.scopes.extend(self.suspended.iter().copied());
+        marker.push_values(held.iter());
+        loop {
+            if let Some(id) = marker.scopes.pop() {
+                if !std::mem::replace(&mut marker.reached[id.0], true) {
+                    let scope = &self.scopes[id.0];
+                    marker.scopes.extend(scope.enclosing);
+                    marker.push_values(scope.values.values().flatten());
+                }
+            } else if let Some(value) = marker.values.pop() {
+                marker.trace(&value);
+            } else {
+                break;
+            }
+        }
+        // Already free scopes count as reached, so that they aren't freed twice.
+        for id in self.free.iter() {
+            marker.reached[id.0] = true;
+        }
+        let mut freed = 0;
+        for (index, reached) in marker.reached.into_iter().enumerate() {
+            if !reached {
+                self.scopes[index].values = HashMap::new();
+                self.free.push(ScopeId(index));
+                freed += 1;
+            }
+        }
+        self.next_collection = FIRST_COLLECTION.max(self.scope_count() * 2);
+        freed
+    }
+    // --- Variables ---
+    /// Redefining a variable simply replaces it. At global scope this is deliberate, so that
+    /// declarations can be retyped at the prompt without complaint. Redefining a local in the same
+    /// block never gets this far, the resolver rejects it.
+    pub fn define(&mut self, name: Identifier, value: Value) {
+        self.scopes[self.current.0].values.insert(name, Some(value));
+    }
+    /// Like `define()`, but for `var a;`. Reading the variable is an error until something is
+    /// assigned to it, since forgetting to is far more likely than wanting nil. Anyone who does want
+    /// nil can say so with `var a = nil;`.
+    pub fn declare(&mut self, name: Identifier) {
+        self.scopes[self.current.0].values.insert(name, None);
+    }
+    /// Reaches the global scope however deeply nested this is, for things like natives that only
+    /// ever live there. Shadowing locals are ignored, and so are variables that haven't been
+    /// assigned yet.
+    pub fn get_global(&self, name: &str) -> Option<&Value> {
+        self.scopes[GLOBAL_SCOPE.0].values.get(name)?.as_ref()
+    }
+    pub fn define_global(&mut self, name: Identifier, value: Value) {
+        self.scopes[GLOBAL_SCOPE.0].values.insert(name, Some(value));
+    }
+    pub fn get(
+        &self,
+        name: &str,
+        location: source_file::SourceSpan,
+    ) -> Result<Value, errors::Error> {
+        match self.lookup(name) {
+            Some(Some(value)) => Ok(value.clone()),
+            Some(None) => Err(unassigned_variable_error(name, location)),
+            None => Err(self.undefined_variable_error(name, location)),
+        }
+    }
+    pub fn assign(
+        &mut self,
+        name: &str,
+        value: Value,
+        location: source_file::SourceSpan,
+    ) -> Result<(), errors::Error> {
+        match self.lookup_mut(name) {
+            Some(variable) => {
+                *variable = Some(value);
+                Ok(())
+            }
+            None => Err(self.undefined_variable_error(name, location)),
+        }
+    }
+    // The innermost variable with the name, searching outwards.
+    fn lookup(&self, name: &str) -> Option<&Option<Value>> {
+        self.ancestors()
+            .find_map(|id| self.scopes[id.0].values.get(name))
+    }
+    fn lookup_mut(&mut self, name: &str) -> Option<&mut Option<Value>> {
+        let id = self
+            .ancestors()
+            .find(|id| self.scopes[id.0].values.contains_key(name))?;
+        self.scopes[id.0].values.get_mut(name)
+    }
+    /// Every variable that can be seen from here, in any scope out to the global one. Shadowed
+    /// names show up more than once.
+    pub fn visible_names(&self) -> Vec<&Identifier> {
+        self.ancestors()
+            .flat_map(|id| self.scopes[id.0].values.keys())
+            .collect()
+    }
+    // Made here rather than at the global scope where the search ends, so that the suggestion can
+    // come from any scope that's visible.
+    fn undefined_variable_error(
+        &self,
+        name: &str,
+        location: source_file::SourceSpan,
+    ) -> errors::Error {
+        let error = errors::Error::runtime(location, "Undefined variable")
+            .with_code(ErrorCode::UndefinedVariable)
+            .with_subject(name.to_string());
+        let candidates = self.visible_names().into_iter().map(String::as_str);
+        match language_utilities::closest_match(name, candidates) {
+            Some(suggestion) => error.with_note(format!("did you mean '{}'?", suggestion)),
+            None => error,
+        }
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment::new()
+    }
+}
+
+// The state of a collection partway through marking. Scopes and values still to be looked at are
+// kept in lists rather than recursed into, since a long enough list of lists would otherwise
+// overflow the stack.
+struct Marker {
+    reached: Vec<bool>,
+    scopes: Vec<ScopeId>,
+    values: Vec<Value>,
+    /// Every list, map, tuple, class and instance already traced, by address, so that ones holding
+    /// themselves don't go around forever.
+    shared: HashSet<*const ()>,
+}
+
+impl Marker {
+    // Only values that could lead to a scope are kept, there's no point copying every string.
+    fn push_values<'a>(&mut self, values: impl Iterator<Item = &'a Value>) {
+        self.values
+            .extend(values.filter(|value| value.can_reach_scopes()).cloned());
+    }
+    fn first_visit<T: ?Sized>(&mut self, shared: &Rc<T>) -> bool {
+        self.shared.insert(Rc::as_ptr(shared) as *const ())
+    }
+    fn trace(&mut self, value: &Value) {
+        match value {
+            Value::Function(function) => {
+                self.scopes.push(function.closure);
+                if let Some(this) = &function.this {
+                    self.values.push(Value::Instance(Rc::clone(this)));
+                }
+            }
+            Value::Class(class) => {
+                if self.first_visit(class) {
+                    self.scopes
+                        .extend(class.methods.values().map(|method| method.closure));
+                    if let Some(superclass) = &class.superclass {
+                        self.values.push(Value::Class(Rc::clone(superclass)));
+                    }
+                }
+            }
+            Value::Instance(instance) => {
+                if self.first_visit(instance) {
+                    let instance = instance.borrow();
+                    self.values.push(Value::Class(Rc::clone(&instance.class)));
+                    self.push_values(instance.fields.values());
+                }
+            }
+            Value::List(list) => {
+                if self.first_visit(list) {
+                    self.push_values(list.borrow().iter());
+                }
+            }
+            Value::Map(map) => {
+                if self.first_visit(map) {
+                    self.push_values(map.borrow().values());
+                }
+            }
+            Value::Tuple(tuple) => {
+                if self.first_visit(tuple) {
+                    self.push_values(tuple.iter());
+                }
+            }
+            Value::Number(_)
+            | Value::String(_)
+            | Value::Boolean(_)
+            | Value::Nil
+            | Value::Native(_) => {}
+        }
+    }
+}
+
+fn unassigned_variable_error(name: &str, location: source_file::SourceSpan) -> errors::Error {
+    errors::Error::runtime(location, "Variable used before being assigned")
+        .with_code(ErrorCode::UnassignedVariable)
+        .with_subject(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(environment: &Environment, name: &str) -> Result<Value, errors::Error> {
+        environment.get(name, source_file::SourceSpan::new())
+    }
+
+    // --- Scopes ---
+
+    #[test]
+    fn inner_scopes_shadow_outer_ones_until_popped() {
+        let mut environment = Environment::new();
+        environment.define("a".to_string(), Value::Number(1.0));
+        environment.push_scope();
+        environment.define("a".to_string(), Value::Number(2.0));
+        assert_eq!(get(&environment, "a").unwrap(), Value::Number(2.0));
+        environment.pop_scope();
+        assert_eq!(get(&environment, "a").unwrap(), Value::Number(1.0));
+    }
+
+    #[test]
+    fn assigning_reaches_the_enclosing_scope() {
+        let mut environment = Environment::new();
+        environment.define("a".to_string(), Value::Number(1.0));
+        environment.push_scope();
+        environment.push_scope();
+        environment
+            .assign("a", Value::Number(3.0), source_file::SourceSpan::new())
+            .unwrap();
+        environment.pop_scope();
+        environment.pop_scope();
+        assert_eq!(get(&environment, "a").unwrap(), Value::Number(3.0));
+    }
+
+    #[test]
+    fn redefining_a_global_replaces_it() {
+        let mut environment = Environment::new();
+        environment.define("a".to_string(), Value::Number(1.0));
+        environment.define("a".to_string(), Value::Nil);
+        assert_eq!(get(&environment, "a").unwrap(), Value::Nil);
+    }
+
+    // --- Errors ---
+
+    #[test]
+    fn getting_an_undefined_variable_names_it() {
+        let error = get(&Environment::new(), "missing").unwrap_err();
+        assert_eq!(error.code(), Some(ErrorCode::UndefinedVariable));
+        assert_eq!(error.subject(), Some("missing"));
+    }
+
+    #[test]
+    fn assigning_an_undefined_variable_names_it() {
+        let mut environment = Environment::new();
+        let error = environment
+            .assign("missing", Value::Nil, source_file::SourceSpan::new())
+            .unwrap_err();
+        assert_eq!(error.code(), Some(ErrorCode::UndefinedVariable));
+        assert_eq!(error.subject(), Some("missing"));
+    }
+
+    #[test]
+    fn popped_variables_are_undefined() {
+        let mut environment = Environment::new();
+        environment.push_scope();
+        environment.define("inner".to_string(), Value::Boolean(true));
+        environment.pop_scope();
+        let error = get(&environment, "inner").unwrap_err();
+        assert_eq!(error.code(), Some(ErrorCode::UndefinedVariable));
+    }
+
+    #[test]
+    fn declared_variables_cant_be_read_until_assigned() {
+        let mut environment = Environment::new();
+        environment.declare("a".to_string());
+        let error = get(&environment, "a").unwrap_err();
+        assert_eq!(error.code(), Some(ErrorCode::UnassignedVariable));
+        assert_eq!(error.subject(), Some("a"));
+    }
+}
@@ -0,0 +1,381 @@
+use std::fmt;
+
+// -----| Codes |-----
+
+/// Every distinct kind of diagnostic has a code, which stays the same even when the wording of its
+/// message changes, so that it can be looked up (with `--explain`) and matched on by tools. The
+/// thousands say where it comes from: 0 for scanning, 1 for parsing, 2 for variables (whether
+/// found by the resolver or at runtime), and 3 for everything else at runtime.
+///
+/// Codes are never reused, so if a diagnostic goes away its number should stay unused.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorCode {
+    // Scanning
+    UnexpectedCharacter,
+    UnterminatedString,
+    UnterminatedRawString,
+    UnterminatedHeredoc,
+    HeredocDelimiter,
+    InvalidNumber,
+    // Parsing
+    ExpectedExpression,
+    ExpectedToken,
+    TrailingInput,
+    InvalidAssignmentTarget,
+    AssignmentInCondition,
+    TooManyArguments,
+    ContinueOutsideLoop,
+    ReturnOutsideFunction,
+    OutsideClass,
+    ReturnFromInitializer,
+    NoSuperclass,
+    InheritsFromItself,
+    // Variables
+    ReadInOwnInitializer,
+    DuplicateLocal,
+    UndefinedVariable,
+    UnassignedVariable,
+    RedeclaredGlobal,
+    UnreadLocal,
+    // Runtime
+    InvalidOperands,
+    ShiftOutOfRange,
+    NotANumber,
+    NotCallable,
+    WrongArgumentCount,
+    NotAnInstance,
+    NonBooleanCondition,
+    StackOverflow,
+    BudgetExceeded,
+    Io,
+    InvalidArgument,
+    AssertionFailed,
+    Conversion,
+    IndexOutOfBounds,
+    MissingKey,
+    UndefinedProperty,
+    InvalidSuperclass,
+    FormatString,
+    IllegalOperator,
+}
+
+impl ErrorCode {
+    pub const ALL: &'static [ErrorCode] = &[
+        ErrorCode::UnexpectedCharacter,
+        ErrorCode::UnterminatedString,
+        ErrorCode::UnterminatedRawString,
+        ErrorCode::UnterminatedHeredoc,
+        ErrorCode::HeredocDelimiter,
+        ErrorCode::InvalidNumber,
+        ErrorCode::ExpectedExpression,
+        ErrorCode::ExpectedToken,
+        ErrorCode::TrailingInput,
+        ErrorCode::InvalidAssignmentTarget,
+        ErrorCode::AssignmentInCondition,
+        ErrorCode::TooManyArguments,
+        ErrorCode::ContinueOutsideLoop,
+        ErrorCode::ReturnOutsideFunction,
+        ErrorCode::OutsideClass,
+        ErrorCode::ReturnFromInitializer,
+        ErrorCode::NoSuperclass,
+        ErrorCode::InheritsFromItself,
+        ErrorCode::ReadInOwnInitializer,
+        ErrorCode::DuplicateLocal,
+        ErrorCode::UndefinedVariable,
+        ErrorCode::UnassignedVariable,
+        ErrorCode::RedeclaredGlobal,
+        ErrorCode::UnreadLocal,
+        ErrorCode::InvalidOperands,
+        ErrorCode::ShiftOutOfRange,
+        ErrorCode::NotANumber,
+        ErrorCode::NotCallable,
+        ErrorCode::WrongArgumentCount,
+        ErrorCode::NotAnInstance,
+        ErrorCode::NonBooleanCondition,
+        ErrorCode::StackOverflow,
+        ErrorCode::BudgetExceeded,
+        ErrorCode::Io,
+        ErrorCode::InvalidArgument,
+        ErrorCode::AssertionFailed,
+        ErrorCode::Conversion,
+        ErrorCode::IndexOutOfBounds,
+        ErrorCode::MissingKey,
+        ErrorCode::UndefinedProperty,
+        ErrorCode::InvalidSuperclass,
+        ErrorCode::FormatString,
+        ErrorCode::IllegalOperator,
+    ];
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedCharacter => "E0001",
+            ErrorCode::UnterminatedString => "E0002",
+            ErrorCode::UnterminatedRawString => "E0003",
+            ErrorCode::UnterminatedHeredoc => "E0004",
+            ErrorCode::HeredocDelimiter => "E0005",
+            ErrorCode::InvalidNumber => "E0006",
+            ErrorCode::ExpectedExpression => "E1001",
+            ErrorCode::ExpectedToken => "E1002",
+            ErrorCode::TrailingInput => "E1003",
+            ErrorCode::InvalidAssignmentTarget => "E1004",
+            ErrorCode::AssignmentInCondition => "E1005",
+            ErrorCode::TooManyArguments => "E1006",
+            ErrorCode::ContinueOutsideLoop => "E1007",
+            ErrorCode::ReturnOutsideFunction => "E1008",
+            ErrorCode::OutsideClass => "E1009",
+            // E1010 was for syntax that wasn't supported yet, and is retired.
+            ErrorCode::ReturnFromInitializer => "E1011",
+            ErrorCode::NoSuperclass => "E1012",
+            ErrorCode::InheritsFromItself => "E1013",
+            ErrorCode::ReadInOwnInitializer => "E2001",
+            ErrorCode::DuplicateLocal => "E2002",
+            ErrorCode::UndefinedVariable => "E2003",
+            ErrorCode::UnassignedVariable => "E2004",
+            ErrorCode::RedeclaredGlobal => "E2005",
+            ErrorCode::UnreadLocal => "E2006",
+            ErrorCode::InvalidOperands => "E3001",
+            ErrorCode::ShiftOutOfRange => "E3002",
+            ErrorCode::NotANumber => "E3003",
+            ErrorCode::NotCallable => "E3004",
+            ErrorCode::WrongArgumentCount => "E3005",
+            ErrorCode::NotAnInstance => "E3006",
+            ErrorCode::NonBooleanCondition => "E3007",
+            ErrorCode::StackOverflow => "E3008",
+            ErrorCode::BudgetExceeded => "E3009",
+            ErrorCode::Io => "E3010",
+            ErrorCode::InvalidArgument => "E3011",
+            ErrorCode::AssertionFailed => "E3012",
+            ErrorCode::Conversion => "E3013",
+            ErrorCode::IndexOutOfBounds => "E3014",
+            ErrorCode::MissingKey => "E3015",
+            ErrorCode::UndefinedProperty => "E3016",
+            ErrorCode::InvalidSuperclass => "E3017",
+            ErrorCode::FormatString => "E3018",
+            ErrorCode::IllegalOperator => "E3019",
+        }
+    }
+    /// Case doesn't matter, so `e2003` is found too.
+    pub fn from_code(code: &str) -> Option<ErrorCode> {
+        ErrorCode::ALL
+            .iter()
+            .find(|candidate| candidate.as_str().eq_ignore_ascii_case(code))
+            .copied()
+    }
+    /// A longer description than fits in an error message, of what usually causes it and what to
+    /// do about it.
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            ErrorCode::UnexpectedCharacter => {
+                "A character was found that isn't part of any token, like `@` or `$` outside of a \
+                 string. Either remove it, or if it was meant to be text, put it in a string."
+            }
+            ErrorCode::UnterminatedString => {
+                "A string was started with `\"` but the file ended before the closing `\"`. \
+                 Strings can run over several lines, so the missing quote may be far from where \
+                 the string starts."
+            }
+            ErrorCode::UnterminatedRawString => {
+                "A raw string like `r\"...\"` or `r#\"...\"#` was never closed. A raw string \
+                 started with some number of `#`s only ends at a quote followed by the same \
+                 number of `#`s."
+            }
+            ErrorCode::UnterminatedHeredoc => {
+                "A heredoc was started but the file ended before a line holding only its closing \
+                 delimiter."
+            }
+            ErrorCode::HeredocDelimiter => {
+                "A heredoc's opening delimiter has to be the last thing on its line, since the \
+                 text of the heredoc starts on the next one."
+            }
+            ErrorCode::InvalidNumber => {
+                "A number literal couldn't be read. This covers digits that don't belong to the \
+                 literal's base (like `0b102`), a base prefix with no digits after it, digit \
+                 separators anywhere but between two digits, and whole numbers too large to \
+                 represent."
+            }
+            ErrorCode::ExpectedExpression => {
+                "Something that produces a value (like a number, a variable or a call) was \
+                 expected, but something else was found, or the input ended. This is often a \
+                 missing operand, like in `1 +;`, or a keyword used where a value should be."
+            }
+            ErrorCode::ExpectedToken => {
+                "A particular token was required at this point, most often the `;` that ends a \
+                 statement or a closing bracket. The error is reported at whatever was found \
+                 instead, so the missing token usually belongs just before it."
+            }
+            ErrorCode::TrailingInput => {
+                "An expression was read completely, but there was more input after it. At the \
+                 prompt this usually means a statement was typed without its `;`."
+            }
+            ErrorCode::InvalidAssignmentTarget => {
+                "The left side of an `=` has to be a variable or a property, like `a = 1` or \
+                 `a.b = 1`. Anything else, like `1 = a` or `a + b = c`, can't be assigned to."
+            }
+            ErrorCode::AssignmentInCondition => {
+                "A condition is an assignment, like `if (a = b)`, which is most often a typo for \
+                 `==`. If the assignment is on purpose, wrap it in another set of parentheses, \
+                 `if ((a = b))`, to say so."
+            }
+            ErrorCode::TooManyArguments => {
+                "A call can't be given more than 255 arguments, and a function can't declare more \
+                 than 255 parameters."
+            }
+            ErrorCode::ContinueOutsideLoop => {
+                "`continue` skips to the next iteration of a loop, so it can only be used inside \
+                 one."
+            }
+            ErrorCode::ReturnOutsideFunction => {
+                "`return` can only be used inside a function. To stop a whole script early, call \
+                 `exit()`."
+            }
+            ErrorCode::OutsideClass => {
+                "`this` and `super` refer to the instance a method was called on, so they only \
+                 mean anything inside a class."
+            }
+            ErrorCode::ReturnFromInitializer => {
+                "An `init` method always gives back the new instance, so it can't return anything \
+                 else. A bare `return;` is fine, to finish initializing early."
+            }
+            ErrorCode::NoSuperclass => {
+                "`super` refers to the superclass of the class it's in, so it can't be used in a \
+                 class declared without one, like `class A < B { ... }`."
+            }
+            ErrorCode::InheritsFromItself => {
+                "A class was declared with itself as its superclass, like `class A < A {}`."
+            }
+            ErrorCode::ReadInOwnInitializer => {
+                "A local variable was used in its own initializer, like `var a = a;`. Inside a \
+                 block that would read the new variable before it has a value, rather than any \
+                 outer variable with the same name. Give one of the two a different name."
+            }
+            ErrorCode::DuplicateLocal => {
+                "Two local variables in the same scope have the same name. Unlike globals, locals \
+                 can't be redeclared, only shadowed in an inner block."
+            }
+            ErrorCode::UndefinedVariable => {
+                "A variable was used (or assigned to) that hasn't been declared with `var`. Check \
+                 the spelling, and that the declaration runs before the use."
+            }
+            ErrorCode::UnassignedVariable => {
+                "A variable was declared without an initializer, like `var a;`, and read before \
+                 anything was assigned to it. Give it a value first, even if it's `nil`."
+            }
+            ErrorCode::RedeclaredGlobal => {
+                "A global variable was declared twice in the same script. This is allowed, the \
+                 second declaration replaces the first, but it's usually a mistake. Assign to the \
+                 variable instead if that's what was meant."
+            }
+            ErrorCode::UnreadLocal => {
+                "A local variable is never read, so it may as well not be there. If that's on \
+                 purpose, start its name with an underscore."
+            }
+            ErrorCode::InvalidOperands => {
+                "An operator was used on values it doesn't work on, like `nil + 1` or `-\"a\"`. \
+                 The message says which types the operator takes. Use `num()` or `str()` to \
+                 convert values where needed."
+            }
+            ErrorCode::ShiftOutOfRange => {
+                "Numbers are shifted as 64 bit integers, so shift amounts have to be from 0 to \
+                 63."
+            }
+            ErrorCode::NotANumber => {
+                "Arithmetic produced NaN, like `0 / 0` or the square root of a negative number. \
+                 This is only an error with `--strict-nan` (or always, for natives that can't \
+                 produce anything sensible)."
+            }
+            ErrorCode::NotCallable => {
+                "Only functions and classes can be called. The value before the `(` was \
+                 something else."
+            }
+            ErrorCode::WrongArgumentCount => {
+                "A function was called with a different number of arguments than it takes."
+            }
+            ErrorCode::NotAnInstance => {
+                "Properties can only be read from and written to instances of classes. The value \
+                 before the `.` was something else."
+            }
+            ErrorCode::NonBooleanCondition => {
+                "The condition of a `?:` has to be `true` or `false`, other values aren't \
+                 converted."
+            }
+            ErrorCode::StackOverflow => {
+                "Calls were nested deeper than the interpreter allows, which is usually \
+                 recursion that never stops."
+            }
+            ErrorCode::BudgetExceeded => {
+                "The script ran for more steps than `--max-steps` allows, or for longer than \
+                 `--timeout-ms` does. Raise the limit, or check for a loop that never ends."
+            }
+            ErrorCode::Io => {
+                "Reading input or writing output failed, like when the output is piped to a \
+                 program that has already exited."
+            }
+            ErrorCode::InvalidArgument => {
+                "A native function was given an argument it can't use. The message says what it \
+                 expected."
+            }
+            ErrorCode::AssertionFailed => {
+                "`assert()` was given something false. The message includes the second argument \
+                 to `assert()`, if one was given."
+            }
+            ErrorCode::Conversion => {
+                "A value couldn't be converted between lox and JSON, since not everything in one \
+                 has an equivalent in the other."
+            }
+            ErrorCode::IndexOutOfBounds => {
+                "A list or tuple was indexed past its end (or before its start, or with something \
+                 other than a whole number), a substring didn't fit inside its string, or a list \
+                 was popped when it was already empty. Indexes start at 0, so the last element of \
+                 a list is at `listLength(list) - 1`."
+            }
+            ErrorCode::MissingKey => {
+                "A key was looked up in a map that doesn't have it. Use `mapHas(map, key)` to check \
+                 first if the key might not be there."
+            }
+            ErrorCode::UndefinedProperty => {
+                "An instance was asked for a property that it has no field or method for. Fields \
+                 only exist once they've been assigned, usually in `init`."
+            }
+            ErrorCode::InvalidSuperclass => {
+                "A class was declared to inherit from something that isn't a class."
+            }
+            ErrorCode::FormatString => {
+                "A string was formatted with `%`, but the values didn't match it. Each `%s` is \
+                 filled with one value, so there have to be exactly as many values as there are \
+                 `%s`s, given as a tuple if there's more than one, like `\"%s of %s\" % (1, 2)`. \
+                 The only other thing that can follow a `%` is another `%`, for a literal percent \
+                 sign."
+            }
+            ErrorCode::IllegalOperator => {
+                "An operator reached the interpreter that it doesn't know how to apply. The parser \
+                 should never produce one, so this is a bug in the interpreter rather than in the \
+                 script."
+            }
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_unique_and_found_by_name() {
+        for (index, code) in ErrorCode::ALL.iter().enumerate() {
+            assert_eq!(ErrorCode::from_code(code.as_str()), Some(*code));
+            assert!(ErrorCode::ALL[..index]
+                .iter()
+                .all(|earlier| earlier.as_str() != code.as_str()));
+        }
+    }
+
+    #[test]
+    fn retired_codes_are_not_found() {
+        assert_eq!(ErrorCode::from_code("E1010"), None);
+    }
+}
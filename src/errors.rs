@@ -1,147 +1,820 @@
 use std::fmt;
-use std::process;
+use unicode_segmentation::UnicodeSegmentation;
 
+use crate::error_codes::ErrorCode;
+use crate::scanner::USE_EXTENDED_UNICODE;
 use crate::source_file;
 
+#[derive(Debug, Clone)]
 pub struct ErrorDescription {
     pub subject: Option<String>,
     pub location: Option<source_file::SourceSpan>,
     pub description: String,
 }
 
-// impl fmt::Display for ErrorDescription {
-//     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-//         let ErrorDescription {
-//             subject,
-//             location,
-//             description,
-//         } = self;
-//         if let Some(subject_value) = subject {
-//             write!(
-//                 f,
-//                 "[line: {}, col: {}] Error ({}): {}",
-//                 location.start.line, location.start.column, description, subject_value
-//             )
-//         } else {
-//             write!(
-//                 f,
-//                 "[line: {}, col: {}] Error ({})",
-//                 location.start.line, location.start.column, description
-//             )
-//         }
-//     }
-// }
-
+#[derive(Debug, Clone)]
 pub enum ErrorKind {
     Scanning,
     Parsing,
+    /// Mistakes that are only found once the whole program has been parsed, like how variables
+    /// are used.
+    Resolution,
     Runtime,
+    /// Not really an error, but the script asking to stop with `exit()`. It unwinds the same way
+    /// errors do, so that whoever's running the script gets to decide what stopping means. The
+    /// command line exits with the code, while the prompt carries on.
+    Exit(exitcode::ExitCode),
+}
+
+/// Warnings are reported alongside errors, but never stop execution.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A call that was in progress, named after what was called and placed at the call site.
+#[derive(Debug, Clone)]
+pub struct CallFrame {
+    pub name: String,
+    pub location: source_file::SourceSpan,
 }
 
+const MAX_TRACE_FRAMES: usize = 16;
+
+/// Another place in the source that has something to do with an error, like where a variable was
+/// first declared when it's declared again.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub location: source_file::SourceSpan,
+    pub message: String,
+}
+
+/// Everything that can go wrong with a script, from scanning it through to running it. It's a
+/// normal Rust error too, so it can be passed up with `?` alongside io errors and the like (as a
+/// `Box<dyn std::error::Error>`, say), though that loses the source it's shown against.
+#[derive(Debug, Clone)]
 pub struct Error {
     pub kind: ErrorKind,
+    pub severity: Severity,
+    /// Which diagnostic this is, for everything but errors that can't be helped (like exits) or
+    /// shouldn't happen at all.
+    pub code: Option<ErrorCode>,
+    /// The calls that led to a runtime error, outermost first. Empty for everything else, and for
+    /// runtime errors outside of any call.
+    pub trace: Vec<CallFrame>,
+    /// Shown after the error, each with its own excerpt of the source.
+    pub labels: Vec<Label>,
+    /// Anything else worth knowing, that isn't about anywhere in particular.
+    pub notes: Vec<String>,
+    /// Whether the parser had already run out of tokens when it found this, which after a scanning
+    /// error usually means the scanner swallowed the rest of the source.
+    pub at_end_of_input: bool,
     pub description: ErrorDescription,
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let kind_string = match self.kind {
-            ErrorKind::Scanning | ErrorKind::Parsing => String::from("Syntax"),
-            ErrorKind::Runtime => String::from("Runtime"),
-        };
-
-        let location_string = if let Some(location_value) = self.description.location {
-            format!(
+        if let Some(location_value) = self.description.location {
+            write!(
+                f,
                 "[line: {}, col: {}] ",
                 location_value.start.line, location_value.start.column
-            )
-        } else {
-            String::from("")
+            )?;
+        }
+        self.fmt_message(f, None)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Displays an error as coming from a particular file, in the `file:line:column:` form that editors
+/// and terminals know how to jump to.
+pub struct InFile<'a> {
+    pub error: &'a Error,
+    pub file_name: &'a str,
+}
+
+impl fmt::Display for InFile<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file_name)?;
+        if let Some(location_value) = self.error.description.location {
+            write!(
+                f,
+                ":{}:{}",
+                location_value.start.line, location_value.start.column
+            )?;
+        }
+        write!(f, ": ")?;
+        self.error.fmt_message(f, Some(self.file_name))
+    }
+}
+
+/// Displays an error along with the line of source it's on, with what it's about underlined:
+///
+/// ```text
+/// script.lox:3:12: error[E1001]: Expected value or expression, found ';'
+///   |
+/// 3 | var a = 1 +;
+///   |            ^
+/// ```
+///
+/// Errors without a location, or whose location isn't in the source, are displayed the same as
+/// they would be otherwise.
+pub struct InSource<'a> {
+    pub error: &'a Error,
+    pub file: &'a source_file::SourceFile,
+    /// Whether to color the severity and underline by how bad it is, and dim the gutter. Only for
+    /// terminals, anywhere else the escape codes just get in the way.
+    pub color: bool,
+}
+
+impl fmt::Display for InSource<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let location = self.error.description.location;
+        match (&self.file.name, location) {
+            (Some(file_name), Some(location)) => write!(
+                f,
+                "{}:{}:{}: ",
+                file_name, location.start.line, location.start.column
+            )?,
+            (Some(file_name), None) => write!(f, "{}: ", file_name)?,
+            (None, Some(location)) => write!(
+                f,
+                "[line: {}, col: {}] ",
+                location.start.line, location.start.column
+            )?,
+            (None, None) => {}
+        }
+        self.error.fmt_headline(f, self.color)?;
+        if let Some(location) = location {
+            let style = self.color.then(|| self.error.severity.style());
+            fmt_excerpt(f, self.file, location, Underline::Primary, style)?;
+        }
+        for label in self.error.labels.iter() {
+            let underline = Underline::Secondary(&label.message);
+            fmt_excerpt(
+                f,
+                self.file,
+                label.location,
+                underline,
+                self.color.then_some(BLUE),
+            )?;
+        }
+        for note in self.error.notes.iter() {
+            write!(f, "\n  = note: {}", note)?;
+        }
+        self.error.fmt_trace(f, self.file.name.as_deref())
+    }
+}
+
+// What's under the error itself is marked with `^~~~`, and anything else with `----` and what it
+// is, the same as rustc does it.
+enum Underline<'a> {
+    Primary,
+    Secondary(&'a str),
+}
+
+// The gutter is as wide as the line number. Columns count graphemes, the same as the scanner does,
+// and any tabs before the span are kept as tabs in the underline so that it lines up however wide
+// they're shown. A span running onto later lines is underlined to the end of its first line, with
+// a marker saying that it carries on.
+fn fmt_excerpt(
+    f: &mut fmt::Formatter<'_>,
+    file: &source_file::SourceFile,
+    location: source_file::SourceSpan,
+    kind: Underline,
+    style: Option<&str>,
+) -> fmt::Result {
+    let line_number = location.start.line;
+    let line = match file.line(line_number) {
+        Some(line) => line,
+        None => return Ok(()),
+    };
+    let graphemes: Vec<&str> = line.graphemes(USE_EXTENDED_UNICODE).collect();
+    let start = location.start.column.saturating_sub(1);
+    let padding: String = (0..start)
+        .map(|index| match graphemes.get(index) {
+            Some(&"\t") => '\t',
+            _ => ' ',
+        })
+        .collect();
+    let (width, continues) = if location.end.line == line_number {
+        (
+            location.end.column.saturating_sub(location.start.column),
+            false,
+        )
+    } else {
+        (graphemes.len().saturating_sub(start), true)
+    };
+    // Even an empty span (like the end of the file) gets a caret, to show where it is.
+    let mut underline = match kind {
+        Underline::Primary => format!("^{}", "~".repeat(width.saturating_sub(1))),
+        Underline::Secondary(_) => "-".repeat(width.max(1)),
+    };
+    if continues {
+        underline.push_str(" ...");
+    }
+    if let Underline::Secondary(message) = kind {
+        underline.push_str(&format!(" {}", message));
+    }
+    let gutter = " ".repeat(line_number.to_string().len());
+    let dim = style.map(|_| DIM);
+    write!(
+        f,
+        "\n{}\n{} {}\n{} {}{}",
+        paint(&format!("{} |", gutter), dim),
+        paint(&format!("{} |", line_number), dim),
+        line,
+        paint(&format!("{} |", gutter), dim),
+        padding,
+        paint(&underline, style)
+    )
+}
+
+// --- Color ---
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+// Nothing's added without a style, so that plain and colored output can share the same code.
+fn paint(text: &str, style: Option<&str>) -> String {
+    match style {
+        Some(style) => format!("{}{}{}", style, text, RESET),
+        None => text.to_string(),
+    }
+}
+
+impl Severity {
+    fn style(&self) -> &'static str {
+        match self {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        }
+    }
+}
+
+impl Error {
+    // --- Constructors ---
+    /// An error at a place in the source, or nowhere in particular if there's no location (like
+    /// errors from natives, which are placed at the call later).
+    pub fn new(
+        kind: ErrorKind,
+        location: Option<source_file::SourceSpan>,
+        description: impl Into<String>,
+    ) -> Self {
+        Error {
+            kind,
+            severity: Severity::Error,
+            code: None,
+            trace: Vec::new(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+            at_end_of_input: false,
+            description: ErrorDescription {
+                subject: None,
+                location,
+                description: description.into(),
+            },
+        }
+    }
+    pub fn scanning(location: source_file::SourceSpan, description: impl Into<String>) -> Self {
+        Error::new(ErrorKind::Scanning, Some(location), description)
+    }
+    pub fn parsing(location: source_file::SourceSpan, description: impl Into<String>) -> Self {
+        Error::new(ErrorKind::Parsing, Some(location), description)
+    }
+    pub fn resolution(location: source_file::SourceSpan, description: impl Into<String>) -> Self {
+        Error::new(ErrorKind::Resolution, Some(location), description)
+    }
+    pub fn runtime(location: source_file::SourceSpan, description: impl Into<String>) -> Self {
+        Error::new(ErrorKind::Runtime, Some(location), description)
+    }
+    /// What the error is about, like the name of the variable that doesn't exist.
+    pub fn with_subject(mut self, subject: impl Into<String>) -> Self {
+        self.description.subject = Some(subject.into());
+        self
+    }
+    pub fn with_label(
+        mut self,
+        location: source_file::SourceSpan,
+        message: impl Into<String>,
+    ) -> Self {
+        self.labels.push(Label {
+            location,
+            message: message.into(),
+        });
+        self
+    }
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+    pub fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = Some(code);
+        self
+    }
+    pub fn as_warning(mut self) -> Self {
+        self.severity = Severity::Warning;
+        self
+    }
+    pub fn as_end_of_input(mut self) -> Self {
+        self.at_end_of_input = true;
+        self
+    }
+    // --- Accessors ---
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+    pub fn code(&self) -> Option<ErrorCode> {
+        self.code
+    }
+    /// Where in the source the error is, if anywhere.
+    pub fn span(&self) -> Option<source_file::SourceSpan> {
+        self.description.location
+    }
+    /// The description on its own, without the kind, code or subject around it.
+    pub fn message(&self) -> &str {
+        &self.description.description
+    }
+    pub fn subject(&self) -> Option<&str> {
+        self.description.subject.as_deref()
+    }
+    // --- Reporting ---
+    /// The code the script asked to exit with, if this is an exit rather than an actual error.
+    pub fn exit_code(&self) -> Option<exitcode::ExitCode> {
+        match self.kind {
+            ErrorKind::Exit(code) => Some(code),
+            _ => None,
+        }
+    }
+    // Only what's shown counts, so the same message with different notes is still different.
+    fn is_duplicate_of(&self, other: &Error) -> bool {
+        let spans_match = match (self.description.location, other.description.location) {
+            (Some(a), Some(b)) => a.start == b.start && a.end == b.end,
+            (None, None) => true,
+            _ => false,
+        };
+        spans_match
+            && self.code == other.code
+            && self.severity == other.severity
+            && self.description.subject == other.description.subject
+            && self.description.description == other.description.description
+            && self.notes == other.notes
+    }
+    // Everything but the location, which goes in front in different ways.
+    fn fmt_message(&self, f: &mut fmt::Formatter<'_>, file_name: Option<&str>) -> fmt::Result {
+        self.fmt_headline(f, false)?;
+        // Without the source to quote, labels can only say where they are.
+        for label in self.labels.iter() {
+            write!(
+                f,
+                "\n    {} (line {}, col {})",
+                label.message, label.location.start.line, label.location.start.column
+            )?;
+        }
+        for note in self.notes.iter() {
+            write!(f, "\n    note: {}", note)?;
+        }
+        self.fmt_trace(f, file_name)
+    }
+    // The same as rustc's, like `error[E2003]: Undefined variable 'x'`. The kind isn't shown, the
+    // code already says which stage it came from, and it's there in the JSON for anything that
+    // needs it.
+    fn fmt_headline(&self, f: &mut fmt::Formatter<'_>, color: bool) -> fmt::Result {
+        let severity_string = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+
+        let code_string = match self.code {
+            Some(code) => format!("[{}]", code),
+            None => String::new(),
         };
 
         let subject_string = if let Some(subject_value) = &self.description.subject {
-            format!(": {}", subject_value)
+            format!(" '{}'", subject_value)
         } else {
             String::from("")
         };
 
         write!(
             f,
-            "{}{} Error ({}){}",
-            location_string, kind_string, self.description.description, subject_string
+            "{}: {}{}",
+            paint(
+                &format!("{}{}", severity_string, code_string),
+                color.then(|| self.severity.style())
+            ),
+            self.description.description,
+            subject_string
         )
     }
+    // The innermost call comes first, right under the error it caused, and the calls that led
+    // there follow it outwards. Only so many are shown, since past a point (like with runaway
+    // recursion) the rest are all the same and just bury the error. With a file to name, each call
+    // is given in the same `file:line:column` form as the error itself, so it can be jumped to.
+    fn fmt_trace(&self, f: &mut fmt::Formatter<'_>, file_name: Option<&str>) -> fmt::Result {
+        for frame in self.trace.iter().rev().take(MAX_TRACE_FRAMES) {
+            let start = frame.location.start;
+            match file_name {
+                Some(file_name) => write!(
+                    f,
+                    "\n    in {}, called from {}:{}:{}",
+                    frame.name, file_name, start.line, start.column
+                )?,
+                None => write!(
+                    f,
+                    "\n    in {}, called from line {}",
+                    frame.name, start.line
+                )?,
+            }
+        }
+        if self.trace.len() > MAX_TRACE_FRAMES {
+            write!(
+                f,
+                "\n    ... and {} more",
+                self.trace.len() - MAX_TRACE_FRAMES
+            )?;
+        }
+        Ok(())
+    }
+    /// For tools rather than people, with the kind and severity spelled out separately. The
+    /// location, subject and trace are left out when there isn't one.
+    pub fn to_json(&self) -> String {
+        format!("{{{}}}", self.json_fields().join(", "))
+    }
+    /// The same as `to_json()`, with the name of the file (if it has one) and where the error is
+    /// as byte offsets into it, which is what editors tend to want.
+    pub fn to_json_in(&self, file: &source_file::SourceFile) -> String {
+        let mut fields = self.json_fields();
+        if let Some(name) = &file.name {
+            fields.insert(2, format!("\"file\": {}", json_string(name)));
+        }
+        if let Some(location) = self.description.location {
+            if let (Some(offset), Some(end_offset)) = (
+                file.byte_offset(location.start),
+                file.byte_offset(location.end),
+            ) {
+                fields.push(format!("\"offset\": {}", offset));
+                fields.push(format!("\"end_offset\": {}", end_offset));
+            }
+        }
+        format!("{{{}}}", fields.join(", "))
+    }
+    fn json_fields(&self) -> Vec<String> {
+        let kind_string = match self.kind {
+            ErrorKind::Scanning => "scanning",
+            ErrorKind::Parsing => "parsing",
+            ErrorKind::Resolution => "resolution",
+            ErrorKind::Runtime => "runtime",
+            ErrorKind::Exit(_) => "exit",
+        };
+        let severity_string = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let mut fields = vec![
+            format!("\"kind\": {}", json_string(kind_string)),
+            format!("\"severity\": {}", json_string(severity_string)),
+        ];
+        if let Some(code) = self.code {
+            fields.push(format!("\"code\": {}", json_string(code.as_str())));
+        }
+        if let Some(location) = self.description.location {
+            fields.push(format!("\"line\": {}", location.start.line));
+            fields.push(format!("\"column\": {}", location.start.column));
+            fields.push(format!("\"end_line\": {}", location.end.line));
+            fields.push(format!("\"end_column\": {}", location.end.column));
+        }
+        fields.push(format!(
+            "\"description\": {}",
+            json_string(&self.description.description)
+        ));
+        if let Some(subject) = &self.description.subject {
+            fields.push(format!("\"subject\": {}", json_string(subject)));
+        }
+        if !self.labels.is_empty() {
+            let labels: Vec<String> = self
+                .labels
+                .iter()
+                .map(|label| {
+                    format!(
+                        "{{\"line\": {}, \"column\": {}, \"end_line\": {}, \"end_column\": {}, \
+                         \"message\": {}}}",
+                        label.location.start.line,
+                        label.location.start.column,
+                        label.location.end.line,
+                        label.location.end.column,
+                        json_string(&label.message)
+                    )
+                })
+                .collect();
+            fields.push(format!("\"labels\": [{}]", labels.join(", ")));
+        }
+        if !self.notes.is_empty() {
+            let notes: Vec<String> = self.notes.iter().map(|note| json_string(note)).collect();
+            fields.push(format!("\"notes\": [{}]", notes.join(", ")));
+        }
+        if !self.trace.is_empty() {
+            let frames: Vec<String> = self
+                .trace
+                .iter()
+                .map(|frame| {
+                    format!(
+                        "{{\"name\": {}, \"line\": {}}}",
+                        json_string(&frame.name),
+                        frame.location.start.line
+                    )
+                })
+                .collect();
+            fields.push(format!("\"trace\": [{}]", frames.join(", ")));
+        }
+        fields
+    }
 }
-// pub enum Error {
-//     Scanning(ErrorDescription),
-//     Parsing(ErrorDescription),
-// }
 
-// impl fmt::Display for Error {
-//     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-//         let Error::Syntax(description) = self;
-// 		match self {
-// 			Error::Scanning(description) => write!(f, "{}", description)
-// 		}
-//     }
-// }
+/// Quotes and escapes a string for JSON.
+pub fn json_string(string: &str) -> String {
+    let mut ret = String::from("\"");
+    for character in string.chars() {
+        match character {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => ret.push_str("\\r"),
+            '\t' => ret.push_str("\\t"),
+            control if control.is_control() => ret.push_str(&format!("\\u{:04x}", control as u32)),
+            _ => ret.push(character),
+        }
+    }
+    ret.push('"');
+    ret
+}
 
+#[derive(Debug, Clone)]
 pub struct ErrorLog {
     pub errors: Vec<Error>,
+    /// The source the errors are in, so that they can be shown with it. Left out if it isn't
+    /// around any more, in which case the errors are shown on their own.
+    pub source: Option<source_file::SourceFile>,
+    pub style: ErrorStyle,
+}
+
+/// How an `ErrorLog` is printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ErrorStyle {
+    #[default]
+    Plain,
+    /// Only along with the source, otherwise it's the same as plain.
+    Colored,
+    /// One JSON object per line, for editors and other tools to read.
+    Json,
 }
 
 impl ErrorLog {
     pub fn new() -> Self {
-        ErrorLog { errors: Vec::new() }
-    }
-    // pub fn log(
-    //     &mut self,
-    //     location: source_file::SourceSpan,
-    //     subject: &str,
-    //     description: &str,
-    // ) -> &Self {
-    //     self.errors.push(ErrorDescription {
-    //         subject: Some(String::from(subject)),
-    //         location,
-    //         description: String::from(description),
-    //     });
-    //     self
-    // }
+        ErrorLog {
+            errors: Vec::new(),
+            source: None,
+            style: ErrorStyle::Plain,
+        }
+    }
     pub fn push(&mut self, error: Error) {
         self.errors.push(error);
     }
+    /// Puts the errors in the order they appear in the source, rather than the order they were
+    /// found in. Anything without a location goes at the end, and errors at the same place keep
+    /// their order.
+    pub fn sort_by_location(&mut self) {
+        self.errors
+            .sort_by_key(|error| match error.description.location {
+                Some(location) => (false, Some(location.start)),
+                None => (true, None),
+            });
+    }
+    /// Drops parsing errors that are most likely only there because of a scanning error before
+    /// them. Whatever the scanner couldn't make sense of never becomes a token, so the parser
+    /// usually trips over the gap it leaves (like `var a = @;` being missing its value too). That's
+    /// taken to be any parsing error starting inside a scanning error, or later on the line it ends
+    /// on.
+    ///
+    /// Something unterminated, like the string in `print "abc;`, swallows the rest of the source,
+    /// so the parser runs out of tokens and reports that at the last one it had, which comes before
+    /// the scanning error. Errors from running out are dropped too if a scanning error comes after
+    /// where they're placed, or if they aren't placed anywhere.
+    pub fn remove_cascading_errors(&mut self) {
+        let scanning_spans: Vec<source_file::SourceSpan> = self
+            .errors
+            .iter()
+            .filter(|error| matches!(error.kind, ErrorKind::Scanning))
+            .filter_map(|error| error.description.location)
+            .collect();
+        self.errors.retain(|error| {
+            if !matches!(
+                (&error.kind, &error.severity),
+                (ErrorKind::Parsing, Severity::Error)
+            ) {
+                return true;
+            }
+            match error.description.location {
+                Some(location) => !scanning_spans.iter().any(|span| {
+                    (span.start <= location.start && location.start.line <= span.end.line)
+                        || (error.at_end_of_input && location.start <= span.start)
+                }),
+                None => !error.at_end_of_input || scanning_spans.is_empty(),
+            }
+        });
+    }
+    /// Drops errors that say exactly the same thing about exactly the same place as one before them,
+    /// which happens when recovering from one error runs into the same problem again.
+    pub fn remove_duplicates(&mut self) {
+        let mut kept: Vec<Error> = Vec::with_capacity(self.errors.len());
+        for error in self.errors.drain(..) {
+            if !kept.iter().any(|earlier| earlier.is_duplicate_of(&error)) {
+                kept.push(error);
+            }
+        }
+        self.errors = kept;
+    }
+    /// Turns every warning into an error, for when warnings shouldn't be let slide.
+    pub fn promote_warnings(&mut self) {
+        for error in self.errors.iter_mut() {
+            error.severity = Severity::Error;
+        }
+    }
+    /// Copies everything from another log onto the end of this one.
+    pub fn append(&mut self, other: &ErrorLog) {
+        self.extend(other.iter().cloned());
+    }
+    /// The number of logged errors, including warnings.
     pub fn len(&self) -> usize {
         self.errors.len()
     }
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+    pub fn iter(&self) -> std::slice::Iter<'_, Error> {
+        self.errors.iter()
+    }
+    /// The number of logged errors, excluding warnings.
+    pub fn error_count(&self) -> usize {
+        self.errors
+            .iter()
+            .filter(|error| error.severity == Severity::Error)
+            .count()
+    }
+    pub fn warning_count(&self) -> usize {
+        self.errors
+            .iter()
+            .filter(|error| error.severity == Severity::Warning)
+            .count()
+    }
+    /// A line like "3 errors, 1 warning emitted", or nothing for an empty log.
+    pub fn summary(&self) -> Option<String> {
+        let counts = [
+            (self.error_count(), "error"),
+            (self.warning_count(), "warning"),
+        ];
+        let parts: Vec<String> = counts
+            .iter()
+            .filter(|(count, _)| *count > 0)
+            .map(|(count, noun)| match count {
+                1 => format!("1 {}", noun),
+                _ => format!("{} {}s", count, noun),
+            })
+            .collect();
+        if parts.is_empty() {
+            return None;
+        }
+        Some(format!("{} emitted", parts.join(", ")))
+    }
 }
 
-// Should this really be implemented as an actual `fmt::Display`?
-// impl fmt::Display for ErrorLog {
-//     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-//         let mut result = String::new();
-//         for error in self.errors.iter() {
-//             result.push_str(&format!("{}\n", error.to_string()).to_string());
-//         }
-//         write!(f, "{}", result)
-//     }
-// }
+impl Extend<Error> for ErrorLog {
+    fn extend<T: IntoIterator<Item = Error>>(&mut self, iter: T) {
+        self.errors.extend(iter);
+    }
+}
+
+impl<'a> IntoIterator for &'a ErrorLog {
+    type Item = &'a Error;
+    type IntoIter = std::slice::Iter<'a, Error>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for ErrorLog {
+    type Item = Error;
+    type IntoIter = std::vec::IntoIter<Error>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.into_iter()
+    }
+}
+
+/// One error after another, against the source if it's there, but never colored or as json, and
+/// without the summary that's printed after them. See `print_error_log()` for that.
+impl fmt::Display for ErrorLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, error) in self.errors.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            match &self.source {
+                Some(file) => write!(
+                    f,
+                    "{}",
+                    InSource {
+                        error,
+                        file,
+                        color: false,
+                    }
+                )?,
+                None => write!(f, "{}", error)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// So that a whole log can be returned as an error, like from `run_source()`.
+impl std::error::Error for ErrorLog {}
+
+impl Default for ErrorLog {
+    fn default() -> Self {
+        ErrorLog::new()
+    }
+}
 
 pub trait ErrorLoggable {
     fn error_log(&self) -> &ErrorLog;
 }
 
+impl ErrorLoggable for ErrorLog {
+    fn error_log(&self) -> &ErrorLog {
+        self
+    }
+}
+
+/// Following the book, errors in the script itself (scanning, parsing and resolution) exit with 65,
+/// and errors while running it exit with 70. Warnings don't count.
+pub fn exit_code_for(log: &ErrorLog) -> exitcode::ExitCode {
+    let mut code = exitcode::OK;
+    for error in log {
+        if error.severity == Severity::Warning {
+            continue;
+        }
+        match error.kind {
+            ErrorKind::Runtime => return exitcode::SOFTWARE,
+            ErrorKind::Exit(code) => return code,
+            ErrorKind::Scanning | ErrorKind::Parsing | ErrorKind::Resolution => {
+                code = exitcode::DATAERR
+            }
+        }
+    }
+    code
+}
+
+#[cfg(not(feature = "wasm"))]
 pub fn exit_with_code(code: exitcode::ExitCode) {
-    process::exit(code);
+    std::process::exit(code);
 }
 
-// pub fn exit_on_error(code: exitcode::ExitCode, error_log: &ErrorLog) {
-//     println!("{}", error_log);
-//     exit_with_code(code);
-// }
+// There's no process to exit in the browser, so it's up to whoever's embedding the interpreter to
+// stop when there's an error. This goes by the feature rather than the target, so the command line
+// has to be built without it.
+#[cfg(feature = "wasm")]
+pub fn exit_with_code(_code: exitcode::ExitCode) {}
 
+// Printed to stderr, so that they don't end up mixed into whatever a script prints when its output
+// is being piped somewhere.
 pub fn print_error_log(log: &ErrorLog) {
-    for error in log.errors.iter() {
-        println!("{}", error.to_string());
+    for error in log {
+        match (log.style, &log.source) {
+            (ErrorStyle::Json, Some(file)) => eprintln!("{}", error.to_json_in(file)),
+            (ErrorStyle::Json, None) => eprintln!("{}", error.to_json()),
+            (style, Some(file)) => eprintln!(
+                "{}",
+                InSource {
+                    error,
+                    file,
+                    color: style == ErrorStyle::Colored,
+                }
+            ),
+            (_, None) => eprintln!("{}", error),
+        }
+    }
+    // Anything reading the json wants one diagnostic per line and nothing else.
+    if log.style != ErrorStyle::Json {
+        if let Some(summary) = log.summary() {
+            eprintln!("{}", summary);
+        }
     }
 }
 
@@ -149,3 +822,169 @@ pub fn report_and_exit(code: exitcode::ExitCode, error_log: &ErrorLog) {
     print_error_log(error_log);
     exit_with_code(code);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Only lines and columns matter here, the index just has to put them in order.
+    fn span(line: usize, start: usize, end: usize) -> source_file::SourceSpan {
+        let location = |column| source_file::SourceLocation {
+            line,
+            column,
+            index: line * 1000 + column,
+        };
+        source_file::SourceSpan {
+            start: location(start),
+            end: location(end),
+        }
+    }
+
+    // How the whole log is shown against its source, as everything but the command line sees it.
+    fn rendered(source: &str) -> String {
+        let mut log = crate::run_source(source).unwrap_err();
+        log.source = Some(source_file::SourceFile::unnamed(source.to_string()));
+        log.to_string()
+    }
+
+    // --- Logs ---
+
+    #[test]
+    fn identical_errors_collapse_into_one() {
+        let mut log = ErrorLog::new();
+        log.push(Error::parsing(span(1, 5, 6), "Expected expression"));
+        log.push(Error::parsing(span(1, 5, 6), "Expected expression"));
+        log.push(Error::parsing(span(1, 5, 6), "Expected something else"));
+        log.remove_duplicates();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log.error_count(), 2);
+    }
+
+    #[test]
+    fn sorts_by_location_rather_than_when_found() {
+        let mut log = ErrorLog::new();
+        log.push(Error::runtime(span(3, 1, 2), "third"));
+        log.push(Error::new(ErrorKind::Runtime, None, "nowhere"));
+        log.push(Error::parsing(span(1, 7, 8), "second"));
+        log.push(Error::scanning(span(1, 2, 3), "first"));
+        log.sort_by_location();
+        let messages: Vec<&str> = log.iter().map(Error::message).collect();
+        assert_eq!(messages, vec!["first", "second", "third", "nowhere"]);
+    }
+
+    #[test]
+    fn drops_parsing_errors_caused_by_a_scanning_error() {
+        let mut log = ErrorLog::new();
+        log.push(Error::parsing(span(1, 1, 2), "before"));
+        log.push(Error::scanning(span(1, 5, 6), "scanning"));
+        log.push(Error::parsing(span(1, 5, 6), "inside"));
+        log.push(Error::parsing(span(1, 8, 9), "later on the line"));
+        log.push(Error::parsing(span(2, 1, 2), "next line"));
+        log.remove_cascading_errors();
+        let messages: Vec<&str> = log.iter().map(Error::message).collect();
+        assert_eq!(messages, vec!["before", "scanning", "next line"]);
+    }
+
+    #[test]
+    fn drops_running_out_of_tokens_before_a_scanning_error() {
+        let mut log = ErrorLog::new();
+        log.push(Error::parsing(span(1, 1, 2), "before").as_end_of_input());
+        log.push(Error::new(ErrorKind::Parsing, None, "nowhere").as_end_of_input());
+        log.push(Error::scanning(span(1, 5, 9), "unterminated"));
+        log.remove_cascading_errors();
+        let messages: Vec<&str> = log.iter().map(Error::message).collect();
+        assert_eq!(messages, vec!["unterminated"]);
+    }
+
+    // Running out of tokens after the last scanning error has nothing to do with it.
+    #[test]
+    fn keeps_running_out_of_tokens_after_a_scanning_error() {
+        let mut log = ErrorLog::new();
+        log.push(Error::scanning(span(1, 5, 6), "scanning"));
+        log.push(Error::parsing(span(2, 3, 4), "after").as_end_of_input());
+        log.remove_cascading_errors();
+        assert_eq!(log.len(), 2);
+    }
+
+    #[test]
+    fn summarizes_errors_and_warnings() {
+        let mut log = ErrorLog::new();
+        assert_eq!(log.summary(), None);
+        log.push(Error::parsing(span(1, 1, 2), "a").as_warning());
+        assert_eq!(log.summary().as_deref(), Some("1 warning emitted"));
+        for column in 1..4 {
+            log.push(Error::parsing(span(2, column, column + 1), "b"));
+        }
+        assert_eq!(
+            log.summary().as_deref(),
+            Some("3 errors, 1 warning emitted")
+        );
+    }
+
+    // --- Exit codes ---
+
+    #[test]
+    fn static_errors_exit_with_65() {
+        for error in [
+            Error::scanning(span(1, 1, 2), "a"),
+            Error::parsing(span(1, 1, 2), "a"),
+            Error::resolution(span(1, 1, 2), "a"),
+        ] {
+            let mut log = ErrorLog::new();
+            log.push(error);
+            assert_eq!(exit_code_for(&log), exitcode::DATAERR);
+        }
+    }
+
+    #[test]
+    fn runtime_errors_exit_with_70_whatever_came_before() {
+        let mut log = ErrorLog::new();
+        log.push(Error::parsing(span(1, 1, 2), "a"));
+        log.push(Error::runtime(span(2, 1, 2), "b"));
+        assert_eq!(exit_code_for(&log), exitcode::SOFTWARE);
+    }
+
+    #[test]
+    fn warnings_alone_exit_with_0() {
+        let mut log = ErrorLog::new();
+        assert_eq!(exit_code_for(&log), exitcode::OK);
+        log.push(Error::parsing(span(1, 1, 2), "a").as_warning());
+        assert_eq!(exit_code_for(&log), exitcode::OK);
+    }
+
+    // --- Rendering ---
+
+    #[test]
+    fn renders_scanning_errors_under_the_source() {
+        assert_eq!(
+            rendered("var a = 1;\nvar b = @;"),
+            "[line: 2, col: 9] error[E0001]: Unexpected character '@'
+  |
+2 | var b = @;
+  |         ^"
+        );
+    }
+
+    #[test]
+    fn renders_parsing_errors_under_the_source() {
+        assert_eq!(
+            rendered("print (1 +\n  2;"),
+            "[line: 2, col: 4] error[E1002]: Expected ')' after expression, instead found ';'
+  |
+2 |   2;
+  |    ^"
+        );
+    }
+
+    #[test]
+    fn renders_runtime_errors_under_the_source() {
+        assert_eq!(
+            rendered("var a = nil;\nprint\ta - 1;"),
+            "[line: 2, col: 7] error[E3001]: Operands of '-' must be numbers, found nil and \
+             number 1
+  |
+2 | print\ta - 1;
+  |      \t^~~~~"
+        );
+    }
+}
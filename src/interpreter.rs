@@ -1,6 +1,23 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::environment::Environment;
+use crate::error_codes::ErrorCode;
 use crate::errors;
-use crate::parser::{BinaryExpr, Expr, LiteralKind, Stmt, TernaryExpr, UnaryExpr};
-use crate::scanner::Token;
+use crate::natives;
+use crate::parser::{
+    BinaryExpr, CallExpr, ClassStmt, Expr, ExprArena, ExprId, GetExpr, LogicalExpr, Program,
+    SetExpr, Stmt, SuperExpr, TernaryExpr, UnaryExpr,
+};
+use crate::scanner::{Token, USE_EXTENDED_UNICODE};
+use crate::source_file;
+use crate::value::{Callable, Class, Function, Instance, Value};
 
 // // Rust's native method of runtime introspection is not recomended for anything other than debugging.
 // trait TypeInfoable {
@@ -9,300 +26,1901 @@ use crate::scanner::Token;
 
 // -----| Comparison Utilities |-----
 
-trait Boolable {
-    fn to_bool_option(&self) -> Option<bool>;
+/// The one rule for what counts as true, as in the book: `nil` and `false` are falsey, and
+/// everything else (including `0` and `""`) is truthy. This is what `!`, `if`, `while`, `and` and
+/// `or` all use. Ternaries still insist on a real boolean.
+pub fn is_truthy(investigatee: &Value) -> bool {
+    !matches!(investigatee, Value::Nil | Value::Boolean(false))
+}
+
+// Strings are ordered a grapheme at a time, the same units the scanner reads source in, rather
+// than by bytes or chars.
+fn compare_strings(a: &str, b: &str) -> Ordering {
+    a.graphemes(USE_EXTENDED_UNICODE)
+        .cmp(b.graphemes(USE_EXTENDED_UNICODE))
+}
+
+// -----| Reporting Utilities |-----
+
+fn construct_runtime_error(
+    description: String,
+    location: source_file::SourceSpan,
+) -> errors::Error {
+    errors::Error::runtime(location, description)
+}
+
+fn construct_located_runtime_error(
+    description: String,
+    subject: String,
+    location: source_file::SourceSpan,
+) -> errors::Error {
+    errors::Error::runtime(location, description).with_subject(subject)
 }
 
-impl Boolable for LiteralKind {
-    fn to_bool_option(&self) -> Option<bool> {
-        match self {
-            LiteralKind::Boolean(value) => Some(*value),
-            LiteralKind::Nil => Some(false),
-            LiteralKind::Number(_) => None,
-            LiteralKind::String(_) => None,
+// Gives an error a location, unless it already has a better one.
+fn locate_error(mut error: errors::Error, location: source_file::SourceSpan) -> errors::Error {
+    if error.description.location.is_none() {
+        error.description.location = Some(location);
+    }
+    error
+}
+
+fn construct_binary_operands_error(
+    operator: &Token,
+    expected: &str,
+    left: &Value,
+    right: &Value,
+    location: source_file::SourceSpan,
+) -> errors::Error {
+    construct_runtime_error(
+        format!(
+            "Operands of '{}' must be {}, found {} and {}",
+            operator,
+            expected,
+            describe_value(left),
+            describe_value(right)
+        ),
+        location,
+    )
+    .with_code(ErrorCode::InvalidOperands)
+}
+
+// For the bitwise operators, which only make sense on whole numbers.
+fn expect_integer(
+    operator: &Token,
+    value: &Value,
+    location: source_file::SourceSpan,
+) -> Result<i64, errors::Error> {
+    match value {
+        Value::Number(number) if number.is_finite() && number.fract() == 0.0 => Ok(*number as i64),
+        _ => Err(construct_runtime_error(
+            format!(
+                "Operands of '{}' must be whole numbers, found {}",
+                operator,
+                describe_value(value)
+            ),
+            location,
+        )
+        .with_code(ErrorCode::InvalidOperands)),
+    }
+}
+
+// Unlike `stringify()`, the type is spelled out, so that it's clear what went wrong.
+fn describe_value(value: &Value) -> String {
+    match value {
+        Value::Nil => String::from(value.type_name()),
+        Value::String(string) => format!("{} \"{}\"", value.type_name(), string),
+        _ => format!("{} {}", value.type_name(), stringify(value)),
+    }
+}
+
+// -----| Output Utilities |-----
+
+/// How values are shown to the user, by `print`, when echoed by the prompt, and when converted for
+/// string concatenation. Nothing user visible should format a value any other way.
+pub fn stringify(value: &Value) -> String {
+    stringify_within(value, &mut Vec::new())
+}
+
+// Strings inside lists, maps and tuples are quoted, so that `["a, b"]` can be told apart from `["a", "b"]`.
+// A list or map that's already being shown further out (because it contains itself, somewhere) is
+// cut short. Map entries are shown in order of their keys, since maps themselves have no order.
+fn stringify_within(value: &Value, enclosing: &mut Vec<*const ()>) -> String {
+    match value {
+        Value::Number(number) => stringify_number(*number),
+        Value::String(string) => string.to_string(),
+        Value::Boolean(boolean) => boolean.to_string(),
+        Value::Nil => String::from("nil"),
+        Value::Native(_) => String::from("<native fn>"),
+        Value::Function(function) => format!("<fn {}>", function.declaration.name),
+        Value::Class(class) => class.name.clone(),
+        Value::Instance(instance) => format!("{} instance", instance.borrow().class.name),
+        Value::List(list) => {
+            let pointer = Rc::as_ptr(list) as *const ();
+            if enclosing.contains(&pointer) {
+                return String::from("[...]");
+            }
+            enclosing.push(pointer);
+            let elements: Vec<String> = list
+                .borrow()
+                .iter()
+                .map(|element| stringify_element(element, enclosing))
+                .collect();
+            enclosing.pop();
+            format!("[{}]", elements.join(", "))
         }
+        // Tuples can't contain themselves directly, only through a list or map, which is caught there.
+        Value::Tuple(elements) => {
+            let elements: Vec<String> = elements
+                .iter()
+                .map(|element| stringify_element(element, enclosing))
+                .collect();
+            match elements.len() {
+                1 => format!("({},)", elements[0]),
+                _ => format!("({})", elements.join(", ")),
+            }
+        }
+        Value::Map(map) => {
+            let pointer = Rc::as_ptr(map) as *const ();
+            if enclosing.contains(&pointer) {
+                return String::from("{...}");
+            }
+            enclosing.push(pointer);
+            let map = map.borrow();
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            let entries: Vec<String> = keys
+                .into_iter()
+                .map(|key| format!("\"{}\": {}", key, stringify_element(&map[key], enclosing)))
+                .collect();
+            enclosing.pop();
+            format!("{{{}}}", entries.join(", "))
+        }
+    }
+}
+
+fn stringify_element(element: &Value, enclosing: &mut Vec<*const ()>) -> String {
+    match element {
+        Value::String(string) => format!("\"{}\"", string),
+        _ => stringify_within(element, enclosing),
+    }
+}
+
+/// Fills each `%s` in the template with the next value, shown exactly as `print` would show it. A
+/// literal percent sign is written `%%`. Anything else after a `%`, or a different number of values
+/// than there are places for them, is an error.
+pub fn format_string(template: &str, values: &[Value]) -> Result<String, String> {
+    let mut ret = String::new();
+    let mut remaining = values.iter();
+    let mut characters = template.chars();
+    while let Some(character) = characters.next() {
+        if character != '%' {
+            ret.push(character);
+            continue;
+        }
+        match characters.next() {
+            Some('%') => ret.push('%'),
+            Some('s') => match remaining.next() {
+                Some(value) => ret.push_str(&stringify(value)),
+                None => {
+                    return Err(format!(
+                        "Not enough values for format string, found {}",
+                        values.len()
+                    ))
+                }
+            },
+            Some(other) => return Err(format!("Unknown format specifier '%{}'", other)),
+            None => return Err(String::from("Format string can't end with a lone '%'")),
+        }
+    }
+    if remaining.next().is_some() {
+        return Err(format!(
+            "Too many values for format string, found {}",
+            values.len()
+        ));
     }
+    Ok(ret)
 }
 
-fn is_truthy(investigatee: LiteralKind) -> bool {
-    if let Some(value) = investigatee.to_bool_option() {
-        value
+// Conveniently, rust's own formatting already does most of what we want. Integral numbers have no
+// decimal point (`4`, not `4.0`), anything else gets the shortest representation that reads back
+// as the same number (so `0.1 + 0.2` is `0.30000000000000004`), and negative zero keeps its sign.
+// Only the non-finite numbers need spelling out, rust would say `inf`.
+fn stringify_number(number: f64) -> String {
+    if number.is_nan() {
+        String::from("NaN")
+    } else if number == f64::INFINITY {
+        String::from("Infinity")
+    } else if number == f64::NEG_INFINITY {
+        String::from("-Infinity")
     } else {
-        false
+        number.to_string()
     }
 }
 
-// For now, just relying on PartialEq should be good enough. In the future, this may need to be
-// changed, which is why we use this function to wrap the equality check.
-fn is_equal(a: LiteralKind, b: LiteralKind) -> bool {
-    a == b
-    // Maybe in the future we want to prevent comparisons between types that can never be
-    // equivilent. Certianly I have no interest in equality checks suceeding between heterogenus
-    // types of the kind JS allows.
-    // if enum_variant_equal(&a, &b) {
-    //     return a == b;
-    // }
-    // panic!("Illegal equality comparison of operands")
+// -----| Interpreter |-----
+
+/// Everything that lasts for as long as a program runs.
+pub struct Interpreter {
+    pub environment: Environment,
+    /// Where natives like `readLine()` read from.
+    pub input: Box<dyn BufRead>,
+    /// Where `print` (and the prompt's echo) writes to.
+    pub writer: Box<dyn Write>,
+    /// Whether arithmetic that produces NaN is an error, rather than quietly carrying on.
+    pub strict_nan: bool,
+    /// Where `random()` and friends get their numbers from.
+    pub random: natives::Random,
+    /// How deep calls can nest before giving up with a runtime error, rather than overflowing the
+    /// rust stack and taking the whole process down with it.
+    pub max_call_depth: usize,
+    /// The calls currently in progress, outermost first, for tracing runtime errors back through.
+    pub call_stack: Vec<errors::CallFrame>,
+    /// How many steps (statements run, calls made and loops gone around) a program can take before
+    /// it's stopped, for running scripts that can't be trusted to finish. Unlimited if there isn't
+    /// one.
+    pub max_steps: Option<u64>,
+    /// How long a program can run for before it's stopped. Unlimited if there isn't one. Note that
+    /// there's no clock to check in the browser, so this can't be used there.
+    pub timeout: Option<Duration>,
+    steps: u64,
+    deadline: Option<Instant>,
+    /// Values partway through being used, like the arguments of a call that's still evaluating the
+    /// rest of them, which only the interpreter knows about. See `collect_garbage()`.
+    held: Vec<Value>,
 }
 
-// -----| Reporting Utilities |-----
+/// How deep lox calls can nest by default. This was asked for twice, first as 512 with the message
+/// "Maximum call stack size exceeded", and later as "in the low thousands" with the message "Stack
+/// overflow: maximum call depth N exceeded". The later one is what's here: 512 is shallow enough
+/// that ordinary recursive code (walking a long list, say) runs out, and naming the limit in the
+/// message tells whoever hits it what to raise.
+// Each lox call is several rust frames deep, so this many takes far more stack than the main
+// thread has, at least in a debug build. Anything that lets lox recurse this deep needs to run it on
+// a thread with a bigger stack, like the command line does.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 2000;
 
-fn construct_runtime_error(description: String) -> errors::Error {
-    errors::Error {
-        kind: errors::ErrorKind::Runtime,
-        description: errors::ErrorDescription {
-            subject: None,  // TODO
-            location: None, // TODO
-            description,
-        },
+impl Interpreter {
+    /// Reads from stdin and writes to stdout, and the natives are already defined in the global
+    /// scope.
+    pub fn new() -> Self {
+        let mut environment = Environment::new();
+        natives::define_natives(&mut environment);
+        Interpreter {
+            environment,
+            input: stdin_input(),
+            writer: Box::new(io::stdout()),
+            strict_nan: false,
+            random: natives::Random::new(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            call_stack: Vec::new(),
+            max_steps: None,
+            timeout: None,
+            steps: 0,
+            deadline: None,
+            held: Vec::new(),
+        }
+    }
+    pub fn with_input(mut self, input: Box<dyn BufRead>) -> Self {
+        self.input = input;
+        self
+    }
+    pub fn with_writer(mut self, writer: Box<dyn Write>) -> Self {
+        self.writer = writer;
+        self
+    }
+    /// Writes a value on its own line, exactly as `print` shows it. The writer is flushed every
+    /// time, so that output always shows up before the next prompt or anything read from input.
+    pub fn print_value(&mut self, value: &Value) -> Result<(), errors::Error> {
+        writeln!(self.writer, "{}", value)
+            .and_then(|_| self.writer.flush())
+            .map_err(|error| {
+                errors::Error::new(
+                    errors::ErrorKind::Runtime,
+                    None,
+                    format!("Failed to write output ({})", error),
+                )
+                .with_code(ErrorCode::Io)
+            })
+    }
+    /// Starts counting steps and time from zero. `interpret()` does this itself, but anything
+    /// calling `execute()` or `evaluate()` directly has to do it first for the limits to mean
+    /// anything.
+    pub fn start_budget(&mut self) {
+        self.steps = 0;
+        self.deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+    }
+    fn budget_exceeded(&self) -> bool {
+        let out_of_steps = self
+            .max_steps
+            .is_some_and(|max_steps| self.steps > max_steps);
+        let out_of_time = self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline);
+        out_of_steps || out_of_time
+    }
+    /// How many calls are in progress right now.
+    pub fn call_depth(&self) -> usize {
+        self.call_stack.len()
+    }
+    /// Frees the scopes that nothing can reach any more, and returns how many that was. This
+    /// happens by itself every so often, this is for doing it right now.
+    pub fn collect_garbage(&mut self) -> usize {
+        self.environment.collect_garbage(&self.held)
+    }
+    // Keeps a value the collector couldn't otherwise see alive until the expression being evaluated
+    // is done with, see `evaluate()`.
+    fn hold(&mut self, value: &Value) {
+        if value.can_reach_scopes() {
+            self.held.push(value.clone());
+        }
+    }
+    // Every statement, call and loop iteration costs a step, so that nothing can run forever
+    // without passing through here. That also makes it the place to collect garbage, since nothing
+    // can pile up for long without passing through here either.
+    fn take_step(
+        &mut self,
+        location: Option<source_file::SourceSpan>,
+    ) -> Result<(), errors::Error> {
+        if self.environment.wants_collection() {
+            self.collect_garbage();
+        }
+        self.steps += 1;
+        if self.budget_exceeded() {
+            let mut error = errors::Error::new(
+                errors::ErrorKind::Runtime,
+                location,
+                "Execution budget exceeded",
+            )
+            .with_code(ErrorCode::BudgetExceeded);
+            error.trace = self.call_stack.clone();
+            return Err(error);
+        }
+        Ok(())
     }
 }
 
-// -----| Drivers |-----
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
 
-// --- Statements ---
+/// A writer that can still be read from after it's handed to an interpreter, for capturing
+/// everything a program prints. Every clone shares the same contents.
+#[derive(Clone, Default)]
+pub struct OutputBuffer {
+    bytes: Rc<RefCell<Vec<u8>>>,
+}
 
-pub fn interpret(statements: Vec<Stmt>) {
-    for statement in statements {
-        if let Some(error) = interpret_statement(statement) {
-            // Hmm, this seems wrong.
-            let mut log = errors::ErrorLog::new();
-            log.push(error);
-            errors::report_and_exit(exitcode::SOFTWARE, &log)
-        }
+impl OutputBuffer {
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.bytes.borrow()).into_owned()
     }
 }
 
-pub fn interpret_statement(stmt: Stmt) -> Option<errors::Error> {
-    match stmt {
-        Stmt::Expression(statement) => match interpret_expression(statement.expression) {
-            Ok(_) => None,
-            Err(error) => Some(error),
-        },
-        Stmt::Print(statement) => match interpret_expression(statement.expression) {
-            Ok(value) => {
-                println!("{:?}", value);
-                None
-            }
-            Err(error) => Some(error),
-        },
+impl Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.bytes.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
     }
 }
 
-// --- Expressions ---
-
-pub fn interpret_expression(expr: Expr) -> Result<LiteralKind, errors::Error> {
-    let ret = match expr {
-        Expr::Literal(literal) => Ok(literal),
-        Expr::Grouping(group) => interpret_expression(*group),
-        Expr::Unary(unary) => interpret_unary(unary),
-        Expr::Binary(binary) => interpret_binary(binary),
-        Expr::Ternary(ternary) => interpret_ternary(ternary),
-    };
-    ret
+// Stdin is already buffered underneath, and the prompt reads from that same buffer, so this
+// deliberately doesn't buffer any further. Otherwise it could read ahead into lines meant for the
+// prompt.
+fn stdin_input() -> Box<dyn BufRead> {
+    Box::new(BufReader::with_capacity(1, io::stdin()))
 }
 
-// We've broken up the different expression categories, but we could also break up the individual
-// operand handlers. Also, there are many checks in these functions that could themselves be
-// functions, but we are leaving them expanded for now for flexibility. The error reporting can also
-// be made way simpler
-fn interpret_unary(UnaryExpr { operator, right }: UnaryExpr) -> Result<LiteralKind, errors::Error> {
-    let right_literal = interpret_expression(*right)?;
-    match operator {
-        Token::Minus => {
-            if let LiteralKind::Number(value) = right_literal {
-                return Ok(LiteralKind::Number(-value));
-            } else {
-                return Err(construct_runtime_error(format!(
-                    "Illegal operand for unary '{}' expression: {:?}",
-                    Token::Minus,
-                    right_literal
-                )));
-            }
-        }
-        Token::Bang => {
-            match right_literal {
-                // following two lines are technically redundant. Could be better
-                LiteralKind::Nil | LiteralKind::Boolean(_) => {
-                    return Ok(LiteralKind::Boolean(!is_truthy(right_literal)));
+// -----| Drivers |-----
+
+/// How a statement finished, for statements that jump somewhere other than the next one. These
+/// unwind out through the enclosing statements until they reach whatever they jump to, which the
+/// resolver has already checked is there.
+#[derive(Debug, PartialEq)]
+pub enum ControlFlow {
+    /// Carry on with the next statement as usual.
+    Next,
+    /// Skip the rest of the innermost loop's body, and go on to its next iteration.
+    Continue,
+    /// Leave the function being called, with this as the result.
+    Return(Value),
+}
+
+impl Interpreter {
+    // --- Statements ---
+
+    /// Runs each statement in order, stopping at the first runtime error.
+    /// The program is only borrowed, so the same one can be run again.
+    pub fn interpret(&mut self, program: &Program) -> Result<(), errors::Error> {
+        self.start_budget();
+        for statement in program.statements.iter() {
+            self.execute(statement, &program.expressions)?;
+        }
+        Ok(())
+    }
+
+    pub fn execute(
+        &mut self,
+        stmt: &Stmt,
+        expressions: &ExprArena,
+    ) -> Result<ControlFlow, errors::Error> {
+        self.take_step(None)?;
+        match stmt {
+            Stmt::Expression(statement) => {
+                self.evaluate(statement.expression, expressions)?;
+            }
+            Stmt::Print(statement) => {
+                let value = self.evaluate(statement.expression, expressions)?;
+                // Statements don't have locations of their own, so failing to print is placed at
+                // what was being printed.
+                let location = expressions.get(statement.expression).location;
+                self.print_value(&value)
+                    .map_err(|error| locate_error(error, location))?;
+            }
+            Stmt::Block(statement) => {
+                self.environment.push_scope();
+                let result = self.execute_block(&statement.statements, expressions);
+                // The scope has to be popped whether or not the block finished.
+                self.environment.pop_scope();
+                return result;
+            }
+            Stmt::If(statement) => {
+                let condition = self.evaluate(statement.condition, expressions)?;
+                if is_truthy(&condition) {
+                    return self.execute(&statement.then_branch, expressions);
+                } else if let Some(else_branch) = &statement.else_branch {
+                    return self.execute(else_branch, expressions);
                 }
-                _ => {
-                    return Err(construct_runtime_error(format!(
-                        "Illegal operand for unary '{}' expression: {:?}",
-                        Token::Bang,
-                        right_literal
-                    )));
+            }
+            Stmt::While(statement) => {
+                let condition_location = expressions.get(statement.condition).location;
+                while is_truthy(&self.evaluate(statement.condition, expressions)?) {
+                    // `continue` only cuts the body short, the increment still runs after it.
+                    if let flow @ ControlFlow::Return(_) =
+                        self.execute(&statement.body, expressions)?
+                    {
+                        return Ok(flow);
+                    }
+                    if let Some(increment) = statement.increment {
+                        self.evaluate(increment, expressions)?;
+                    }
+                    // Going back around is charged on its own, rather than trusting the body to
+                    // cost something.
+                    self.take_step(Some(condition_location))?;
                 }
             }
+            Stmt::Continue(_) => return Ok(ControlFlow::Continue),
+            Stmt::Return(statement) => {
+                let value = match statement.value {
+                    Some(value) => self.evaluate(value, expressions)?,
+                    None => Value::Nil,
+                };
+                return Ok(ControlFlow::Return(value));
+            }
+            Stmt::Function(statement) => {
+                let function = Function {
+                    declaration: Rc::clone(statement),
+                    expressions: expressions.clone(),
+                    closure: self.environment.capture(),
+                    this: None,
+                    is_initializer: false,
+                };
+                self.environment
+                    .define(statement.name.clone(), Value::Function(function));
+            }
+            Stmt::Class(statement) => self.execute_class(statement, expressions)?,
+            Stmt::Var(statement) => match statement.initializer {
+                Some(initializer) => {
+                    let value = self.evaluate(initializer, expressions)?;
+                    self.environment.define(statement.name.clone(), value);
+                }
+                None => self.environment.declare(statement.name.clone()),
+            },
+        }
+        Ok(ControlFlow::Next)
+    }
+
+    // Methods close over a scope holding `super` when there's a superclass, so that `super` always
+    // means the superclass of the class the method was declared in, not the instance's class.
+    fn execute_class(
+        &mut self,
+        statement: &ClassStmt,
+        expressions: &ExprArena,
+    ) -> Result<(), errors::Error> {
+        let superclass = match statement.superclass {
+            Some(superclass) => match self.evaluate(superclass, expressions)? {
+                Value::Class(class) => Some(class),
+                other => {
+                    return Err(construct_runtime_error(
+                        format!(
+                            "Superclass must be a class, found {}",
+                            describe_value(&other)
+                        ),
+                        expressions.get(superclass).location,
+                    )
+                    .with_code(ErrorCode::InvalidSuperclass))
+                }
+            },
+            None => None,
+        };
+        if let Some(superclass) = &superclass {
+            self.environment.push_scope();
+            self.environment
+                .define(String::from("super"), Value::Class(Rc::clone(superclass)));
+        }
+        let closure = self.environment.capture();
+        let methods = statement
+            .methods
+            .iter()
+            .map(|method| {
+                let function = Function {
+                    declaration: Rc::clone(method),
+                    expressions: expressions.clone(),
+                    closure,
+                    this: None,
+                    is_initializer: method.name == "init",
+                };
+                (method.name.clone(), function)
+            })
+            .collect();
+        if superclass.is_some() {
+            self.environment.pop_scope();
+        }
+        let class = Class {
+            name: statement.name.clone(),
+            superclass,
+            methods,
+        };
+        self.environment
+            .define(statement.name.clone(), Value::Class(Rc::new(class)));
+        Ok(())
+    }
+
+    // Stops early at anything that jumps out of the block.
+    fn execute_block(
+        &mut self,
+        statements: &[Stmt],
+        expressions: &ExprArena,
+    ) -> Result<ControlFlow, errors::Error> {
+        for statement in statements {
+            let flow = self.execute(statement, expressions)?;
+            if flow != ControlFlow::Next {
+                return Ok(flow);
+            }
+        }
+        Ok(ControlFlow::Next)
+    }
+
+    // --- Expressions ---
+
+    // Note that deeply nested source (rather than deeply nested calls, which `max_call_depth`
+    // catches) can still overflow, but the parser will have overflowed on it long before we get
+    // here.
+    pub fn evaluate(
+        &mut self,
+        expr: ExprId,
+        expressions: &ExprArena,
+    ) -> Result<Value, errors::Error> {
+        // Anything held while evaluating this is let go of once it's done, however it finishes.
+        let held = self.held.len();
+        let result = self.evaluate_holding(expr, expressions);
+        self.held.truncate(held);
+        result
+    }
+
+    fn evaluate_holding(
+        &mut self,
+        expr: ExprId,
+        expressions: &ExprArena,
+    ) -> Result<Value, errors::Error> {
+        match &expressions.get(expr).expr {
+            Expr::Literal(literal) => Ok(Value::from(literal.clone())),
+            Expr::Grouping(group) => self.evaluate(*group, expressions),
+            Expr::Unary(unary) => {
+                self.evaluate_unary(unary, expressions.get(expr).location, expressions)
+            }
+            Expr::Binary(binary) => {
+                self.evaluate_binary(binary, expressions.get(expr).location, expressions)
+            }
+            Expr::Ternary(ternary) => {
+                self.evaluate_ternary(ternary, expressions.get(expr).location, expressions)
+            }
+            Expr::Logical(logical) => self.evaluate_logical(logical, expressions),
+            // Like javascript, an operand that can't be evaluated (most usefully, a variable that
+            // doesn't exist) has the type "undefined" rather than being an error. Anything it did
+            // before failing still happens though. Running out of budget and exiting are the
+            // exceptions, otherwise `typeof` would be a way to escape them.
+            Expr::Typeof(operand) => {
+                let type_name = match self.evaluate(*operand, expressions) {
+                    Ok(value) => value.type_name(),
+                    Err(error) if self.budget_exceeded() || error.exit_code().is_some() => {
+                        return Err(error)
+                    }
+                    Err(_) => "undefined",
+                };
+                Ok(Value::String(type_name.to_string()))
+            }
+            Expr::List(list) => {
+                let elements = self.evaluate_elements(&list.elements, expressions)?;
+                Ok(Value::List(Rc::new(RefCell::new(elements))))
+            }
+            Expr::Tuple(tuple) => {
+                let elements = self.evaluate_elements(&tuple.elements, expressions)?;
+                Ok(Value::Tuple(elements.into()))
+            }
+            Expr::Map(map) => {
+                let mut entries = HashMap::new();
+                for (key, value) in map.entries.iter() {
+                    let value = self.evaluate(*value, expressions)?;
+                    self.hold(&value);
+                    entries.insert(key.clone(), value);
+                }
+                Ok(Value::Map(Rc::new(RefCell::new(entries))))
+            }
+            Expr::Variable(name) => self.environment.get(name, expressions.get(expr).location),
+            Expr::Assign(assign) => {
+                let value = self.evaluate(assign.value, expressions)?;
+                self.environment.assign(
+                    &assign.name,
+                    value.clone(),
+                    expressions.get(expr).location,
+                )?;
+                Ok(value)
+            }
+            Expr::Get(get) => self.evaluate_get(get, expressions),
+            Expr::Set(set) => self.evaluate_set(set, expressions),
+            Expr::Call(call) => {
+                self.evaluate_call(call, expressions.get(expr).location, expressions)
+            }
+            // The resolver has already checked that these are inside a method, and a method is only
+            // ever called once it's bound to an instance.
+            Expr::This(_) => self.environment.get("this", expressions.get(expr).location),
+            Expr::Super(super_expr) => self.evaluate_super(super_expr),
         }
-        // Note, I think this should theoretically be impossible. The parser should catch these
-        // earlier. That's why we panic
-        _ => panic!("Illegal operator for unary expression: {}", operator),
     }
-}
 
-// Right now, we're checking if both operands are numeric for every single operator, but also we
-// only support numeric operations (the book allows string concatenation but I don't). We could
-// thus check for numeric once at the beginning, but that would have to be refactored if we ever
-// wanted to support non-numeric binary operations.
-fn interpret_binary(
-    BinaryExpr {
-        left,
-        operator,
-        right,
-    }: BinaryExpr,
-) -> Result<LiteralKind, errors::Error> {
-    let left_literal = interpret_expression(*left)?;
-    let right_literal = interpret_expression(*right)?;
-    match operator {
-        Token::Minus => {
-            // TODO: Find a nicer looking way of doing this. I tried double extracting from a tuple,
-            // but the values had to be `move`d into the tuple, so they couldn't be used in the
-            // panic string format.
-            if let LiteralKind::Number(left_value) = left_literal {
-                if let LiteralKind::Number(right_value) = right_literal {
-                    return Ok(LiteralKind::Number(left_value - right_value));
+    // We've broken up the different expression categories, but we could also break up the
+    // individual operand handlers. Also, there are many checks in these functions that could
+    // themselves be functions, but we are leaving them expanded for now for flexibility. The error
+    // reporting can also be made way simpler
+    fn evaluate_unary(
+        &mut self,
+        UnaryExpr { operator, right }: &UnaryExpr,
+        location: source_file::SourceSpan,
+        expressions: &ExprArena,
+    ) -> Result<Value, errors::Error> {
+        let right_literal = self.evaluate(*right, expressions)?;
+        match operator {
+            Token::Minus => {
+                if let Value::Number(value) = right_literal {
+                    Ok(Value::Number(-value))
+                } else {
+                    Err(construct_runtime_error(
+                        format!(
+                            "Operand of '{}' must be a number, found {}",
+                            operator,
+                            describe_value(&right_literal)
+                        ),
+                        location,
+                    )
+                    .with_code(ErrorCode::InvalidOperands))
+                }
+            }
+            Token::Bang => Ok(Value::Boolean(!is_truthy(&right_literal))),
+            Token::Tilde => {
+                let value = expect_integer(operator, &right_literal, location)?;
+                Ok(Value::Number(!value as f64))
+            }
+            // Note, I think this should theoretically be impossible. The parser should catch these
+            // earlier.
+            _ => Err(construct_runtime_error(
+                format!("Illegal operator for unary expression: {}", operator),
+                location,
+            )
+            .with_code(ErrorCode::IllegalOperator)),
+        }
+    }
+
+    // Long chains like `a + b + c + ...` parse into a tree that leans left, as deep as the chain is
+    // long, so the chain is walked with a loop rather than by recursing down it. Otherwise a
+    // generated sum with thousands of terms would overflow the stack. Operands are still evaluated
+    // left to right.
+    fn evaluate_binary(
+        &mut self,
+        binary: &BinaryExpr,
+        location: source_file::SourceSpan,
+        expressions: &ExprArena,
+    ) -> Result<Value, errors::Error> {
+        let mut chain = vec![(binary, location)];
+        let mut leftmost = binary.left;
+        while let Expr::Binary(inner) = &expressions.get(leftmost).expr {
+            chain.push((inner, expressions.get(leftmost).location));
+            leftmost = inner.left;
+        }
+        let mut value = self.evaluate(leftmost, expressions)?;
+        for (
+            BinaryExpr {
+                operator, right, ..
+            },
+            location,
+        ) in chain.into_iter().rev()
+        {
+            self.hold(&value);
+            let right_value = self.evaluate(*right, expressions)?;
+            value = self.apply_binary(operator, value, right_value, location)?;
+        }
+        Ok(value)
+    }
+
+    // Right now, we're checking if both operands are numeric for every single operator, since the
+    // only non-numeric operations are '+' and the comparisons on strings.
+    fn apply_binary(
+        &self,
+        operator: &Token,
+        left_literal: Value,
+        right_literal: Value,
+        location: source_file::SourceSpan,
+    ) -> Result<Value, errors::Error> {
+        match operator {
+            Token::Minus => {
+                // TODO: Find a nicer looking way of doing this. I tried double extracting from a
+                // tuple, but the values had to be `move`d into the tuple, so they couldn't be used
+                // in the error message.
+                if let Value::Number(left_value) = left_literal {
+                    if let Value::Number(right_value) = right_literal {
+                        return self.arithmetic_result(left_value - right_value, location);
+                    }
+                }
+                Err(construct_binary_operands_error(
+                    operator,
+                    "numbers",
+                    &left_literal,
+                    &right_literal,
+                    location,
+                ))
+            }
+            Token::Slash => {
+                if let Value::Number(left_value) = left_literal {
+                    if let Value::Number(right_value) = right_literal {
+                        return self.arithmetic_result(left_value / right_value, location);
+                    }
                 }
+                Err(construct_binary_operands_error(
+                    operator,
+                    "numbers",
+                    &left_literal,
+                    &right_literal,
+                    location,
+                ))
             }
-            return Err(construct_runtime_error(format!(
-                "Illegal operand for binary '{}' expression: {:?} {} {:?}",
-                Token::Minus,
-                left_literal,
-                Token::Minus,
-                right_literal
-            )));
-        }
-        Token::Slash => {
-            if let LiteralKind::Number(left_value) = left_literal {
-                if let LiteralKind::Number(right_value) = right_literal {
-                    return Ok(LiteralKind::Number(left_value / right_value));
+            // Like rust (and C), the result takes the sign of the left operand. With a string on the
+            // left it's formatting instead, like python, so `"Hello, %s!" % name` fills in the name.
+            // A tuple fills in one value per element, as in `"%s of %s" % (1, 2)`.
+            Token::Percent => {
+                if let Value::Number(left_value) = left_literal {
+                    if let Value::Number(right_value) = right_literal {
+                        return self.arithmetic_result(left_value % right_value, location);
+                    }
                 }
+                if let Value::String(template) = &left_literal {
+                    let values = match right_literal {
+                        Value::Tuple(elements) => elements.to_vec(),
+                        other => vec![other],
+                    };
+                    return format_string(template, &values).map(Value::String).map_err(
+                        |description| {
+                            construct_runtime_error(description, location)
+                                .with_code(ErrorCode::FormatString)
+                        },
+                    );
+                }
+                Err(construct_binary_operands_error(
+                    operator,
+                    "numbers, or a string on the left",
+                    &left_literal,
+                    &right_literal,
+                    location,
+                ))
             }
-            return Err(construct_runtime_error(format!(
-                "Illegal operand for binary '{}' expression: {:?} {} {:?}",
-                Token::Slash,
-                left_literal,
-                Token::Slash,
-                right_literal
-            )));
-        }
-        Token::Star => {
-            if let LiteralKind::Number(left_value) = left_literal {
-                if let LiteralKind::Number(right_value) = right_literal {
-                    return Ok(LiteralKind::Number(left_value * right_value));
+            Token::Star => {
+                if let Value::Number(left_value) = left_literal {
+                    if let Value::Number(right_value) = right_literal {
+                        return self.arithmetic_result(left_value * right_value, location);
+                    }
                 }
+                Err(construct_binary_operands_error(
+                    operator,
+                    "numbers",
+                    &left_literal,
+                    &right_literal,
+                    location,
+                ))
             }
-            return Err(construct_runtime_error(format!(
-                "Illegal operand for binary '{}' expression: {:?} {} {:?}",
-                Token::Star,
-                left_literal,
-                Token::Star,
-                right_literal
-            )));
-        }
-        Token::Plus => {
-            if let LiteralKind::Number(left_value) = left_literal {
-                if let LiteralKind::Number(right_value) = right_literal {
-                    return Ok(LiteralKind::Number(left_value + right_value));
+            // If either side is a string, the other is converted exactly as `print` would show it,
+            // so `"count: " + 3` is "count: 3" and `"is " + nil` is "is nil".
+            Token::Plus => {
+                match (&left_literal, &right_literal) {
+                    (Value::Number(left_value), Value::Number(right_value)) => {
+                        return self.arithmetic_result(left_value + right_value, location);
+                    }
+                    (Value::String(_), _) | (_, Value::String(_)) => {
+                        return Ok(Value::String(format!(
+                            "{}{}",
+                            stringify(&left_literal),
+                            stringify(&right_literal)
+                        )));
+                    }
+                    _ => {}
                 }
+                Err(construct_binary_operands_error(
+                    operator,
+                    "two numbers, or include a string",
+                    &left_literal,
+                    &right_literal,
+                    location,
+                ))
             }
-            return Err(construct_runtime_error(format!(
-                "Illegal operand for binary '{}' expression: {:?} {} {:?}",
-                Token::Plus,
-                left_literal,
-                Token::Plus,
-                right_literal
-            )));
-        }
-        Token::Greater => {
-            if let LiteralKind::Number(left_value) = left_literal {
-                if let LiteralKind::Number(right_value) = right_literal {
-                    return Ok(LiteralKind::Boolean(left_value > right_value));
+            Token::Greater => {
+                if let Value::Number(left_value) = left_literal {
+                    if let Value::Number(right_value) = right_literal {
+                        return Ok(Value::Boolean(left_value > right_value));
+                    }
+                }
+                if let (Value::String(left_value), Value::String(right_value)) =
+                    (&left_literal, &right_literal)
+                {
+                    let ordering = compare_strings(left_value, right_value);
+                    return Ok(Value::Boolean(ordering == Ordering::Greater));
+                }
+                Err(construct_binary_operands_error(
+                    operator,
+                    "two numbers or two strings",
+                    &left_literal,
+                    &right_literal,
+                    location,
+                ))
+            }
+            Token::GreaterEqual => {
+                if let Value::Number(left_value) = left_literal {
+                    if let Value::Number(right_value) = right_literal {
+                        return Ok(Value::Boolean(left_value >= right_value));
+                    }
+                }
+                if let (Value::String(left_value), Value::String(right_value)) =
+                    (&left_literal, &right_literal)
+                {
+                    let ordering = compare_strings(left_value, right_value);
+                    return Ok(Value::Boolean(ordering != Ordering::Less));
                 }
+                Err(construct_binary_operands_error(
+                    operator,
+                    "two numbers or two strings",
+                    &left_literal,
+                    &right_literal,
+                    location,
+                ))
             }
-            return Err(construct_runtime_error(format!(
-                "Illegal operand for binary '{}' expression: {:?} {} {:?}",
-                Token::Greater,
-                left_literal,
-                Token::Greater,
-                right_literal
-            )));
-        }
-        Token::GreaterEqual => {
-            if let LiteralKind::Number(left_value) = left_literal {
-                if let LiteralKind::Number(right_value) = right_literal {
-                    return Ok(LiteralKind::Boolean(left_value >= right_value));
+            Token::Less => {
+                if let Value::Number(left_value) = left_literal {
+                    if let Value::Number(right_value) = right_literal {
+                        return Ok(Value::Boolean(left_value < right_value));
+                    }
                 }
+                if let (Value::String(left_value), Value::String(right_value)) =
+                    (&left_literal, &right_literal)
+                {
+                    let ordering = compare_strings(left_value, right_value);
+                    return Ok(Value::Boolean(ordering == Ordering::Less));
+                }
+                Err(construct_binary_operands_error(
+                    operator,
+                    "two numbers or two strings",
+                    &left_literal,
+                    &right_literal,
+                    location,
+                ))
             }
-            return Err(construct_runtime_error(format!(
-                "Illegal operand for binary '{}' expression: {:?} {} {:?}",
-                Token::GreaterEqual,
-                left_literal,
-                Token::GreaterEqual,
-                right_literal
-            )));
-        }
-        Token::Less => {
-            if let LiteralKind::Number(left_value) = left_literal {
-                if let LiteralKind::Number(right_value) = right_literal {
-                    return Ok(LiteralKind::Boolean(left_value < right_value));
+            Token::LessEqual => {
+                if let Value::Number(left_value) = left_literal {
+                    if let Value::Number(right_value) = right_literal {
+                        return Ok(Value::Boolean(left_value <= right_value));
+                    }
+                }
+                if let (Value::String(left_value), Value::String(right_value)) =
+                    (&left_literal, &right_literal)
+                {
+                    let ordering = compare_strings(left_value, right_value);
+                    return Ok(Value::Boolean(ordering != Ordering::Greater));
                 }
+                Err(construct_binary_operands_error(
+                    operator,
+                    "two numbers or two strings",
+                    &left_literal,
+                    &right_literal,
+                    location,
+                ))
+            }
+            // Numbers are still f64 underneath, so both sides have to be whole to be treated as
+            // 64 bit integers. Any bits past the 53 an f64 can hold exactly are lost on the way back.
+            Token::Ampersand | Token::Pipe | Token::Caret => {
+                let left_value = expect_integer(operator, &left_literal, location)?;
+                let right_value = expect_integer(operator, &right_literal, location)?;
+                let result = match operator {
+                    Token::Ampersand => left_value & right_value,
+                    Token::Pipe => left_value | right_value,
+                    _ => left_value ^ right_value,
+                };
+                Ok(Value::Number(result as f64))
             }
-            return Err(construct_runtime_error(format!(
-                "Illegal operand for binary '{}' expression: {:?} {} {:?}",
-                Token::Less,
-                left_literal,
-                Token::Less,
-                right_literal
-            )));
-        }
-        Token::LessEqual => {
-            if let LiteralKind::Number(left_value) = left_literal {
-                if let LiteralKind::Number(right_value) = right_literal {
-                    return Ok(LiteralKind::Boolean(left_value <= right_value));
+            // Right shifts keep the sign, so `-8 >> 1` is -4.
+            Token::LessLess | Token::GreaterGreater => {
+                let left_value = expect_integer(operator, &left_literal, location)?;
+                let right_value = expect_integer(operator, &right_literal, location)?;
+                let shifted = u32::try_from(right_value).ok().and_then(|amount| {
+                    if *operator == Token::LessLess {
+                        left_value.checked_shl(amount)
+                    } else {
+                        left_value.checked_shr(amount)
+                    }
+                });
+                match shifted {
+                    Some(result) => Ok(Value::Number(result as f64)),
+                    None => Err(construct_runtime_error(
+                        format!(
+                            "Shift amount of '{}' must be between 0 and 63, found {}",
+                            operator, right_value
+                        ),
+                        location,
+                    )
+                    .with_code(ErrorCode::ShiftOutOfRange)),
                 }
             }
-            return Err(construct_runtime_error(format!(
-                "Illegal operand for binary '{}' expression: {:?} {} {:?}",
-                Token::LessEqual,
-                left_literal,
-                Token::LessEqual,
-                right_literal
-            )));
+            Token::BangEqual => Ok(Value::Boolean(!left_literal.lox_eq(&right_literal))),
+            Token::EqualEqual => Ok(Value::Boolean(left_literal.lox_eq(&right_literal))),
+            // TODO: Find out if these are actually impossible cases like I said above...
+            _ => Err(construct_runtime_error(
+                format!("Illegal operator for binary expression: {}", operator),
+                location,
+            )
+            .with_code(ErrorCode::IllegalOperator)),
+        }
+    }
+
+    // NaN is a perfectly good f64, and by default it's allowed to propagate like any other number
+    // (printing as `NaN`). In strict mode, the operation that first produces it is reported
+    // instead, since that's much easier to track down than a NaN that surfaces somewhere else
+    // entirely.
+    //
+    // Infinity is never an error, strict or not. Overflowing past the largest f64 and dividing
+    // anything but zero by zero give a signed infinity, which prints as `Infinity` (or
+    // `-Infinity`) and can be written that way too. Underflowing past the smallest quietly gives
+    // zero. Unlike NaN, infinities still compare and do arithmetic sensibly, so there's nothing to
+    // be gained by stopping.
+    fn arithmetic_result(
+        &self,
+        number: f64,
+        location: source_file::SourceSpan,
+    ) -> Result<Value, errors::Error> {
+        if self.strict_nan && number.is_nan() {
+            return Err(construct_runtime_error(
+                String::from("Arithmetic operation produced NaN"),
+                location,
+            )
+            .with_code(ErrorCode::NotANumber));
+        }
+        Ok(Value::Number(number))
+    }
+
+    fn evaluate_ternary(
+        &mut self,
+        TernaryExpr {
+            condition,
+            left_result,
+            right_result,
+        }: &TernaryExpr,
+        location: source_file::SourceSpan,
+        expressions: &ExprArena,
+    ) -> Result<Value, errors::Error> {
+        let condition_literal = self.evaluate(*condition, expressions)?;
+        // Note, we could check if this is "truthy" instead of an explicit boolean check, but I'd
+        // prefer not to.
+        if let Value::Boolean(condition_value) = condition_literal {
+            // This is an important decision. I'm currently short circuiting, but that doesn't mean
+            // I have to.
+            if condition_value {
+                self.evaluate(*left_result, expressions)
+            } else {
+                self.evaluate(*right_result, expressions)
+            }
+        } else {
+            Err(construct_runtime_error(
+                format!(
+                    "Non boolean type used as condition in ternary: {}",
+                    describe_value(&condition_literal)
+                ),
+                location,
+            )
+            .with_code(ErrorCode::NonBooleanCondition))
+        }
+    }
+
+    // Unlike the ternary, these go by truthiness, as in the book. Whichever operand decides the
+    // result is the result, so `nil or "fallback"` is "fallback" rather than true, and the right
+    // side is only evaluated if the left doesn't already decide it. Chains are walked with a loop,
+    // the same as in `evaluate_binary()`.
+    fn evaluate_logical(
+        &mut self,
+        logical: &LogicalExpr,
+        expressions: &ExprArena,
+    ) -> Result<Value, errors::Error> {
+        let mut chain = vec![logical];
+        let mut leftmost = logical.left;
+        while let Expr::Logical(inner) = &expressions.get(leftmost).expr {
+            chain.push(inner);
+            leftmost = inner.left;
+        }
+        let mut value = self.evaluate(leftmost, expressions)?;
+        for LogicalExpr {
+            operator, right, ..
+        } in chain.into_iter().rev()
+        {
+            let decided = match operator {
+                Token::Or => is_truthy(&value),
+                _ => !is_truthy(&value),
+            };
+            if !decided {
+                value = self.evaluate(*right, expressions)?;
+            }
+        }
+        Ok(value)
+    }
+
+    // Each element is held as soon as it's evaluated, since evaluating the rest could run a
+    // collection.
+    fn evaluate_elements(
+        &mut self,
+        elements: &[ExprId],
+        expressions: &ExprArena,
+    ) -> Result<Vec<Value>, errors::Error> {
+        let mut values = Vec::new();
+        for element in elements {
+            let value = self.evaluate(*element, expressions)?;
+            self.hold(&value);
+            values.push(value);
         }
-        Token::BangEqual => {
-            return Ok(LiteralKind::Boolean(!is_equal(left_literal, right_literal)))
+        Ok(values)
+    }
+
+    fn evaluate_call(
+        &mut self,
+        CallExpr { callee, arguments }: &CallExpr,
+        location: source_file::SourceSpan,
+        expressions: &ExprArena,
+    ) -> Result<Value, errors::Error> {
+        let callee_value = self.evaluate(*callee, expressions)?;
+        self.hold(&callee_value);
+        let argument_values = self.evaluate_elements(arguments, expressions)?;
+        let function: &dyn Callable = match &callee_value {
+            Value::Native(native) => native,
+            Value::Function(function) => function,
+            Value::Class(class) => class,
+            _ => {
+                return Err(construct_runtime_error(
+                    format!(
+                        "Can only call functions and classes, found {}",
+                        describe_value(&callee_value)
+                    ),
+                    location,
+                )
+                .with_code(ErrorCode::NotCallable))
+            }
+        };
+        let argument_count = argument_values.len();
+        if argument_count < function.arity() || argument_count > function.max_arity() {
+            let expected = if function.arity() == function.max_arity() {
+                function.arity().to_string()
+            } else {
+                format!("{} to {}", function.arity(), function.max_arity())
+            };
+            return Err(construct_runtime_error(
+                format!("Expected {} arguments but got {}", expected, argument_count),
+                location,
+            )
+            .with_code(ErrorCode::WrongArgumentCount));
         }
-        Token::EqualEqual => {
-            return Ok(LiteralKind::Boolean(is_equal(left_literal, right_literal)))
+        // Recursion with no base case would otherwise run until the rust stack overflows, taking
+        // the whole process with it.
+        if self.call_depth() >= self.max_call_depth {
+            return Err(construct_runtime_error(
+                format!(
+                    "Stack overflow: maximum call depth {} exceeded",
+                    self.max_call_depth
+                ),
+                location,
+            )
+            .with_code(ErrorCode::StackOverflow));
+        }
+        self.take_step(Some(location))?;
+        self.call_stack.push(errors::CallFrame {
+            name: function.name().to_string(),
+            location,
+        });
+        // Natives don't know where they were called from, so their errors are placed here. The
+        // innermost call the error passes through is the one that sees the whole stack, so that's
+        // where the trace is taken.
+        let result = function.call(self, argument_values).map_err(|error| {
+            let mut error = locate_error(error, location);
+            if error.trace.is_empty() {
+                error.trace = self.call_stack.clone();
+            }
+            error
+        });
+        // Unwound whether or not the call failed, so the prompt carries on from an empty stack
+        // after an error.
+        self.call_stack.pop();
+        result
+    }
+
+    // The arguments have already been checked against the parameters by `evaluate_call()`.
+    pub(crate) fn call_function(
+        &mut self,
+        function: &Function,
+        arguments: Vec<Value>,
+    ) -> Result<Value, errors::Error> {
+        self.environment.enter(function.closure);
+        if let Some(this) = &function.this {
+            self.environment
+                .define(String::from("this"), Value::Instance(Rc::clone(this)));
+        }
+        for (parameter, argument) in function.declaration.parameters.iter().zip(arguments) {
+            self.environment.define(parameter.name.clone(), argument);
+        }
+        let result = self.execute_block(&function.declaration.body, &function.expressions);
+        // Left whether or not the body finished, the same as a block.
+        self.environment.leave();
+        let returned = match result? {
+            ControlFlow::Return(value) => value,
+            _ => Value::Nil,
+        };
+        match &function.this {
+            // Even `init()` called again directly gives back the instance.
+            Some(this) if function.is_initializer => Ok(Value::Instance(Rc::clone(this))),
+            _ => Ok(returned),
+        }
+    }
+
+    fn evaluate_super(&mut self, super_expr: &SuperExpr) -> Result<Value, errors::Error> {
+        let location = super_expr.keyword_location;
+        let (Value::Class(superclass), Value::Instance(this)) = (
+            self.environment.get("super", location)?,
+            self.environment.get("this", location)?,
+        ) else {
+            unreachable!("The resolver only allows `super` inside a subclass's methods");
+        };
+        match superclass.find_method(&super_expr.method) {
+            Some(method) => Ok(Value::Function(method.bind(this))),
+            None => Err(construct_located_runtime_error(
+                String::from("Undefined property"),
+                super_expr.method.clone(),
+                super_expr.method_location,
+            )
+            .with_code(ErrorCode::UndefinedProperty)),
         }
-        // TODO: Find out if these are actually impossible cases like I said above...
-        _ => panic!("Illegal operator for binary expression: {}", operator),
+    }
+
+    // Errors point at the property name rather than the whole chain, so that in `a.b.c` it's clear
+    // which link failed.
+    fn evaluate_get(
+        &mut self,
+        GetExpr {
+            object,
+            name,
+            name_location,
+        }: &GetExpr,
+        expressions: &ExprArena,
+    ) -> Result<Value, errors::Error> {
+        let object_literal = self.evaluate(*object, expressions)?;
+        if let Value::Instance(instance) = &object_literal {
+            return Instance::get(instance, name).ok_or_else(|| {
+                construct_located_runtime_error(
+                    String::from("Undefined property"),
+                    name.to_string(),
+                    *name_location,
+                )
+                .with_code(ErrorCode::UndefinedProperty)
+            });
+        }
+        Err(construct_located_runtime_error(
+            format!(
+                "Only instances have properties, found {}",
+                describe_value(&object_literal)
+            ),
+            name.to_string(),
+            *name_location,
+        )
+        .with_code(ErrorCode::NotAnInstance))
+    }
+
+    fn evaluate_set(
+        &mut self,
+        SetExpr {
+            object,
+            name,
+            name_location,
+            value,
+        }: &SetExpr,
+        expressions: &ExprArena,
+    ) -> Result<Value, errors::Error> {
+        let object_literal = self.evaluate(*object, expressions)?;
+        // Checked before the value is evaluated, so that nothing it does happens for nothing.
+        if let Value::Instance(instance) = object_literal {
+            self.hold(&Value::Instance(Rc::clone(&instance)));
+            let value = self.evaluate(*value, expressions)?;
+            instance
+                .borrow_mut()
+                .fields
+                .insert(name.clone(), value.clone());
+            return Ok(value);
+        }
+        Err(construct_located_runtime_error(
+            format!(
+                "Only instances have fields, found {}",
+                describe_value(&object_literal)
+            ),
+            name.to_string(),
+            *name_location,
+        )
+        .with_code(ErrorCode::NotAnInstance))
     }
 }
 
-fn interpret_ternary(
-    TernaryExpr {
-        condition,
-        left_result,
-        right_result,
-    }: TernaryExpr,
-) -> Result<LiteralKind, errors::Error> {
-    let condition_literal = interpret_expression(*condition)?;
-    // Note, we could check if this is "truthy" instead of an explicit boolean check, but I'd prefer
-    // not to.
-    if let LiteralKind::Boolean(condition_value) = condition_literal {
-        // This is an important decision. I'm currently short circuiting, but that doesn't mean I
-        // have to.
-        if condition_value {
-            interpret_expression(*left_result)
-        } else {
-            interpret_expression(*right_result)
+// --- Compatibility ---
+
+#[deprecated(note = "use `Interpreter::interpret` instead")]
+pub fn interpret(program: Program, interpreter: &mut Interpreter) -> Result<(), errors::Error> {
+    interpreter.interpret(&program)
+}
+
+#[deprecated(note = "use `Interpreter::execute` instead")]
+pub fn interpret_statement(
+    stmt: Stmt,
+    expressions: &ExprArena,
+    interpreter: &mut Interpreter,
+) -> Result<(), errors::Error> {
+    interpreter.execute(&stmt, expressions).map(|_| ())
+}
+
+// Unlike `interpret()`, the budget isn't started, so it carries on from whatever ran last.
+#[deprecated(note = "use `Interpreter::execute` on each statement instead")]
+pub fn interpret_statements(
+    stmts: Vec<Stmt>,
+    expressions: &ExprArena,
+    interpreter: &mut Interpreter,
+) -> Result<(), errors::Error> {
+    stmts
+        .iter()
+        .try_for_each(|stmt| interpreter.execute(stmt, expressions).map(|_| ()))
+}
+
+#[deprecated(note = "use `Interpreter::evaluate` instead")]
+pub fn interpret_expression(
+    expr: ExprId,
+    expressions: &ExprArena,
+    interpreter: &mut Interpreter,
+) -> Result<Value, errors::Error> {
+    interpreter.evaluate(expr, expressions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Everything the program printed, or the first error (static or runtime) rendered as text.
+    // Warnings don't stop it.
+    fn run(source: &str) -> Result<String, String> {
+        run_with(source, Interpreter::new())
+    }
+
+    fn run_with(source: &str, interpreter: Interpreter) -> Result<String, String> {
+        let (program, log) = crate::parse_source(source.to_string());
+        let mut errors = log.errors.iter();
+        if let Some(error) = errors.find(|error| error.severity == errors::Severity::Error) {
+            return Err(error.to_string());
+        }
+        let output = OutputBuffer::default();
+        let mut interpreter = interpreter.with_writer(Box::new(output.clone()));
+        interpreter
+            .interpret(&program)
+            .map_err(|error| error.to_string())?;
+        Ok(output.contents())
+    }
+
+    // --- Programs ---
+
+    #[test]
+    fn runs_statements_in_order() {
+        let source = "var greeting = \"hi\"; print greeting; print 1 + 2;";
+        assert_eq!(run(source).unwrap(), "hi\n3\n");
+    }
+
+    #[test]
+    fn stops_at_the_first_runtime_error() {
+        let error = run("print 1; print -\"abc\"; print 2;").unwrap_err();
+        assert_eq!(
+            error,
+            "[line: 1, col: 16] error[E3001]: Operand of '-' must be a number, found string \
+             \"abc\""
+        );
+    }
+
+    // Far deeper than the test thread's stack could manage if any pass recursed down the chain.
+    #[test]
+    fn runs_long_logical_chains() {
+        let chain = vec!["a"; 100_000].join(" or ");
+        let source = format!("var a = nil; print {} or \"last\";", chain);
+        assert_eq!(run(&source).unwrap(), "last\n");
+        let chain = vec!["a"; 100_000].join(" and ");
+        let source = format!("var a = true; print {} and \"last\";", chain);
+        assert_eq!(run(&source).unwrap(), "last\n");
+    }
+
+    // --- Output ---
+
+    #[test]
+    fn stringifies_numbers() {
+        let table = [
+            (4.0, "4"),
+            (-3.0, "-3"),
+            (0.0, "0"),
+            (-0.0, "-0"),
+            (2.5, "2.5"),
+            (0.1 + 0.2, "0.30000000000000004"),
+            (1.0 / 3.0, "0.3333333333333333"),
+            (1e21, "1000000000000000000000"),
+            (1e-7, "0.0000001"),
+            (f64::NAN, "NaN"),
+            (f64::INFINITY, "Infinity"),
+            (f64::NEG_INFINITY, "-Infinity"),
+        ];
+        for (number, expected) in table {
+            assert_eq!(stringify(&Value::Number(number)), expected);
+        }
+    }
+
+    // The same as the book's test suite expects.
+    #[test]
+    fn prints_literals_like_the_book() {
+        let source = "print nil; print true; print false; print 123; print 987654; print 0; \
+                      print -0; print 123.456; print -0.001; print \"string\"; print \"\";";
+        assert_eq!(
+            run(source).unwrap(),
+            "nil\ntrue\nfalse\n123\n987654\n0\n-0\n123.456\n-0.001\nstring\n\n"
+        );
+    }
+
+    // Everything that shows a value goes through `stringify()`, so they can't disagree.
+    #[test]
+    fn print_and_string_conversion_agree() {
+        let source = "print 4.0; print str(4.0); print 0.1 + 0.2; print str(-0) + \"!\";";
+        assert_eq!(run(source).unwrap(), "4\n4\n0.30000000000000004\n-0!\n");
+    }
+
+    // --- NaN ---
+
+    const MAKES_NAN: [&str; 6] = [
+        "0 / 0",
+        "Infinity - Infinity",
+        "-Infinity + Infinity",
+        "Infinity * 0",
+        "Infinity / Infinity",
+        "sqrt(-1)",
+    ];
+
+    #[test]
+    fn nan_propagates_by_default() {
+        for expression in MAKES_NAN {
+            let source = format!("print {}; print {} + 1;", expression, expression);
+            assert_eq!(run(&source).unwrap(), "NaN\nNaN\n", "{}", expression);
+        }
+    }
+
+    #[test]
+    fn nan_is_an_error_when_strict() {
+        for expression in MAKES_NAN {
+            let mut interpreter = Interpreter::new();
+            interpreter.strict_nan = true;
+            let error = run_with(&format!("print {};", expression), interpreter).unwrap_err();
+            assert!(error.contains("error[E3"), "{}", error);
+            assert!(error.contains("produced NaN"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn strict_mode_only_stops_nan() {
+        let mut interpreter = Interpreter::new();
+        interpreter.strict_nan = true;
+        let source = "print 1 / 0; print 0.5 * 2;";
+        assert_eq!(run_with(source, interpreter).unwrap(), "Infinity\n1\n");
+    }
+
+    // --- Infinity ---
+
+    #[test]
+    fn overflowing_gives_infinity() {
+        // Two to the 1023 is as big a power of two as there is.
+        let source = "var x = 1; for (var i = 0; i < 1023; i = i + 1) x = x * 2; \
+                      print x < x * 2; print x * 2; print -x * 2;";
+        assert_eq!(run(source).unwrap(), "true\nInfinity\n-Infinity\n");
+    }
+
+    #[test]
+    fn underflowing_gives_zero() {
+        let source = "var x = 1; var y = -1; \
+                      for (var i = 0; i < 1100; i = i + 1) { x = x / 2; y = y / 2; } \
+                      print x; print y;";
+        assert_eq!(run(source).unwrap(), "0\n-0\n");
+    }
+
+    // Even when strict, since only NaN is an error.
+    #[test]
+    fn dividing_by_zero_gives_infinity() {
+        let mut interpreter = Interpreter::new();
+        interpreter.strict_nan = true;
+        let source = "print 1 / 0; print -1 / 0; print 1 / -0;";
+        assert_eq!(
+            run_with(source, interpreter).unwrap(),
+            "Infinity\n-Infinity\n-Infinity\n"
+        );
+    }
+
+    #[test]
+    fn infinity_can_be_written_back_in() {
+        let source = "print Infinity == 1 / 0; print -Infinity < -1000000; print str(Infinity);";
+        assert_eq!(run(source).unwrap(), "true\ntrue\nInfinity\n");
+    }
+
+    // --- Truthiness ---
+
+    // Only nil and false are falsey, and `!`, `if`, `while`, `and` and `or` all agree on it.
+    #[test]
+    fn truth_table() {
+        let table = [
+            ("nil", false),
+            ("false", false),
+            ("true", true),
+            ("0", true),
+            ("1", true),
+            ("\"\"", true),
+            ("\"x\"", true),
+        ];
+        for (value, truthy) in table {
+            let source = format!(
+                "print !{v}; if ({v}) print true; else print false; \
+                 var n = 0; while ({v} and n < 1) n = n + 1; print n == 1; \
+                 print ({v} or \"other\") != \"other\";",
+                v = value
+            );
+            let expected = format!("{}\n{}\n{}\n{}\n", !truthy, truthy, truthy, truthy);
+            assert_eq!(run(&source).unwrap(), expected, "{}", value);
+        }
+    }
+
+    // --- Types ---
+
+    #[test]
+    fn type_names_every_kind_of_value() {
+        let source = "fun f() {} class C {} \
+            print type(1); print type(\"s\"); print type(true); print type(nil); \
+            print type(f); print type(clock); print type(C); print type(C()); \
+            print type([1]); print type({\"a\": 1}); print type((1, 2));";
+        assert_eq!(
+            run(source).unwrap(),
+            "number\nstring\nboolean\nnil\nfunction\nfunction\nclass\ninstance\nlist\nmap\ntuple\n"
+        );
+    }
+
+    // Errors name types with `type_name()` too, so they can't drift apart.
+    #[test]
+    fn errors_spell_types_the_same_as_type() {
+        for (value, literal) in [
+            (Value::Boolean(true), "true"),
+            (Value::Nil, "nil"),
+            (Value::String(String::from("s")), "\"s\""),
+        ] {
+            let error = run(&format!("-{};", literal)).unwrap_err();
+            assert!(error.contains(value.type_name()), "{}", error);
+            let source = format!("print type({});", literal);
+            assert_eq!(run(&source).unwrap(), format!("{}\n", value.type_name()));
+        }
+    }
+
+    // --- Type errors ---
+
+    #[test]
+    fn negating_a_string_is_a_runtime_error() {
+        assert_eq!(
+            run("-\"abc\";").unwrap_err(),
+            "[line: 1, col: 1] error[E3001]: Operand of '-' must be a number, found string \"abc\""
+        );
+    }
+
+    #[test]
+    fn type_errors_name_the_operator_and_both_operands() {
+        assert_eq!(
+            run("print true - 1;").unwrap_err(),
+            "[line: 1, col: 7] error[E3001]: Operands of '-' must be numbers, found boolean true \
+             and number 1"
+        );
+        let error = run("print 1 < nil;").unwrap_err();
+        assert!(error.contains("Operands of '<'"), "{}", error);
+        assert!(error.contains("found number 1 and nil"), "{}", error);
+    }
+
+    #[test]
+    fn format_string_errors_have_a_code() {
+        for source in [
+            "print \"%s and %s\" % 1;",
+            "print \"%s\" % (1, 2);",
+            "print \"%d\" % 1;",
+            "print \"100%\" % 1;",
+        ] {
+            let error = run(source).unwrap_err();
+            assert!(error.contains("error[E3018]"), "{}", error);
+        }
+    }
+
+    // --- Comparison ---
+
+    #[test]
+    fn compares_strings_lexicographically() {
+        let source = "print \"apple\" < \"banana\"; print \"app\" < \"apple\";
+            print \"Zebra\" < \"apple\"; print \"b\" >= \"a\"; print \"a\" <= \"a\";";
+        assert_eq!(run(source).unwrap(), "true\ntrue\ntrue\ntrue\ntrue\n");
+    }
+
+    // A grapheme at a time, so "e" followed by a combining accent is still after plain "e".
+    #[test]
+    fn compares_strings_by_grapheme() {
+        let source = "print \"e\" < \"e\u{301}\"; print \"ü\" > \"z\"; print \"😀\" > \"a\";";
+        assert_eq!(run(source).unwrap(), "true\ntrue\ntrue\n");
+    }
+
+    #[test]
+    fn every_comparison_operator_takes_strings() {
+        let cases = [
+            ("\"a\" < \"b\"", true),
+            ("\"b\" < \"a\"", false),
+            ("\"a\" < \"a\"", false),
+            ("\"a\" <= \"a\"", true),
+            ("\"b\" <= \"a\"", false),
+            ("\"b\" > \"a\"", true),
+            ("\"a\" > \"a\"", false),
+            ("\"a\" >= \"a\"", true),
+            ("\"a\" >= \"b\"", false),
+            ("\"apple\" > \"app\"", true),
+            ("\"Apple\" >= \"apple\"", false),
+            ("\"\" < \"a\"", true),
+        ];
+        for (comparison, expected) in cases {
+            let output = run(&format!("print {};", comparison)).unwrap();
+            assert_eq!(output, format!("{}\n", expected), "{}", comparison);
+        }
+    }
+
+    #[test]
+    fn comparing_a_string_to_a_number_names_both() {
+        let error = run("print \"a\" < 1;").unwrap_err();
+        assert!(
+            error.contains("found string \"a\" and number 1"),
+            "{}",
+            error
+        );
+        let error = run("print 1 >= \"a\";").unwrap_err();
+        assert!(
+            error.contains("found number 1 and string \"a\""),
+            "{}",
+            error
+        );
+    }
+
+    // --- Var statements ---
+
+    #[test]
+    fn var_with_initializer_defines_the_value() {
+        assert_eq!(run("var a = 1 + 2; print a;").unwrap(), "3\n");
+        assert_eq!(run("{ var a = \"inner\"; print a; }").unwrap(), "inner\n");
+    }
+
+    #[test]
+    fn var_without_initializer_declares_but_leaves_unassigned() {
+        assert_eq!(run("var a; a = 4; print a;").unwrap(), "4\n");
+        let error = run("var a; print a;").unwrap_err();
+        assert!(error.contains("used before being assigned"), "{}", error);
+    }
+
+    #[test]
+    fn var_redeclared_at_the_top_level_overwrites() {
+        assert_eq!(
+            run("var a = 1; var a = \"two\"; print a;").unwrap(),
+            "two\n"
+        );
+        assert_eq!(run("var a = 1; var a; a = 3; print a;").unwrap(), "3\n");
+    }
+
+    // --- Loops ---
+
+    #[test]
+    fn while_continue_retests_the_condition() {
+        let source = "var i = 0; while (i < 5) { i = i + 1; if (i == 3) continue; print i; }";
+        assert_eq!(run(source).unwrap(), "1\n2\n4\n5\n");
+    }
+
+    #[test]
+    fn for_continue_still_runs_the_increment() {
+        let source = "for (var i = 0; i < 6; i = i + 1) { if (i % 2 == 0) continue; print i; }";
+        assert_eq!(run(source).unwrap(), "1\n3\n5\n");
+    }
+
+    #[test]
+    fn continue_only_skips_the_innermost_loop() {
+        let source = "for (var i = 0; i < 2; i = i + 1) {
+            for (var j = 0; j < 3; j = j + 1) { if (j == 1) continue; print i * 10 + j; }
+        }";
+        assert_eq!(run(source).unwrap(), "0\n2\n10\n12\n");
+    }
+
+    #[test]
+    fn for_without_a_condition_loops_until_stopped() {
+        let source = "var i = 0; for (;;) { i = i + 1; if (i == 3) exit(0); print i; }";
+        let error = run(source).unwrap_err();
+        assert!(error.contains("exit with code 0"), "{}", error);
+    }
+
+    // --- Functions ---
+
+    #[test]
+    fn functions_return_values() {
+        let source = "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+                      print fib(15);";
+        assert_eq!(run(source).unwrap(), "610\n");
+    }
+
+    #[test]
+    fn functions_without_a_return_give_nil() {
+        assert_eq!(
+            run("fun f() {} print f(); fun g() { return; } print g();").unwrap(),
+            "nil\nnil\n"
+        );
+    }
+
+    #[test]
+    fn return_leaves_loops() {
+        let source = "fun find() { for (var i = 0;; i = i + 1) { while (true) { return i + 3; } } }
+                      print find();";
+        assert_eq!(run(source).unwrap(), "3\n");
+    }
+
+    #[test]
+    fn closures_keep_their_scope_after_it_ends() {
+        let source =
+            "fun counter() { var count = 0; fun next() { count = count + 1; return count; }
+                      return next; }
+                      var a = counter(); var b = counter();
+                      print a(); print a(); print b();";
+        assert_eq!(run(source).unwrap(), "1\n2\n1\n");
+    }
+
+    #[test]
+    fn parameters_shadow_globals_only_while_called() {
+        let source = "var x = \"global\"; fun f(x) { print x; } f(1); print x;";
+        assert_eq!(run(source).unwrap(), "1\nglobal\n");
+    }
+
+    #[test]
+    fn functions_print_with_their_name() {
+        assert_eq!(
+            run("fun f() {} print f; print typeof f;").unwrap(),
+            "<fn f>\nfunction\n"
+        );
+    }
+
+    #[test]
+    fn calls_check_the_number_of_arguments() {
+        let error = run("fun f(a, b) {} f(1);").unwrap_err();
+        assert!(
+            error.contains("Expected 2 arguments but got 1"),
+            "{}",
+            error
+        );
+    }
+
+    // --- Tuples ---
+
+    #[test]
+    fn tuple_literals_print_like_they_are_written() {
+        let source = "print (1, \"a\", nil); print (1,); print (); print typeof (1, 2); print (1);";
+        assert_eq!(
+            run(source).unwrap(),
+            "(1, \"a\", nil)\n(1,)\n()\ntuple\n1\n"
+        );
+    }
+
+    // Unlike lists and maps, tuples are equal when what's in them is.
+    #[test]
+    fn tuples_are_equal_by_their_elements() {
+        let source = "print (1, \"a\") == (1, \"a\"); print (1, 2) == (2, 1); print (1,) == (1, 1);
+                      print ((1,), nil) == ((1,), nil); print [1] == [1]; print (1,) == 1;";
+        assert_eq!(
+            run(source).unwrap(),
+            "true\nfalse\nfalse\ntrue\nfalse\nfalse\n"
+        );
+    }
+
+    // The tuple can't change, but a list inside it is still shared.
+    #[test]
+    fn tuples_hold_lists_by_reference() {
+        let source = "var l = [1]; var t = (l, 2); listPush(l, 3); print t; print tupleGet(t, 1);";
+        assert_eq!(run(source).unwrap(), "([1, 3], 2)\n2\n");
+    }
+
+    // --- Maps ---
+
+    #[test]
+    fn map_literals_make_new_maps() {
+        let source = "var m = {\"b\": 2, a: 1, \"a\": 3,}; print m; print typeof m;
+                      fun make() { return {}; } print make() == make(); print m == m;";
+        assert_eq!(
+            run(source).unwrap(),
+            "{\"a\": 3, \"b\": 2}\nmap\nfalse\ntrue\n"
+        );
+    }
+
+    // The map's entries are still evaluated, even though nothing keeps the map.
+    #[test]
+    fn maps_can_start_a_statement() {
+        let source = "var m = {}; {\"a\": mapSet(m, \"a\", 1)}; {}; { print m; }";
+        assert_eq!(run(source).unwrap(), "{\"a\": 1}\n");
+    }
+
+    // --- Classes ---
+
+    #[test]
+    fn instances_have_fields_and_methods() {
+        let source = "class Point { init(x, y) { this.x = x; this.y = y; }
+                      sum() { return this.x + this.y; } }
+                      var p = Point(1, 2); p.x = 10; print p.sum(); print p; print Point;";
+        assert_eq!(run(source).unwrap(), "12\nPoint instance\nPoint\n");
+    }
+
+    #[test]
+    fn methods_stay_bound_to_their_instance() {
+        let source = "class A { init() { this.n = 1; } get() { return this.n; } }
+                      var get = A().get; print get();";
+        assert_eq!(run(source).unwrap(), "1\n");
+    }
+
+    #[test]
+    fn initializers_always_return_the_instance() {
+        let source = "class A { init() { this.n = 1; return; } }
+                      var a = A(); print a.init() == a; print a.n;";
+        assert_eq!(run(source).unwrap(), "true\n1\n");
+    }
+
+    #[test]
+    fn super_calls_the_superclass_method() {
+        let source = "class A { name() { return \"A\"; } greet() { return \"from \" + this.name(); } }
+                      class B < A { name() { return \"B\"; } greet() { return super.greet() + \"!\"; } }
+                      class C < B {}
+                      print C().greet();";
+        assert_eq!(run(source).unwrap(), "from B!\n");
+    }
+
+    #[test]
+    fn missing_properties_are_runtime_errors() {
+        let error = run("class A {} A().b;").unwrap_err();
+        assert!(
+            error.contains("error[E3016]: Undefined property 'b'"),
+            "{}",
+            error
+        );
+        let error = run("class A {} class B < A { m() { super.m(); } } B().m();").unwrap_err();
+        assert!(
+            error.contains("error[E3016]: Undefined property 'm'"),
+            "{}",
+            error
+        );
+        let error = run("var a = 1; a.b = 2;").unwrap_err();
+        assert!(error.contains("[E3006]"), "{}", error);
+    }
+
+    #[test]
+    fn superclasses_have_to_be_classes() {
+        let error = run("var A = 1; class B < A {}").unwrap_err();
+        assert!(
+            error.contains("Superclass must be a class, found number 1"),
+            "{}",
+            error
+        );
+    }
+
+    // --- Garbage Collection ---
+
+    #[test]
+    fn collecting_frees_scopes_nothing_can_reach() {
+        let source = "for (var i = 0; i < 100; i = i + 1) { fun f() {} }";
+        let (program, _) = crate::parse_source(source.to_string());
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(&program).unwrap();
+        let before = interpreter.environment.scope_count();
+        assert!(before > 100, "{}", before);
+        let freed = interpreter.collect_garbage();
+        // Only the global scope is left.
+        assert_eq!(interpreter.environment.scope_count(), 1);
+        assert_eq!(freed, before - 1);
+    }
+
+    #[test]
+    fn loops_declaring_functions_stay_small() {
+        let source = "var i = 0; while (i < 10000) { fun f() {} i = i + 1; }";
+        let (program, _) = crate::parse_source(source.to_string());
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(&program).unwrap();
+        let scopes = interpreter.environment.scope_count();
+        assert!(scopes <= 1024, "{}", scopes);
+    }
+
+    #[test]
+    fn collecting_keeps_scopes_that_can_still_be_reached() {
+        let source = "var kept = [];
+                      for (var i = 0; i < 3; i = i + 1) {
+                        var n = i; fun get() { return n; } listPush(kept, get);
+                      }
+                      class A { init(v) { this.v = v; } get() { return this.v; } }
+                      var method = A(\"bound\").get;
+                      collectGarbage();
+                      print listGet(kept, 2)(); print method();";
+        assert_eq!(run(source).unwrap(), "2\nbound\n");
+    }
+
+    // The function made for the first argument is only held by the interpreter while the second
+    // is evaluated, so it'd be freed if the interpreter didn't say so.
+    #[test]
+    fn collecting_keeps_values_still_being_used() {
+        let source = "fun make(n) { fun get() { return n; } return get; }
+                      fun churn() { collectGarbage(); return 0; }
+                      fun first(f, x) { return f(); }
+                      print first(make(1), churn());
+                      print listGet([make(2), churn()], 0)();
+                      print make(3)() + churn();";
+        assert_eq!(run(source).unwrap(), "1\n2\n3\n");
+    }
+
+    // Kept shallow, since the tests run on threads with small stacks.
+    #[test]
+    fn runaway_recursion_is_a_runtime_error() {
+        let (program, _) = crate::parse_source(String::from("fun f() { f(); } f();"));
+        let mut interpreter = Interpreter::new();
+        interpreter.max_call_depth = 20;
+        let error = interpreter.interpret(&program).unwrap_err();
+        assert_eq!(error.code, Some(ErrorCode::StackOverflow));
+        assert_eq!(error.trace.len(), 20);
+        assert_eq!(interpreter.call_depth(), 0);
+    }
+
+    #[test]
+    fn empty_loops_still_run_out_of_budget() {
+        for source in ["while (true) {}", "for (;;) {}"] {
+            let (program, log) = crate::parse_source(source.to_string());
+            assert_eq!(log.error_count(), 0);
+            let mut interpreter = Interpreter::new();
+            interpreter.max_steps = Some(1000);
+            let error = interpreter.interpret(&program).unwrap_err();
+            assert_eq!(error.code, Some(ErrorCode::BudgetExceeded));
         }
-    } else {
-        Err(construct_runtime_error(format!(
-            "Non boolean type used as condition in ternary: {:?}",
-            condition_literal
-        )))
     }
 }
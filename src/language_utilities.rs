@@ -4,3 +4,41 @@
 pub fn enum_variant_equal<T>(a: &T, b: &T) -> bool {
     std::mem::discriminant(a) == std::mem::discriminant(b)
 }
+
+// --- Suggestions ---
+
+/// How many single character insertions, deletions and substitutions it takes to turn one string
+/// into the other (the Levenshtein distance).
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // Only the previous row of the table is ever needed.
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The candidate most likely to be what was meant by a misspelled name, if any are close enough.
+/// That's at most two edits away, and fewer edits than the name is long, so that short names don't
+/// match everything else that's short. Ties go to whichever comes first alphabetically, so that
+/// the suggestion doesn't depend on the order the candidates are in.
+pub fn closest_match<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let length = name.chars().count();
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .filter(|candidate| candidate.chars().count().abs_diff(length) <= 2)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= 2 && *distance < length)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
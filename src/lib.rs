@@ -0,0 +1,92 @@
+pub mod ast_printer;
+pub mod environment;
+pub mod error_codes;
+pub mod errors;
+pub mod interpreter;
+mod language_utilities;
+pub mod natives;
+pub mod optimizer;
+pub mod parser;
+pub mod resolver;
+pub mod scanner;
+#[cfg(feature = "serde")]
+pub mod serialization;
+pub mod source_file;
+pub mod value;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+// -----| Pipeline |-----
+
+/// Everything wrong with a source before it runs, from each stage it went through, in the order
+/// it appears in the source. Each stage carries on past earlier errors so that everything wrong can
+/// be reported at once, but no error at any stage lets the program run. Warnings are included too,
+/// but parsing errors that only follow from a scanning error aren't, and neither are repeats.
+pub fn static_error_log(stages: &[&dyn errors::ErrorLoggable]) -> errors::ErrorLog {
+    let mut log = errors::ErrorLog::new();
+    for stage in stages {
+        log.append(stage.error_log());
+    }
+    log.remove_cascading_errors();
+    log.remove_duplicates();
+    log.sort_by_location();
+    log
+}
+
+/// Scans, parses, resolves and folds a whole program, ready to be interpreted. The program
+/// shouldn't be run if anything in the log is an error.
+pub fn parse_source(source: String) -> (parser::Program, errors::ErrorLog) {
+    parse_scanned(scanner::Scanner::from_source(source))
+}
+
+/// The same as `parse_source()`, for source that's already been scanned, like with
+/// `Scanner::from_reader()`.
+pub fn parse_scanned(scanner: scanner::Scanner) -> (parser::Program, errors::ErrorLog) {
+    let mut parser = parser::Parser::new(scanner.tokens());
+    let mut program = parser.parse();
+    let mut resolver = resolver::Resolver::new(&program.expressions);
+    resolver.resolve(&program.statements);
+    let log = static_error_log(&[&scanner, &parser, &resolver]);
+    optimizer::ConstantFolder::fold_program(&mut program);
+    (program, log)
+}
+
+// -----| Embedding |-----
+
+/// Runs a whole lox program, for when the interpreter is embedded in something else. Nothing is
+/// printed other than by the program itself, and nothing exits the process, instead everything
+/// that went wrong comes back in the log. As with the command line, scanning and parsing errors
+/// stop the program from running at all, and a runtime error stops it where it happened.
+///
+/// Warnings are included in the log if there's an error, but otherwise can't be seen. A script that
+/// calls `exit()` also stops with an error, of kind `Exit`, so that it's up to the caller whether
+/// that's a failure.
+///
+/// Deep recursion takes far more stack than the main thread has, see
+/// `interpreter::DEFAULT_MAX_CALL_DEPTH`.
+pub fn run_source(source: &str) -> Result<(), errors::ErrorLog> {
+    run_source_with(source, &RunOptions::default())
+}
+
+/// How `run_source_with()` treats a program, the same as the command line flags of the same names.
+#[derive(Default)]
+pub struct RunOptions {
+    /// Warnings are turned into errors, so that a program with any at all doesn't run.
+    pub deny_warnings: bool,
+}
+
+pub fn run_source_with(source: &str, options: &RunOptions) -> Result<(), errors::ErrorLog> {
+    let (program, mut log) = parse_source(source.to_string());
+    if options.deny_warnings {
+        log.promote_warnings();
+    }
+    if log.error_count() > 0 {
+        return Err(log);
+    }
+    let mut interpreter = interpreter::Interpreter::new();
+    if let Err(error) = interpreter.interpret(&program) {
+        log.push(error);
+        return Err(log);
+    }
+    Ok(())
+}
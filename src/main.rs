@@ -1,29 +1,156 @@
-use exitcode;
 use std::env;
-use std::fs;
-use std::io;
-use std::io::Write;
+use std::fs::File;
+use std::io::IsTerminal;
+use std::thread;
+use std::time::Duration;
 
-use crate::errors::ErrorLoggable;
+use rlox_treewalk::errors::ErrorLoggable;
+use rlox_treewalk::{
+    ast_printer, error_codes, errors, interpreter, optimizer, parser, resolver, scanner,
+    source_file,
+};
 
-mod ast_printer;
-mod errors;
-mod interpreter;
-mod language_utilities;
-mod parser;
-mod scanner;
-mod source_file;
+mod prompt;
+
+const USAGE: &str = "Usage: rlox [--version | --help | --explain code] [--strict-nan] [--deny-warnings] \
+                     [--disassemble] [--output text | --output json] \
+                     [--color auto | --color always | --color never] \
+                     [--error-format human | --error-format json] [--max-steps n] [--timeout-ms n] [script]";
+const HELP: &str = "
+Runs the given lox script, or starts an interactive prompt if none is given.
+
+Options:
+  --version      Print the interpreter version and exit
+  --help         Print this message and exit
+  --explain code Print a longer description of the error with the given code (like E2003) and
+                 exit
+  --strict-nan   Report arithmetic that produces NaN as a runtime error
+  --deny-warnings
+                 Treat warnings as errors, so that the script doesn't run
+  --disassemble  Print the script's AST, with the scope each variable was resolved to, rather
+                 than running it
+  --color when   Whether errors are printed in color, either always, never or auto (the default),
+                 which colors them when printing to a terminal, unless NO_COLOR is set
+  --error-format json
+                 Print each error as a JSON object on its own line, rather than for people to read
+  --output json  Print a single JSON object with the script's output, errors and exit code,
+                 rather than plain text. Only for scripts, not the interactive prompt
+  --max-steps n  Stop with a runtime error after n statements, calls and loop iterations.
+                 Unlimited by default
+  --timeout-ms n Stop with a runtime error after running for n milliseconds. Unlimited by
+                 default";
+// How far through the book the implemented language gets.
+const LANGUAGE_VERSION: &str = "Lox language, Crafting Interpreters chapter 13";
+
+// Everything set from the command line that affects how programs run.
+#[derive(Default)]
+struct Options {
+    strict_nan: bool,
+    deny_warnings: bool,
+    disassemble: bool,
+    output: OutputFormat,
+    color: ColorChoice,
+    error_format: ErrorFormat,
+    max_steps: Option<u64>,
+    timeout: Option<Duration>,
+}
+
+#[derive(Default, PartialEq)]
+enum OutputFormat {
+    #[default]
+    Text,
+    /// Everything that would have been printed is collected into one JSON object for tools to read.
+    Json,
+}
+
+#[derive(Default, PartialEq)]
+enum ErrorFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+#[derive(Default)]
+enum ColorChoice {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    // See https://no-color.org, an empty `NO_COLOR` doesn't count. Asking for color outright wins
+    // over it though.
+    fn use_color(&self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                std::io::stderr().is_terminal()
+                    && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() > 2 {
-        println!("Usage: rlox <script>");
-        errors::exit_with_code(exitcode::USAGE);
-    } else if args.len() == 2 {
-        run_file(&args[1]);
-    } else {
-        run_prompt();
+    // Flags can be given their values as either `--flag value` or `--flag=value`.
+    let mut args = env::args()
+        .skip(1)
+        .flat_map(|arg| match arg.split_once('=') {
+            Some((flag, value)) if arg.starts_with("--") => {
+                vec![flag.to_string(), value.to_string()]
+            }
+            _ => vec![arg],
+        });
+    let mut options = Options::default();
+    let mut script = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--version" => {
+                println!("rlox {} ({})", env!("CARGO_PKG_VERSION"), LANGUAGE_VERSION);
+                errors::exit_with_code(exitcode::OK);
+            }
+            "--help" => {
+                println!("{}", USAGE);
+                println!("{}", HELP);
+                errors::exit_with_code(exitcode::OK);
+            }
+            "--explain" => explain(args.next()),
+            "--strict-nan" => options.strict_nan = true,
+            "--deny-warnings" => options.deny_warnings = true,
+            "--disassemble" => options.disassemble = true,
+            "--output" => match args.next().as_deref() {
+                Some("text") => options.output = OutputFormat::Text,
+                Some("json") => options.output = OutputFormat::Json,
+                _ => exit_with_usage(),
+            },
+            "--color" => match args.next().as_deref() {
+                Some("auto") => options.color = ColorChoice::Auto,
+                Some("always") => options.color = ColorChoice::Always,
+                Some("never") => options.color = ColorChoice::Never,
+                _ => exit_with_usage(),
+            },
+            "--error-format" => match args.next().as_deref() {
+                Some("human") => options.error_format = ErrorFormat::Human,
+                Some("json") => options.error_format = ErrorFormat::Json,
+                _ => exit_with_usage(),
+            },
+            "--max-steps" => options.max_steps = Some(parse_count(args.next())),
+            "--timeout-ms" => {
+                options.timeout = Some(Duration::from_millis(parse_count(args.next())))
+            }
+            _ if arg.starts_with("--") || script.is_some() => exit_with_usage(),
+            _ => script = Some(arg),
+        }
+    }
+    if script.is_none() && (options.output == OutputFormat::Json || options.disassemble) {
+        exit_with_usage();
     }
+    run_with_big_stack(move || match script {
+        Some(file_name) => run_file(&file_name, &options),
+        None => run_prompt(&options),
+    });
     // let expression = parser::Expr::Binary(parser::BinaryExpr {
     // 	left: Box::new(parser::Expr::Unary(parser::UnaryExpr {
     // 		operator: scanner::Token::Minus,
@@ -37,46 +164,248 @@ fn main() {
     // println!("{}", ast_printer::expr_to_ast_string(expression));
 }
 
-fn run_file(file_name: &str) {
-    let contents = fs::read_to_string(file_name).expect("Failed to read file");
-    run(contents);
+// Each lox call is several rust calls deep, which adds up to tens of kilobytes of stack in a debug
+// build. This is enough for as many calls as `DEFAULT_MAX_CALL_DEPTH` allows, with plenty to spare
+// for deeply nested expressions along the way. It's only reserved up front, not actually used.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+// The main thread's stack can't be made any bigger, so everything runs on a thread of its own.
+fn run_with_big_stack(run: impl FnOnce() + Send + 'static) {
+    let runner = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run)
+        .expect("Failed to start the interpreter thread");
+    if let Err(panic) = runner.join() {
+        std::panic::resume_unwind(panic);
+    }
 }
 
-fn print_flush(str: &str) {
-    print!("{}", str);
-    io::stdout().flush().expect("Failed to flush output");
+fn run_file(file_name: &str, options: &Options) {
+    let scanner = match File::open(file_name).and_then(scanner::Scanner::from_reader) {
+        Ok(scanner) => scanner,
+        Err(error) => return exit_on_unreadable_file(file_name, error, options),
+    };
+    let file = source_file::SourceFile {
+        name: Some(file_name.to_string()),
+        source: scanner.source(),
+    };
+    let (program, mut log) = rlox_treewalk::parse_scanned(scanner);
+    log.source = Some(file);
+    log.style = error_style(options);
+    if options.deny_warnings {
+        log.promote_warnings();
+    }
+    match options.output {
+        OutputFormat::Text if options.disassemble => {
+            exit_on_errors(&log);
+            disassemble(&program);
+        }
+        OutputFormat::Text => {
+            exit_on_errors(&log);
+            if let Err(error) = new_interpreter(options).interpret(&program) {
+                exit_on_runtime_error(error, log);
+            }
+        }
+        OutputFormat::Json => run_for_json(program, log, options),
+    }
+}
+
+// Identical to `run_file()`, except that nothing is printed until the end, when everything is
+// printed at once as JSON.
+fn run_for_json(program: parser::Program, mut log: errors::ErrorLog, options: &Options) {
+    let output = interpreter::OutputBuffer::default();
+    let mut interpreter = new_interpreter(options).with_writer(Box::new(output.clone()));
+    if log.error_count() == 0 {
+        if let Err(error) = interpreter.interpret(&program) {
+            log.push(error);
+        }
+    }
+    let code = errors::exit_code_for(&log);
+    let lines: Vec<String> = output.contents().lines().map(errors::json_string).collect();
+    let errors: Vec<String> = log.errors.iter().map(errors::Error::to_json).collect();
+    println!(
+        "{{\"output\": [{}], \"errors\": [{}], \"exit_code\": {}}}",
+        lines.join(", "),
+        errors.join(", "),
+        code
+    );
+    errors::exit_with_code(code);
+}
+
+// The program's already been resolved once, but only to check it, so it's resolved again to see
+// where everything ended up.
+fn disassemble(program: &parser::Program) {
+    let mut resolver = resolver::Resolver::new(&program.expressions);
+    resolver.resolve(&program.statements);
+    println!(
+        "{}",
+        ast_printer::Disassembler {
+            program,
+            locals: resolver.locals(),
+        }
+    );
 }
 
-fn run_prompt() {
+fn run_prompt(options: &Options) {
+    let mut prompt = prompt::Prompt::new();
+    // Shared by every line, so that anything defined on one line can be used on the next.
+    let mut interpreter = new_interpreter(options);
+    // Lines are collected here until they make up something that can be run.
+    let mut pending = String::new();
     loop {
-        let mut line = String::new();
-        print_flush("> ");
-        io::stdin()
-            .read_line(&mut line)
-            .expect("Failed to read user input");
-        if line == "\n" {
+        let prompt_string = if pending.is_empty() { "> " } else { "... " };
+        let line = match prompt.read_line(prompt_string) {
+            Some(line) => line,
+            None => break,
+        };
+        if line.is_empty() && pending.is_empty() {
             break;
         }
-        run(line);
+        pending.push_str(&line);
+        pending.push('\n');
+        if run_prompt_line(pending.clone(), &mut interpreter, options) {
+            pending.clear();
+        }
     }
 }
 
-fn run(source: String) {
-    let scanner = scanner::Scanner::from_source(source);
-    if scanner.error_log().len() > 0 {
-        errors::print_error_log(scanner.error_log());
-    }
+// Identical to `run_file()`, except that a lone expression has its value echoed back, incomplete
+// input isn't run at all, and errors don't end the session. Whatever was defined before an error
+// is still there on the next line. Returns whether the input was complete.
+// TODO: Locations are only ever relative to the input they were typed in, so an error inside a
+// function that was declared on an earlier line is shown against the wrong source. Errors need to
+// know which input they came from for this to be fixed.
+fn run_prompt_line(
+    line: String,
+    interpreter: &mut interpreter::Interpreter,
+    options: &Options,
+) -> bool {
+    let file = source_file::SourceFile::unnamed(line);
+    let scanner = scanner::Scanner::from_source(file.source.clone());
     let mut parser = parser::Parser::new(scanner.tokens());
-    let statements = parser.parse();
+    let input = parser.parse_repl_input();
+    let mut resolver_log = errors::ErrorLog::new();
+    if let parser::ReplInput::Statements(program) = &input {
+        let mut resolver = resolver::Resolver::new(&program.expressions);
+        resolver.resolve(&program.statements);
+        resolver_log.append(resolver.error_log());
+    }
+    let mut log = rlox_treewalk::static_error_log(&[&scanner, &parser, &resolver_log]);
+    if options.deny_warnings {
+        log.promote_warnings();
+    }
+    log.source = Some(file);
+    log.style = error_style(options);
+    errors::print_error_log(&log);
+    if log.error_count() > 0 {
+        return true;
+    }
+    let result = match input {
+        parser::ReplInput::Expression(expression, mut expressions) => {
+            optimizer::ConstantFolder::new(&mut expressions).fold();
+            // Only whole programs start the budget themselves.
+            interpreter.start_budget();
+            interpreter
+                .evaluate(expression, &expressions)
+                .and_then(|value| interpreter.print_value(&value))
+        }
+        parser::ReplInput::Statements(mut program) => {
+            optimizer::ConstantFolder::fold_program(&mut program);
+            interpreter.interpret(&program)
+        }
+        parser::ReplInput::Incomplete => return false,
+    };
+    // Exiting would end the session, which is almost certainly not what was meant.
+    if let Err(error) = result {
+        match error.exit_code() {
+            Some(code) => eprintln!("Script requested exit with code {}", code),
+            // Anything already in the log has been printed.
+            None => {
+                log.errors = vec![error];
+                errors::print_error_log(&log);
+            }
+        }
+    }
+    true
+}
+
+// Warnings are printed, but only errors stop anything running.
+// A file that's missing (or a directory, or not UTF-8) is reported like any other error, rather than
+// as a panic, and exits with the code for input that couldn't be opened.
+fn exit_on_unreadable_file(file_name: &str, error: std::io::Error, options: &Options) {
+    let mut log = errors::ErrorLog::new();
+    log.style = error_style(options);
+    log.push(
+        errors::Error::new(errors::ErrorKind::Runtime, None, "Couldn't read file")
+            .with_code(error_codes::ErrorCode::Io)
+            .with_subject(file_name)
+            .with_note(error.to_string()),
+    );
+    errors::report_and_exit(exitcode::NOINPUT, &log);
+}
 
-    if parser.error_log().len() > 0 {
-        errors::report_and_exit(exitcode::DATAERR, parser.error_log());
+fn exit_on_errors(log: &errors::ErrorLog) {
+    errors::print_error_log(log);
+    let code = errors::exit_code_for(log);
+    if code != exitcode::OK {
+        errors::exit_with_code(code);
     }
+}
 
-    println!("Statement ASTs:");
-    for statement in statements.iter() {
-        println!("{}", ast_printer::stmt_to_ast_string(&statement))
+fn error_style(options: &Options) -> errors::ErrorStyle {
+    if options.error_format == ErrorFormat::Json {
+        errors::ErrorStyle::Json
+    } else if options.color.use_color() {
+        errors::ErrorStyle::Colored
+    } else {
+        errors::ErrorStyle::Plain
     }
+}
 
-    interpreter::interpret(statements);
+fn explain(code: Option<String>) {
+    match code.as_deref().and_then(error_codes::ErrorCode::from_code) {
+        Some(code) => {
+            println!("{}: {}", code, code.explanation());
+            errors::exit_with_code(exitcode::OK);
+        }
+        None => {
+            eprintln!("Unknown error code '{}'", code.unwrap_or_default());
+            errors::exit_with_code(exitcode::USAGE);
+        }
+    }
+}
+
+fn new_interpreter(options: &Options) -> interpreter::Interpreter {
+    let mut interpreter = interpreter::Interpreter::new();
+    interpreter.strict_nan = options.strict_nan;
+    interpreter.max_steps = options.max_steps;
+    interpreter.timeout = options.timeout;
+    interpreter
+}
+
+// For flags that take a whole number, which has to be there.
+fn parse_count(arg: Option<String>) -> u64 {
+    match arg.and_then(|arg| arg.parse().ok()) {
+        Some(count) => count,
+        None => {
+            exit_with_usage();
+            0
+        }
+    }
+}
+
+fn exit_with_usage() {
+    eprintln!("{}", USAGE);
+    errors::exit_with_code(exitcode::USAGE);
+}
+
+// A script asking to exit isn't an error, so there's nothing to report.
+fn exit_on_runtime_error(error: errors::Error, mut log: errors::ErrorLog) {
+    if let Some(code) = error.exit_code() {
+        errors::exit_with_code(code);
+        return;
+    }
+    // Anything already in the log has been printed.
+    log.errors = vec![error];
+    errors::report_and_exit(errors::exit_code_for(&log), &log);
 }
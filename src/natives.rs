@@ -0,0 +1,1050 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::environment::Environment;
+use crate::error_codes::ErrorCode;
+use crate::errors;
+use crate::interpreter::{self, Interpreter};
+use crate::scanner::{Scanner, USE_EXTENDED_UNICODE};
+use crate::value::{List, Map, NativeFunction, Value};
+
+// -----| Registration |-----
+
+const NATIVES: &[NativeFunction] = &[
+    NativeFunction {
+        name: "clock",
+        arity: 0,
+        max_arity: 0,
+        body: clock,
+    },
+    NativeFunction {
+        name: "readLine",
+        arity: 0,
+        max_arity: 0,
+        body: read_line,
+    },
+    NativeFunction {
+        name: "readNumber",
+        arity: 0,
+        max_arity: 0,
+        body: read_number,
+    },
+    NativeFunction {
+        name: "assert",
+        arity: 1,
+        max_arity: 2,
+        body: assert,
+    },
+    NativeFunction {
+        name: "type",
+        arity: 1,
+        max_arity: 1,
+        body: type_of,
+    },
+    NativeFunction {
+        name: "str",
+        arity: 1,
+        max_arity: 1,
+        body: str,
+    },
+    NativeFunction {
+        name: "num",
+        arity: 1,
+        max_arity: 1,
+        body: num,
+    },
+    NativeFunction {
+        name: "len",
+        arity: 1,
+        max_arity: 1,
+        body: len,
+    },
+    NativeFunction {
+        name: "substring",
+        arity: 3,
+        max_arity: 3,
+        body: substring,
+    },
+    NativeFunction {
+        name: "indexOf",
+        arity: 2,
+        max_arity: 2,
+        body: index_of,
+    },
+    NativeFunction {
+        name: "toUpper",
+        arity: 1,
+        max_arity: 1,
+        body: to_upper,
+    },
+    NativeFunction {
+        name: "toLower",
+        arity: 1,
+        max_arity: 1,
+        body: to_lower,
+    },
+    NativeFunction {
+        name: "floor",
+        arity: 1,
+        max_arity: 1,
+        body: floor,
+    },
+    NativeFunction {
+        name: "ceil",
+        arity: 1,
+        max_arity: 1,
+        body: ceil,
+    },
+    NativeFunction {
+        name: "abs",
+        arity: 1,
+        max_arity: 1,
+        body: abs,
+    },
+    NativeFunction {
+        name: "sqrt",
+        arity: 1,
+        max_arity: 1,
+        body: sqrt,
+    },
+    NativeFunction {
+        name: "min",
+        arity: 2,
+        max_arity: 2,
+        body: min,
+    },
+    NativeFunction {
+        name: "max",
+        arity: 2,
+        max_arity: 2,
+        body: max,
+    },
+    NativeFunction {
+        name: "exit",
+        arity: 1,
+        max_arity: 1,
+        body: exit,
+    },
+    NativeFunction {
+        name: "random",
+        arity: 0,
+        max_arity: 0,
+        body: random,
+    },
+    NativeFunction {
+        name: "randomInt",
+        arity: 2,
+        max_arity: 2,
+        body: random_int,
+    },
+    NativeFunction {
+        name: "setRandomSeed",
+        arity: 1,
+        max_arity: 1,
+        body: set_random_seed,
+    },
+    NativeFunction {
+        name: "collectGarbage",
+        arity: 0,
+        max_arity: 0,
+        body: collect_garbage,
+    },
+    NativeFunction {
+        name: "listPush",
+        arity: 2,
+        max_arity: 2,
+        body: list_push,
+    },
+    NativeFunction {
+        name: "listPop",
+        arity: 1,
+        max_arity: 1,
+        body: list_pop,
+    },
+    NativeFunction {
+        name: "listGet",
+        arity: 2,
+        max_arity: 2,
+        body: list_get,
+    },
+    NativeFunction {
+        name: "listSet",
+        arity: 3,
+        max_arity: 3,
+        body: list_set,
+    },
+    NativeFunction {
+        name: "listLength",
+        arity: 1,
+        max_arity: 1,
+        body: list_length,
+    },
+    NativeFunction {
+        name: "tupleGet",
+        arity: 2,
+        max_arity: 2,
+        body: tuple_get,
+    },
+    NativeFunction {
+        name: "mapGet",
+        arity: 2,
+        max_arity: 2,
+        body: map_get,
+    },
+    NativeFunction {
+        name: "mapSet",
+        arity: 3,
+        max_arity: 3,
+        body: map_set,
+    },
+    NativeFunction {
+        name: "mapHas",
+        arity: 2,
+        max_arity: 2,
+        body: map_has,
+    },
+    NativeFunction {
+        name: "mapRemove",
+        arity: 2,
+        max_arity: 2,
+        body: map_remove,
+    },
+    NativeFunction {
+        name: "mapKeys",
+        arity: 1,
+        max_arity: 1,
+        body: map_keys,
+    },
+];
+
+/// Defines every native as a global.
+// Always global, even if this happens to be called from inside a scope.
+pub fn define_natives(environment: &mut Environment) {
+    for native in NATIVES {
+        environment.define_global(native.name.to_string(), Value::Native(native.clone()));
+    }
+}
+
+// -----| Natives |-----
+
+// Seconds since the epoch, as in the book, so that benchmarks written for other implementations
+// work unchanged.
+fn clock(_interpreter: &mut Interpreter, _arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    Ok(Value::Number(seconds_since_epoch()))
+}
+
+// The next line of input, without its line ending, or nil once there's no more input.
+fn read_line(
+    interpreter: &mut Interpreter,
+    _arguments: Vec<Value>,
+) -> Result<Value, errors::Error> {
+    let mut line = String::new();
+    match interpreter.input.read_line(&mut line) {
+        Ok(0) => Ok(Value::Nil),
+        Ok(_) => Ok(Value::String(
+            line.trim_end_matches(['\n', '\r']).to_string(),
+        )),
+        Err(error) => Err(
+            construct_native_error(format!("Failed to read input ({})", error))
+                .with_code(ErrorCode::Io),
+        ),
+    }
+}
+
+// Reads a line like `readLine()`, and gives nil if it isn't a number rather than an error. Bad input
+// is the user's fault rather than the script's, so the script gets the chance to ask again.
+fn read_number(
+    interpreter: &mut Interpreter,
+    arguments: Vec<Value>,
+) -> Result<Value, errors::Error> {
+    match read_line(interpreter, arguments)? {
+        Value::String(line) => Ok(line
+            .trim()
+            .parse::<f64>()
+            .map(Value::Number)
+            .unwrap_or(Value::Nil)),
+        _ => Ok(Value::Nil),
+    }
+}
+
+// Does nothing if the condition is truthy, and is a runtime error (placed at the call) otherwise,
+// with the message if there is one.
+fn assert(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    if interpreter::is_truthy(&arguments[0]) {
+        return Ok(Value::Nil);
+    }
+    Err(construct_native_error(match arguments.get(1) {
+        Some(message) => format!("Assertion failed: {}", interpreter::stringify(message)),
+        None => String::from("Assertion failed"),
+    })
+    .with_code(ErrorCode::AssertionFailed))
+}
+
+// The same names used in error messages, like "number" or "nil".
+fn type_of(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    Ok(Value::String(arguments[0].type_name().to_string()))
+}
+
+// Exactly what `print` would show.
+fn str(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    Ok(Value::String(interpreter::stringify(&arguments[0])))
+}
+
+// Strings are read the same way the scanner reads number literals, so anything that can be written
+// as a number in source can be converted, and nothing else can. Like `readNumber()`, a string that
+// isn't a number gives nil rather than an error, so that input can be checked. Anything other than
+// a string (or a number, which is passed straight through) is a mistake in the script though.
+fn num(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    match &arguments[0] {
+        Value::Number(number) => Ok(Value::Number(*number)),
+        Value::String(string) => Ok(Scanner::parse_number(string)
+            .map(Value::Number)
+            .unwrap_or(Value::Nil)),
+        other => Err(construct_native_error(format!(
+            "Argument to 'num' must be a string or a number, found {}",
+            other.type_name()
+        ))
+        .with_code(ErrorCode::InvalidArgument)),
+    }
+}
+
+// Stops the script, with the code as the process's exit status. This doesn't exit directly, it
+// unwinds like an error would, and it's up to whoever is running the script what to do about it.
+fn exit(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    let code = expect_number("exit", &arguments[0])?;
+    if code.fract() != 0.0 || !(0.0..=255.0).contains(&code) {
+        return Err(construct_native_error(format!(
+            "Exit code must be a whole number from 0 to 255, found {}",
+            interpreter::stringify(&arguments[0])
+        ))
+        .with_code(ErrorCode::InvalidArgument));
+    }
+    let code = code as exitcode::ExitCode;
+    Err(errors::Error::new(
+        errors::ErrorKind::Exit(code),
+        None,
+        format!("Script requested exit with code {}", code),
+    ))
+}
+
+// How many scopes were freed, mostly so that there's something to see. Collecting happens by itself
+// anyway, this is for when it should happen right now.
+fn collect_garbage(
+    interpreter: &mut Interpreter,
+    _arguments: Vec<Value>,
+) -> Result<Value, errors::Error> {
+    Ok(Value::Number(interpreter.collect_garbage() as f64))
+}
+
+// --- Strings ---
+// Strings are measured and indexed in graphemes, the same as the scanner reads them, so that "é" is
+// one character however it's encoded.
+
+fn len(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    let string = expect_string("len", &arguments[0])?;
+    let length = string.graphemes(USE_EXTENDED_UNICODE).count();
+    Ok(Value::Number(length as f64))
+}
+
+// From `start` up to but not including `end`, so `substring(s, 0, len(s))` is the whole string.
+fn substring(
+    _interpreter: &mut Interpreter,
+    arguments: Vec<Value>,
+) -> Result<Value, errors::Error> {
+    let string = expect_string("substring", &arguments[0])?;
+    let graphemes: Vec<&str> = string.graphemes(USE_EXTENDED_UNICODE).collect();
+    let start = expect_position("substring", graphemes.len(), &arguments[1])?;
+    let end = expect_position("substring", graphemes.len(), &arguments[2])?;
+    if start > end {
+        return Err(construct_native_error(format!(
+            "Start of substring ({}) is after its end ({})",
+            start, end
+        ))
+        .with_code(ErrorCode::InvalidArgument));
+    }
+    Ok(Value::String(graphemes[start..end].concat()))
+}
+
+// The index of the first place the needle starts, or -1 if it's nowhere. Only matches starting on a
+// grapheme count, so an accent on its own isn't found inside "é".
+fn index_of(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    let string = expect_string("indexOf", &arguments[0])?;
+    let needle = expect_string("indexOf", &arguments[1])?;
+    let index = string
+        .grapheme_indices(USE_EXTENDED_UNICODE)
+        .position(|(offset, _)| string[offset..].starts_with(needle));
+    Ok(Value::Number(match index {
+        Some(index) => index as f64,
+        None if needle.is_empty() => string.graphemes(USE_EXTENDED_UNICODE).count() as f64,
+        None => -1.0,
+    }))
+}
+
+fn to_upper(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    let string = expect_string("toUpper", &arguments[0])?;
+    Ok(Value::String(string.to_uppercase()))
+}
+
+fn to_lower(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    let string = expect_string("toLower", &arguments[0])?;
+    Ok(Value::String(string.to_lowercase()))
+}
+
+// --- Math ---
+
+fn floor(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    Ok(Value::Number(
+        expect_number("floor", &arguments[0])?.floor(),
+    ))
+}
+
+fn ceil(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    Ok(Value::Number(expect_number("ceil", &arguments[0])?.ceil()))
+}
+
+fn abs(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    Ok(Value::Number(expect_number("abs", &arguments[0])?.abs()))
+}
+
+// The square root of a negative number is NaN, and follows the same rules as arithmetic that
+// produces NaN. It's only an error with `--strict-nan`.
+fn sqrt(interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    let root = expect_number("sqrt", &arguments[0])?.sqrt();
+    if interpreter.strict_nan && root.is_nan() {
+        return Err(construct_native_error(String::from(
+            "Square root of a negative number produced NaN",
+        ))
+        .with_code(ErrorCode::NotANumber));
+    }
+    Ok(Value::Number(root))
+}
+
+fn min(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    let a = expect_number("min", &arguments[0])?;
+    let b = expect_number("min", &arguments[1])?;
+    Ok(Value::Number(a.min(b)))
+}
+
+fn max(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    let a = expect_number("max", &arguments[0])?;
+    let b = expect_number("max", &arguments[1])?;
+    Ok(Value::Number(a.max(b)))
+}
+
+// --- Random Numbers ---
+
+// A number in [0, 1).
+fn random(interpreter: &mut Interpreter, _arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    Ok(Value::Number(interpreter.random.next_number()))
+}
+
+// A whole number between the two bounds, including both, so `randomInt(1, 6)` is a dice roll.
+fn random_int(
+    interpreter: &mut Interpreter,
+    arguments: Vec<Value>,
+) -> Result<Value, errors::Error> {
+    let low = expect_number("randomInt", &arguments[0])?;
+    let high = expect_number("randomInt", &arguments[1])?;
+    if low.fract() != 0.0 || high.fract() != 0.0 {
+        return Err(construct_native_error(format!(
+            "Bounds of 'randomInt' must be whole numbers, found {} and {}",
+            interpreter::stringify(&arguments[0]),
+            interpreter::stringify(&arguments[1])
+        ))
+        .with_code(ErrorCode::InvalidArgument));
+    }
+    if low > high {
+        return Err(construct_native_error(format!(
+            "Lower bound of 'randomInt' can't be greater than the upper bound, found {} and {}",
+            interpreter::stringify(&arguments[0]),
+            interpreter::stringify(&arguments[1])
+        ))
+        .with_code(ErrorCode::InvalidArgument));
+    }
+    let offset = (interpreter.random.next_number() * (high - low + 1.0)).floor();
+    Ok(Value::Number(low + offset))
+}
+
+// Makes every random number after this predictable, for tests.
+fn set_random_seed(
+    interpreter: &mut Interpreter,
+    arguments: Vec<Value>,
+) -> Result<Value, errors::Error> {
+    let seed = expect_number("setRandomSeed", &arguments[0])?;
+    interpreter.random = Random::from_seed(seed.to_bits());
+    Ok(Value::Nil)
+}
+
+// --- Lists ---
+// Lists are shared, so anything these do to one is seen by everything holding it.
+
+fn list_push(
+    _interpreter: &mut Interpreter,
+    arguments: Vec<Value>,
+) -> Result<Value, errors::Error> {
+    let list = expect_list("listPush", &arguments[0])?;
+    list.borrow_mut().push(arguments[1].clone());
+    Ok(Value::Nil)
+}
+
+// Removes and returns the last element.
+fn list_pop(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    let list = expect_list("listPop", &arguments[0])?;
+    let popped = list.borrow_mut().pop();
+    popped.ok_or_else(|| {
+        construct_native_error(String::from("Can't pop from an empty list"))
+            .with_code(ErrorCode::IndexOutOfBounds)
+    })
+}
+
+fn list_get(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    let list = expect_list("listGet", &arguments[0])?;
+    let length = list.borrow().len();
+    let index = expect_index("listGet", &arguments[0], length, &arguments[1])?;
+    let element = list.borrow()[index].clone();
+    Ok(element)
+}
+
+fn list_set(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    let list = expect_list("listSet", &arguments[0])?;
+    let length = list.borrow().len();
+    let index = expect_index("listSet", &arguments[0], length, &arguments[1])?;
+    list.borrow_mut()[index] = arguments[2].clone();
+    Ok(Value::Nil)
+}
+
+fn list_length(
+    _interpreter: &mut Interpreter,
+    arguments: Vec<Value>,
+) -> Result<Value, errors::Error> {
+    let list = expect_list("listLength", &arguments[0])?;
+    let length = list.borrow().len();
+    Ok(Value::Number(length as f64))
+}
+
+// --- Tuples ---
+
+fn tuple_get(
+    _interpreter: &mut Interpreter,
+    arguments: Vec<Value>,
+) -> Result<Value, errors::Error> {
+    let tuple = match &arguments[0] {
+        Value::Tuple(tuple) => tuple,
+        other => {
+            return Err(construct_native_error(format!(
+                "Argument to 'tupleGet' must be a tuple, found {}",
+                other.type_name()
+            ))
+            .with_code(ErrorCode::InvalidArgument))
+        }
+    };
+    let index = expect_index("tupleGet", &arguments[0], tuple.len(), &arguments[1])?;
+    Ok(tuple[index].clone())
+}
+
+// --- Maps ---
+// Shared in the same way as lists. Keys are always strings.
+
+fn map_get(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    let map = expect_map("mapGet", &arguments[0])?;
+    let key = expect_key("mapGet", &arguments[1])?;
+    let value = map.borrow().get(key).cloned();
+    value.ok_or_else(|| {
+        construct_native_error(format!("Map has no key \"{}\"", key))
+            .with_code(ErrorCode::MissingKey)
+    })
+}
+
+fn map_set(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    let map = expect_map("mapSet", &arguments[0])?;
+    let key = expect_key("mapSet", &arguments[1])?;
+    map.borrow_mut()
+        .insert(key.to_string(), arguments[2].clone());
+    Ok(Value::Nil)
+}
+
+fn map_has(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    let map = expect_map("mapHas", &arguments[0])?;
+    let key = expect_key("mapHas", &arguments[1])?;
+    let has = map.borrow().contains_key(key);
+    Ok(Value::Boolean(has))
+}
+
+// Returns what was removed, or nil if the key wasn't there (so removing twice is fine).
+fn map_remove(
+    _interpreter: &mut Interpreter,
+    arguments: Vec<Value>,
+) -> Result<Value, errors::Error> {
+    let map = expect_map("mapRemove", &arguments[0])?;
+    let key = expect_key("mapRemove", &arguments[1])?;
+    let removed = map.borrow_mut().remove(key);
+    Ok(removed.unwrap_or(Value::Nil))
+}
+
+// A new list, in order, so the same map always gives the same keys.
+fn map_keys(_interpreter: &mut Interpreter, arguments: Vec<Value>) -> Result<Value, errors::Error> {
+    let map = expect_map("mapKeys", &arguments[0])?;
+    let mut keys: Vec<String> = map.borrow().keys().cloned().collect();
+    keys.sort();
+    let keys = keys.into_iter().map(Value::String).collect();
+    Ok(Value::List(Rc::new(RefCell::new(keys))))
+}
+
+/// The state behind `random()` and friends. This is splitmix64, which is nowhere near good enough
+/// for anything secure, but is tiny and more than random enough for scripts.
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    /// Seeded from the clock, so each run is different.
+    pub fn new() -> Self {
+        Random::from_seed(seconds_since_epoch().to_bits())
+    }
+    pub fn from_seed(seed: u64) -> Self {
+        Random { state: seed }
+    }
+    fn next_bits(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut bits = self.state;
+        bits = (bits ^ (bits >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        bits = (bits ^ (bits >> 27)).wrapping_mul(0x94d049bb133111eb);
+        bits ^ (bits >> 31)
+    }
+    // The top 53 bits, which is as many as an f64 can hold exactly.
+    fn next_number(&mut self) -> f64 {
+        (self.next_bits() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl Default for Random {
+    fn default() -> Self {
+        Random::new()
+    }
+}
+
+// -----| Utilities |-----
+
+// There's no system clock to ask in the browser (it panics), so there the time comes from
+// javascript instead.
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+fn seconds_since_epoch() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+fn seconds_since_epoch() -> f64 {
+    crate::wasm::milliseconds_since_epoch() / 1000.0
+}
+
+// Every native that wants a number complains about anything else the same way.
+fn expect_number(native: &str, argument: &Value) -> Result<f64, errors::Error> {
+    match argument {
+        Value::Number(number) => Ok(*number),
+        other => Err(construct_native_error(format!(
+            "Argument to '{}' must be a number, found {}",
+            native,
+            other.type_name()
+        ))
+        .with_code(ErrorCode::InvalidArgument)),
+    }
+}
+
+fn expect_string<'a>(native: &str, argument: &'a Value) -> Result<&'a str, errors::Error> {
+    match argument {
+        Value::String(string) => Ok(string),
+        other => Err(construct_native_error(format!(
+            "Argument to '{}' must be a string, found {}",
+            native,
+            other.type_name()
+        ))
+        .with_code(ErrorCode::InvalidArgument)),
+    }
+}
+
+// Like an index into a string, but it can also be just past the last character, for where a range
+// ends.
+fn expect_position(native: &str, length: usize, argument: &Value) -> Result<usize, errors::Error> {
+    let position = expect_number(native, argument)?;
+    if position.fract() != 0.0 || position < 0.0 || position > length as f64 {
+        return Err(construct_native_error(format!(
+            "Position {} is out of bounds for a string of length {}",
+            interpreter::stringify(argument),
+            length
+        ))
+        .with_code(ErrorCode::IndexOutOfBounds));
+    }
+    Ok(position as usize)
+}
+
+fn expect_list<'a>(native: &str, argument: &'a Value) -> Result<&'a List, errors::Error> {
+    match argument {
+        Value::List(list) => Ok(list),
+        other => Err(construct_native_error(format!(
+            "Argument to '{}' must be a list, found {}",
+            native,
+            other.type_name()
+        ))
+        .with_code(ErrorCode::InvalidArgument)),
+    }
+}
+
+fn expect_map<'a>(native: &str, argument: &'a Value) -> Result<&'a Map, errors::Error> {
+    match argument {
+        Value::Map(map) => Ok(map),
+        other => Err(construct_native_error(format!(
+            "Argument to '{}' must be a map, found {}",
+            native,
+            other.type_name()
+        ))
+        .with_code(ErrorCode::InvalidArgument)),
+    }
+}
+
+fn expect_key<'a>(native: &str, argument: &'a Value) -> Result<&'a str, errors::Error> {
+    match argument {
+        Value::String(key) => Ok(key),
+        other => Err(construct_native_error(format!(
+            "Map keys given to '{}' must be strings, found {}",
+            native,
+            other.type_name()
+        ))
+        .with_code(ErrorCode::InvalidArgument)),
+    }
+}
+
+// Indexes count from 0, and have to be somewhere in the list already.
+fn expect_index(
+    native: &str,
+    sequence: &Value,
+    length: usize,
+    argument: &Value,
+) -> Result<usize, errors::Error> {
+    let index = expect_number(native, argument)?;
+    if index.fract() != 0.0 || index < 0.0 || index >= length as f64 {
+        return Err(construct_native_error(format!(
+            "Index {} is out of bounds for a {} of length {}",
+            interpreter::stringify(argument),
+            sequence.type_name(),
+            length
+        ))
+        .with_code(ErrorCode::IndexOutOfBounds));
+    }
+    Ok(index as usize)
+}
+
+fn construct_native_error(description: String) -> errors::Error {
+    errors::Error::new(errors::ErrorKind::Runtime, None, description)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+    use crate::value::NativeBody;
+
+    // Errors are rendered, since they can't be compared or debug printed themselves.
+    fn call(body: NativeBody, arguments: &[Value]) -> Result<Value, String> {
+        body(
+            &mut Interpreter::new().with_input(Box::new(io::empty())),
+            arguments.to_vec(),
+        )
+        .map_err(|error| error.to_string())
+    }
+
+    fn string(string: &str) -> Value {
+        Value::String(string.to_string())
+    }
+
+    // "e" followed by a combining acute accent, which is one grapheme but two chars and three bytes.
+    const DECOMPOSED: &str = "cafe\u{301}!";
+
+    #[test]
+    fn len_counts_graphemes() {
+        assert_eq!(call(len, &[string("héllo")]).unwrap(), Value::Number(5.0));
+        assert_eq!(
+            call(len, &[string(DECOMPOSED)]).unwrap(),
+            Value::Number(5.0)
+        );
+        assert_eq!(call(len, &[string("👋🏽")]).unwrap(), Value::Number(1.0));
+        assert_eq!(call(len, &[string("")]).unwrap(), Value::Number(0.0));
+    }
+
+    #[test]
+    fn substring_takes_grapheme_positions() {
+        let arguments = [string(DECOMPOSED), Value::Number(3.0), Value::Number(4.0)];
+        assert_eq!(call(substring, &arguments).unwrap(), string("e\u{301}"));
+        let arguments = [string("wörld"), Value::Number(0.0), Value::Number(5.0)];
+        assert_eq!(call(substring, &arguments).unwrap(), string("wörld"));
+        let arguments = [string("wörld"), Value::Number(2.0), Value::Number(2.0)];
+        assert_eq!(call(substring, &arguments).unwrap(), string(""));
+    }
+
+    #[test]
+    fn substring_rejects_positions_out_of_range() {
+        for (start, end) in [(0.0, 6.0), (-1.0, 2.0), (0.5, 2.0), (3.0, 2.0)] {
+            let arguments = [string("wörld"), Value::Number(start), Value::Number(end)];
+            assert!(call(substring, &arguments).is_err(), "{} to {}", start, end);
+        }
+    }
+
+    #[test]
+    fn index_of_counts_graphemes() {
+        let arguments = [string("héllo wörld"), string("wörld")];
+        assert_eq!(call(index_of, &arguments).unwrap(), Value::Number(6.0));
+        let arguments = [string("héllo"), string("z")];
+        assert_eq!(call(index_of, &arguments).unwrap(), Value::Number(-1.0));
+    }
+
+    #[test]
+    fn index_of_only_matches_whole_graphemes() {
+        let arguments = [string(DECOMPOSED), string("\u{301}")];
+        assert_eq!(call(index_of, &arguments).unwrap(), Value::Number(-1.0));
+    }
+
+    #[test]
+    fn index_of_finds_an_empty_needle_at_the_start() {
+        let arguments = [string("abc"), string("")];
+        assert_eq!(call(index_of, &arguments).unwrap(), Value::Number(0.0));
+        let arguments = [string(""), string("")];
+        assert_eq!(call(index_of, &arguments).unwrap(), Value::Number(0.0));
+    }
+
+    #[test]
+    fn case_conversion_handles_non_ascii() {
+        assert_eq!(call(to_upper, &[string("héllo")]).unwrap(), string("HÉLLO"));
+        assert_eq!(call(to_lower, &[string("ÉCOLE")]).unwrap(), string("école"));
+    }
+
+    #[test]
+    fn tuple_get_reads_by_index() {
+        let tuple = Value::Tuple(Rc::from(vec![string("a"), Value::Nil]));
+        assert_eq!(
+            call(tuple_get, &[tuple.clone(), Value::Number(0.0)]).unwrap(),
+            string("a")
+        );
+        assert_eq!(
+            call(tuple_get, &[tuple.clone(), Value::Number(1.0)]).unwrap(),
+            Value::Nil
+        );
+        for index in [2.0, -1.0, 0.5] {
+            let error = call(tuple_get, &[tuple.clone(), Value::Number(index)]).unwrap_err();
+            assert!(
+                error.contains("out of bounds for a tuple of length 2"),
+                "{}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn tuple_get_rejects_lists() {
+        let list = Value::List(Rc::new(RefCell::new(vec![Value::Nil])));
+        assert_eq!(
+            call(tuple_get, &[list, Value::Number(0.0)]).unwrap_err(),
+            "error[E3011]: Argument to 'tupleGet' must be a tuple, found list"
+        );
+    }
+
+    fn map(entries: &[(&str, Value)]) -> Value {
+        let entries = entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect();
+        Value::Map(Rc::new(RefCell::new(entries)))
+    }
+
+    #[test]
+    fn map_natives_read_and_change_the_map() {
+        let m = map(&[("a", Value::Number(1.0))]);
+        assert_eq!(
+            call(map_get, &[m.clone(), string("a")]).unwrap(),
+            Value::Number(1.0)
+        );
+        assert_eq!(
+            call(map_has, &[m.clone(), string("b")]).unwrap(),
+            Value::Boolean(false)
+        );
+        call(map_set, &[m.clone(), string("b"), Value::Nil]).unwrap();
+        assert_eq!(
+            call(map_has, &[m.clone(), string("b")]).unwrap(),
+            Value::Boolean(true)
+        );
+        assert_eq!(
+            call(map_remove, &[m.clone(), string("a")]).unwrap(),
+            Value::Number(1.0)
+        );
+        assert_eq!(
+            call(map_remove, &[m.clone(), string("a")]).unwrap(),
+            Value::Nil
+        );
+        assert!(call(map_get, &[m, string("a")])
+            .unwrap_err()
+            .contains("Map has no key \"a\""));
+    }
+
+    #[test]
+    fn map_keys_are_sorted() {
+        let m = map(&[("b", Value::Nil), ("c", Value::Nil), ("a", Value::Nil)]);
+        let keys = call(map_keys, &[m]).unwrap();
+        assert_eq!(keys.to_string(), "[\"a\", \"b\", \"c\"]");
+    }
+
+    #[test]
+    fn map_natives_reject_other_types() {
+        assert!(call(map_get, &[string("a"), string("a")]).is_err());
+        let m = map(&[]);
+        assert!(call(map_has, &[m, Value::Number(1.0)])
+            .unwrap_err()
+            .contains("Map keys given to 'mapHas' must be strings, found number"));
+    }
+
+    #[test]
+    fn string_natives_reject_other_types() {
+        assert!(call(len, &[Value::Number(3.0)]).is_err());
+        assert!(call(index_of, &[string("a"), Value::Nil]).is_err());
+    }
+
+    #[test]
+    fn math_natives_reject_other_types_the_same_way() {
+        let bodies: [(&str, NativeBody); 6] = [
+            ("floor", floor),
+            ("ceil", ceil),
+            ("abs", abs),
+            ("sqrt", sqrt),
+            ("min", min),
+            ("max", max),
+        ];
+        for (name, body) in bodies {
+            let error = call(body, &[string("1"), Value::Number(1.0)]).unwrap_err();
+            assert!(
+                error.contains(&format!(
+                    "error[E3011]: Argument to '{}' must be a number, found string",
+                    name
+                )),
+                "{}",
+                error
+            );
+        }
+        let error = call(random_int, &[Value::Number(2.0), Value::Number(1.0)]).unwrap_err();
+        assert!(error.contains("can't be greater"), "{}", error);
+    }
+
+    #[test]
+    fn num_reads_whatever_the_scanner_does() {
+        let cases = [
+            ("42.5", 42.5),
+            ("  7 \n", 7.0),
+            ("-3", -3.0),
+            ("0x1F", 31.0),
+            ("0b1010", 10.0),
+            ("1_000", 1000.0),
+            ("Infinity", f64::INFINITY),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(
+                call(num, &[string(source)]).unwrap(),
+                Value::Number(expected),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn num_gives_nil_for_anything_else() {
+        for source in ["abc", "", "1 2", "4a", "1e5", "--1", "nil"] {
+            assert_eq!(
+                call(num, &[string(source)]).unwrap(),
+                Value::Nil,
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn num_rejects_other_types_by_name() {
+        let error = call(num, &[Value::Boolean(true)]).unwrap_err();
+        assert!(error.contains("found boolean"), "{}", error);
+        let error = call(num, &[Value::Nil]).unwrap_err();
+        assert!(error.contains("found nil"), "{}", error);
+    }
+
+    // Whatever `str()` shows for a number reads back as that number.
+    #[test]
+    fn num_round_trips_through_str() {
+        for source in [
+            "0",
+            "-0",
+            "42",
+            "42.5",
+            "-0.001",
+            "0.30000000000000004",
+            "Infinity",
+        ] {
+            let number = call(num, &[string(source)]).unwrap();
+            assert_eq!(call(str, &[number]).unwrap(), string(source));
+        }
+    }
+
+    // Runs a whole program with the given input, for everything it printed.
+    fn run(source: &str, input: &str) -> Result<String, String> {
+        let (program, log) = crate::parse_source(source.to_string());
+        assert_eq!(log.error_count(), 0);
+        let output = interpreter::OutputBuffer::default();
+        Interpreter::new()
+            .with_input(Box::new(io::Cursor::new(input.to_string())))
+            .with_writer(Box::new(output.clone()))
+            .interpret(&program)
+            .map_err(|error| error.to_string())?;
+        Ok(output.contents())
+    }
+
+    #[test]
+    fn read_line_strips_line_endings_and_gives_nil_at_the_end() {
+        let source = "print readLine(); print readLine(); print readLine(); print readLine();";
+        assert_eq!(
+            run(source, "first\nsecond\r\nlast").unwrap(),
+            "first\nsecond\nlast\nnil\n"
+        );
+    }
+
+    #[test]
+    fn read_number_gives_nil_for_anything_else() {
+        let source = "print readNumber(); print readNumber(); print readNumber();";
+        assert_eq!(run(source, " 42.5 \nabc\n").unwrap(), "42.5\nnil\nnil\n");
+    }
+
+    #[test]
+    fn plays_guess_the_number() {
+        let source = "
+            var secret = 7;
+            var guess = nil;
+            var guesses = 0;
+            while (guess != secret) {
+                guess = readNumber();
+                if (guess == nil) {
+                    print \"not a number\";
+                } else {
+                    guesses = guesses + 1;
+                    if (guess < secret) print \"higher\";
+                    if (guess > secret) print \"lower\";
+                }
+            }
+            print \"got it in \" + str(guesses);
+        ";
+        assert_eq!(
+            run(source, "5\nten\n9\n7\n").unwrap(),
+            "higher\nnot a number\nlower\ngot it in 3\n"
+        );
+    }
+}
@@ -0,0 +1,239 @@
+use std::io;
+use std::rc::Rc;
+
+use crate::interpreter::{self, Interpreter};
+use crate::parser::{
+    BlockStmt, Expr, ExprArena, ExprId, FunctionStmt, IfStmt, Program, Stmt, WhileStmt,
+};
+
+// -----| Constant Folding |-----
+
+/// Replaces operations on constants with their results ahead of time, so `(2 + 3) * 4` is just
+/// `20` by the time the interpreter sees it. The results are worked out by the interpreter itself,
+/// so there's no chance of the two disagreeing. Branches that can never run are dropped as well.
+pub struct ConstantFolder<'a> {
+    expressions: &'a mut ExprArena,
+}
+
+impl<'a> ConstantFolder<'a> {
+    pub fn new(expressions: &'a mut ExprArena) -> Self {
+        ConstantFolder { expressions }
+    }
+    pub fn fold_program(program: &mut Program) {
+        let mut folder = ConstantFolder::new(&mut program.expressions);
+        folder.fold();
+        let statements = std::mem::take(&mut program.statements);
+        program.statements = folder.eliminate_dead_branches(statements);
+    }
+    // Since children always come before their parents in the arena, a single pass in order is
+    // enough for folds to cascade up the tree.
+    pub fn fold(&mut self) {
+        let ids: Vec<ExprId> = self.expressions.ids().collect();
+        // Nothing being folded can read input or print, or do anything else for that matter.
+        let mut interpreter = Interpreter::new()
+            .with_input(Box::new(io::empty()))
+            .with_writer(Box::new(io::sink()));
+        // Whether NaN is an error is only decided at runtime, so anything producing it is left
+        // for then.
+        interpreter.strict_nan = true;
+        for id in ids {
+            if self.is_foldable(id) {
+                // Anything that fails, like `1 + nil`, is left alone to fail at runtime.
+                let result = interpreter.evaluate(id, self.expressions);
+                if let Some(literal) = result.ok().and_then(|value| value.to_literal()) {
+                    self.expressions.replace(id, Expr::Literal(literal));
+                }
+            }
+        }
+    }
+    // Only operations directly on literals. Groupings count too, otherwise the parentheses in
+    // `(2 + 3) * 4` would stop the outer fold.
+    fn is_foldable(&self, id: ExprId) -> bool {
+        match &self.expressions.get(id).expr {
+            Expr::Binary(binary) => self.is_literal(binary.left) && self.is_literal(binary.right),
+            Expr::Logical(logical) => {
+                self.is_literal(logical.left) && self.is_literal(logical.right)
+            }
+            Expr::Unary(unary) => self.is_literal(unary.right),
+            Expr::Typeof(operand) => self.is_literal(*operand),
+            Expr::Grouping(inner) => self.is_literal(*inner),
+            _ => false,
+        }
+    }
+    // --- Dead Branches ---
+    // Expressions have to be folded first, otherwise only the most literal of conditions (like
+    // `if (true)`) would be caught.
+    pub fn eliminate_dead_branches(&self, statements: Vec<Stmt>) -> Vec<Stmt> {
+        statements
+            .into_iter()
+            .filter_map(|statement| self.eliminate_dead_branch(statement))
+            .collect()
+    }
+    // Returns nothing when the whole statement can go.
+    fn eliminate_dead_branch(&self, statement: Stmt) -> Option<Stmt> {
+        match statement {
+            Stmt::If(IfStmt {
+                condition,
+                then_branch,
+                else_branch,
+            }) => {
+                if let Expr::Literal(literal) = &self.expressions.get(condition).expr {
+                    let value = literal.clone().into();
+                    if interpreter::is_truthy(&value) {
+                        self.eliminate_dead_branch(*then_branch)
+                    } else {
+                        else_branch.and_then(|branch| self.eliminate_dead_branch(*branch))
+                    }
+                } else {
+                    // An else that goes away entirely can just be left off, but there has to be
+                    // something to run when the condition holds, so an empty block stands in.
+                    let then_branch = self
+                        .eliminate_dead_branch(*then_branch)
+                        .unwrap_or_else(empty_block);
+                    let else_branch =
+                        else_branch.and_then(|branch| self.eliminate_dead_branch(*branch));
+                    Some(Stmt::If(IfStmt {
+                        condition,
+                        then_branch: Box::new(then_branch),
+                        else_branch: else_branch.map(Box::new),
+                    }))
+                }
+            }
+            // A loop that never runs can go, but one that always does still has to loop.
+            Stmt::While(WhileStmt {
+                condition,
+                body,
+                increment,
+            }) => {
+                if let Expr::Literal(literal) = &self.expressions.get(condition).expr {
+                    if !interpreter::is_truthy(&literal.clone().into()) {
+                        return None;
+                    }
+                }
+                let body = self
+                    .eliminate_dead_branch(*body)
+                    .unwrap_or_else(empty_block);
+                Some(Stmt::While(WhileStmt {
+                    condition,
+                    body: Box::new(body),
+                    increment,
+                }))
+            }
+            Stmt::Block(BlockStmt { statements }) => Some(Stmt::Block(BlockStmt {
+                statements: self.eliminate_dead_branches(statements),
+            })),
+            Stmt::Function(mut function) => {
+                self.eliminate_dead_branches_in_function(&mut function);
+                Some(Stmt::Function(function))
+            }
+            Stmt::Class(mut class) => {
+                for method in class.methods.iter_mut() {
+                    self.eliminate_dead_branches_in_function(method);
+                }
+                Some(Stmt::Class(class))
+            }
+            _ => Some(statement),
+        }
+    }
+    // Nothing else can have a hold of the function yet, straight out of the parser, but if something
+    // does then it's left as it is.
+    fn eliminate_dead_branches_in_function(&self, function: &mut Rc<FunctionStmt>) {
+        if let Some(function) = Rc::get_mut(function) {
+            let body = std::mem::take(&mut function.body);
+            function.body = self.eliminate_dead_branches(body);
+        }
+    }
+    // --- Utilities ---
+    fn is_literal(&self, id: ExprId) -> bool {
+        matches!(self.expressions.get(id).expr, Expr::Literal(_))
+    }
+}
+
+fn empty_block() -> Stmt {
+    Stmt::Block(BlockStmt {
+        statements: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, scanner::Scanner};
+
+    // The program as the printer shows it once it's been folded.
+    fn folded(source: &str) -> String {
+        let scanner = Scanner::from_source(source.to_string());
+        let mut program = Parser::new(scanner.tokens()).parse();
+        ConstantFolder::fold_program(&mut program);
+        program.to_string()
+    }
+
+    // --- Folding ---
+
+    #[test]
+    fn folds_operations_on_constants() {
+        assert_eq!(folded("print (2 + 3) * 4;"), "Print Statement: 20");
+        assert_eq!(folded("print !(1 < 2);"), "Print Statement: false");
+        assert_eq!(folded("print \"a\" + \"b\";"), "Print Statement: ab");
+    }
+
+    #[test]
+    fn folds_as_much_of_an_expression_as_it_can() {
+        assert_eq!(folded("print a + 1 * 2;"), "Print Statement: (+ (var a) 2)");
+    }
+
+    // Those are for the interpreter to report, where it would have anyway.
+    #[test]
+    fn leaves_errors_and_nan_alone() {
+        assert_eq!(folded("print 1 + nil;"), "Print Statement: (+ 1 nil)");
+        assert_eq!(folded("print 0 / 0;"), "Print Statement: (/ 0 0)");
+    }
+
+    #[test]
+    fn folded_programs_still_give_the_right_answer() {
+        let output = interpreter::OutputBuffer::default();
+        let (program, log) = crate::parse_source(String::from("print (2 + 3) * 4;"));
+        assert!(log.is_empty());
+        Interpreter::new()
+            .with_writer(Box::new(output.clone()))
+            .interpret(&program)
+            .unwrap();
+        assert_eq!(output.contents(), "20\n");
+    }
+
+    // --- Dead Branches ---
+
+    #[test]
+    fn keeps_only_the_branch_that_runs() {
+        assert_eq!(
+            folded("if (true) print 1; else print 2;"),
+            "Print Statement: 1"
+        );
+        assert_eq!(
+            folded("if (1 > 2) print 1; else print 2;"),
+            "Print Statement: 2"
+        );
+    }
+
+    #[test]
+    fn drops_ifs_that_never_run() {
+        assert_eq!(folded("if (false) print 1;"), "");
+        assert_eq!(folded("while (nil) print 1;"), "");
+    }
+
+    #[test]
+    fn drops_dead_elses_under_a_live_condition() {
+        assert_eq!(
+            folded("if (a) print 1; else if (false) print 2;"),
+            "If Statement: (var a)\n  Then: Print Statement: 1"
+        );
+    }
+
+    #[test]
+    fn drops_dead_branches_inside_functions() {
+        assert_eq!(
+            folded("fun f() { if (true) return 1; else return 2; }"),
+            "Function Statement: f() {\n  Return Statement: 1\n}"
+        );
+    }
+}
@@ -1,6 +1,15 @@
+use std::fmt;
+use std::rc::Rc;
+
+use rlox_derive::AstNode;
+
+use crate::error_codes::ErrorCode;
 use crate::errors;
+use crate::interpreter;
 use crate::language_utilities::enum_variant_equal;
 use crate::scanner::{self, WhitespaceKind};
+use crate::source_file;
+use crate::value::Value;
 
 // -----| Syntax Grammer |-----
 //
@@ -8,14 +17,29 @@ use crate::scanner::{self, WhitespaceKind};
 
 // -----| Declaration Grammar |-----
 //
-// declaration  -> varDecl | statement ;
+// declaration  -> classDecl | funDecl | varDecl | statement ;
+// classDecl    -> "class" IDENTIFIER ( "<" IDENTIFIER )? "{" function* "}" ;
+// funDecl      -> "fun" function ;
+// function     -> IDENTIFIER "(" parameters? ")" block ;
+// parameters   -> IDENTIFIER ( "," IDENTIFIER )* ;
 // varDecl      -> "var" IDENTIFIER ( "=" expression )? ";" ;
 
 // -----| Statement Grammar |-----
 //
-// statement    -> epxrStmt | print Stmt ;
+// statement    -> exprStmt | forStmt | ifStmt | printStmt | returnStmt | whileStmt
+//                 | continueStmt | block ;
 // exprStmt     -> expression ";" ;
+// forStmt      -> "for" "(" ( varDecl | exprStmt | ";" ) expression? ";" expression? ")"
+//                 statement ;
+// ifStmt       -> "if" "(" expression ")" statement ( "else" statement )? ;
 // printStmt    -> "print" expression ";" ;
+// returnStmt   -> "return" expression? ";" ;
+// whileStmt    -> "while" "(" expression ")" statement ;
+// continueStmt -> "continue" ";" ;
+// block        -> "{" declaration* "}" ;
+//
+// A statement starting with "{" is a block, unless it's followed by a key and ":", or by "}" ";",
+// in which case it's an exprStmt starting with a map.
 
 const STATEMENT_BEGINNING_TOKENS: &[scanner::Token] = &[
     scanner::Token::Class,
@@ -33,37 +57,112 @@ pub enum Stmt {
     Expression(ExprStmt),
     Print(PrintStmt),
     Var(VarStmt),
+    Block(BlockStmt),
+    If(IfStmt),
+    While(WhileStmt),
+    Continue(ContinueStmt),
+    /// Shared with every function value made from it, which outlive the program they came from.
+    Function(Rc<FunctionStmt>),
+    Return(ReturnStmt),
+    Class(ClassStmt),
 }
 
 pub struct ExprStmt {
-    pub expression: Expr,
+    pub expression: ExprId,
 }
 
 // TODO: Get rid of this as soon as you have a standard library. This is a bootstrapping thing.
 pub struct PrintStmt {
-    pub expression: Expr,
+    pub expression: ExprId,
 }
 
 pub struct VarStmt {
     pub name: scanner::Identifier,
-    pub initializer: Option<Expr>,
+    pub name_location: source_file::SourceSpan,
+    pub initializer: Option<ExprId>,
+}
+
+pub struct BlockStmt {
+    pub statements: Vec<Stmt>,
+}
+
+pub struct IfStmt {
+    pub condition: ExprId,
+    pub then_branch: Box<Stmt>,
+    pub else_branch: Option<Box<Stmt>>,
+}
+
+// There's no for statement, `for` loops are desugared into these, see `for_statement()`.
+pub struct WhileStmt {
+    pub condition: ExprId,
+    pub body: Box<Stmt>,
+    /// Run after every pass through the body, including ones cut short by `continue`. Only `for`
+    /// loops have one, it can't be part of the body since `continue` would skip it.
+    pub increment: Option<ExprId>,
+}
+
+pub struct ContinueStmt {
+    pub location: source_file::SourceSpan,
+}
+
+pub struct FunctionStmt {
+    pub name: scanner::Identifier,
+    pub name_location: source_file::SourceSpan,
+    pub parameters: Vec<Parameter>,
+    pub body: Vec<Stmt>,
+}
+
+pub struct Parameter {
+    pub name: scanner::Identifier,
+    pub location: source_file::SourceSpan,
+}
+
+pub struct ClassStmt {
+    pub name: scanner::Identifier,
+    pub name_location: source_file::SourceSpan,
+    /// Always a variable, but kept as an expression since that's how it's looked up.
+    pub superclass: Option<ExprId>,
+    pub methods: Vec<Rc<FunctionStmt>>,
+}
+
+pub struct ReturnStmt {
+    /// Where the `return` keyword is, since there mightn't be a value to point at.
+    pub location: source_file::SourceSpan,
+    pub value: Option<ExprId>,
 }
 
 // -----| Expression Grammer |-----
 //
 // In increasing order of precedence
 //
-// expression  -> ternary ;
-// ternary     -> equality ( "?" equality ":" equality )* ;
+// expression  -> assignment ;
+// assignment  -> ( call "." )? IDENTIFIER "=" assignment | ternary ;
+// ternary     -> logic_or ( "?" logic_or ":" logic_or )* ;
+// logic_or    -> logic_and ( "or" logic_and )* ;
+// logic_and   -> equality ( "and" equality )* ;
 // equality    -> comparison ( ( "!=" | "==" ) comparison )* ;
-// comparison  -> term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
+// comparison  -> bitwiseOr ( ( ">" | ">=" | "<" | "<=" ) bitwiseOr )* ;
+// bitwiseOr   -> bitwiseXor ( "|" bitwiseXor )* ;
+// bitwiseXor  -> bitwiseAnd ( "^" bitwiseAnd )* ;
+// bitwiseAnd  -> shift ( "&" shift )* ;
+// shift       -> term ( ( "<<" | ">>" ) term )* ;
 // term        -> factor ( ( "-" | "+" ) factor )* ;
-// factor      -> unary ( ( "/" | "*" ) unary )* ;
-// unary       -> ( "!" | "-" ) unary | primary ;
-// primary     -> NUMBER| | STRING | "true" | "false" | "nil" | "(" expression ")" | IDENTIFIER ;
+// factor      -> unary ( ( "/" | "*" | "%" ) unary )* ;
+// unary       -> ( "!" | "-" | "~" | "typeof" ) unary | call ;
+// call        -> primary ( "(" arguments? ")" | "." IDENTIFIER )* ;
+// arguments   -> expression ( "," expression )* ;
+// primary     -> NUMBER | STRING | "true" | "false" | "nil" | "this" | IDENTIFIER
+//                | "super" "." IDENTIFIER | "(" expression ")" | list | map | tuple ;
+// list        -> "[" ( expression ( "," expression )* ","? )? "]" ;
+// map         -> "{" ( entry ( "," entry )* ","? )? "}" ;
+// entry       -> ( STRING | IDENTIFIER ) ":" expression ;
+// tuple       -> "(" ( expression "," ( expression ( "," expression )* ","? )? )? ")" ;
+//
+// STRING covers all three ways of writing one: quoted, raw (`r"..."` or `r#"..."#`) and heredocs
+// (`<<<END` up to a line that's only `END`). Only the scanner cares which, they all parse the same.
 
 // TODO: Really think about how clone and copy are to be implemented here.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum LiteralKind {
     Number(f64),
     String(String),
@@ -71,59 +170,250 @@ pub enum LiteralKind {
     Nil,
 }
 
-#[derive(Debug)]
+// Exactly as `print` would show the same value, so `nil`, `true` and `false` are all lowercase
+// rather than rust's `Nil` and so on.
+impl fmt::Display for LiteralKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", interpreter::stringify(&Value::from(self.clone())))
+    }
+}
+
+#[derive(Debug, Clone, AstNode)]
 pub enum Expr {
     Binary(BinaryExpr),
     Ternary(TernaryExpr),
-    Grouping(Box<Expr>),
+    Logical(LogicalExpr),
+    Grouping(ExprId),
     Unary(UnaryExpr),
+    Typeof(ExprId),
     Literal(LiteralKind),
-    // Variable(scanner::Identifier),
+    List(ListExpr),
+    Map(MapExpr),
+    Tuple(TupleExpr),
+    Variable(scanner::Identifier),
+    Assign(AssignExpr),
+    Get(GetExpr),
+    Set(SetExpr),
+    Call(CallExpr),
+    This(ThisExpr),
+    Super(SuperExpr),
 }
 
 // TODO: Perhaps convert these Tokens to SourceTokens
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BinaryExpr {
-    pub left: Box<Expr>,
+    pub left: ExprId,
     pub operator: scanner::Token,
-    pub right: Box<Expr>,
+    pub right: ExprId,
 }
 
 // We only have one of these, so the operators are implicit
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TernaryExpr {
-    pub condition: Box<Expr>,
-    pub left_result: Box<Expr>,
-    pub right_result: Box<Expr>,
+    pub condition: ExprId,
+    pub left_result: ExprId,
+    pub right_result: ExprId,
+}
+
+// Kept apart from binaries, since the right side isn't always evaluated.
+#[derive(Debug, Clone)]
+pub struct LogicalExpr {
+    pub left: ExprId,
+    pub operator: scanner::Token,
+    pub right: ExprId,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct UnaryExpr {
     pub operator: scanner::Token,
-    pub right: Box<Expr>,
+    pub right: ExprId,
+}
+
+#[derive(Debug, Clone)]
+pub struct AssignExpr {
+    pub name: scanner::Identifier,
+    pub value: ExprId,
+    /// Where the "=" is, mostly so that it can be pointed at by diagnostics.
+    pub equal_location: source_file::SourceSpan,
+}
+
+// In a chain like `a.b.c` any one of the links could be the one that fails, so property expressions
+// keep hold of where their own name is.
+#[derive(Debug, Clone)]
+pub struct GetExpr {
+    pub object: ExprId,
+    pub name: scanner::Identifier,
+    pub name_location: source_file::SourceSpan,
+}
+
+#[derive(Debug, Clone)]
+pub struct SetExpr {
+    pub object: ExprId,
+    pub name: scanner::Identifier,
+    pub name_location: source_file::SourceSpan,
+    pub value: ExprId,
+}
+
+// There's nothing to `this` but where it is, which the arena already has.
+#[derive(Debug, Clone)]
+pub struct ThisExpr;
+
+/// `super.method`, which can only ever look up a method, never call it outright.
+#[derive(Debug, Clone)]
+pub struct SuperExpr {
+    pub keyword_location: source_file::SourceSpan,
+    pub method: scanner::Identifier,
+    pub method_location: source_file::SourceSpan,
+}
+
+/// `[a, b, c]`, which makes a new list each time it's evaluated.
+#[derive(Debug, Clone)]
+pub struct ListExpr {
+    pub elements: Vec<ExprId>,
+}
+
+/// `{"a": 1, b: 2}`, which makes a new map each time it's evaluated. Keys are always strings, a
+/// bare name is just a shorter way of writing one.
+#[derive(Debug, Clone)]
+pub struct MapExpr {
+    pub entries: Vec<(String, ExprId)>,
+}
+
+/// `(a, b, c)`, or `(a,)` with one element and `()` with none. Told apart from a grouping by the
+/// comma.
+#[derive(Debug, Clone)]
+pub struct TupleExpr {
+    pub elements: Vec<ExprId>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CallExpr {
+    pub callee: ExprId,
+    pub arguments: Vec<ExprId>,
+}
+
+// -----| Expression Arena |-----
+
+/// Refers to an expression by its position in the `ExprArena` it was parsed into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExprId(u32);
+
+#[derive(Debug, Clone)]
+pub struct ExprNode {
+    pub expr: Expr,
+    /// All of the source making up the expression, children included.
+    pub location: source_file::SourceSpan,
+}
+
+/// Every expression parsed out of a source lives here, and refers to its children by id rather
+/// than owning them. Besides saving an allocation per node, this gives each node a stable identity
+/// that later passes can key their own data on.
+///
+/// Cloning an arena only shares it, so that functions can cheaply keep hold of the expressions
+/// they were parsed with. Changing a shared arena copies it first, though nothing needs to change
+/// one once it's being run.
+#[derive(Debug, Clone)]
+pub struct ExprArena {
+    nodes: Rc<Vec<ExprNode>>,
+}
+
+impl ExprArena {
+    pub fn new() -> Self {
+        ExprArena {
+            nodes: Rc::new(Vec::new()),
+        }
+    }
+    pub fn with_capacity(capacity: usize) -> Self {
+        ExprArena {
+            nodes: Rc::new(Vec::with_capacity(capacity)),
+        }
+    }
+    pub fn get(&self, id: ExprId) -> &ExprNode {
+        &self.nodes[id.0 as usize]
+    }
+    /// Swaps out the expression at `id`, keeping its location.
+    pub fn replace(&mut self, id: ExprId, expr: Expr) {
+        Rc::make_mut(&mut self.nodes)[id.0 as usize].expr = expr;
+    }
+    // Children are always parsed, and so pushed, before their parents, so this visits every child
+    // before its parent.
+    pub fn ids(&self) -> impl Iterator<Item = ExprId> {
+        (0..self.nodes.len() as u32).map(ExprId)
+    }
+    fn push(&mut self, expr: Expr, location: source_file::SourceSpan) -> ExprId {
+        let id = ExprId(self.nodes.len() as u32);
+        Rc::make_mut(&mut self.nodes).push(ExprNode { expr, location });
+        id
+    }
+}
+
+impl Default for ExprArena {
+    fn default() -> Self {
+        ExprArena::new()
+    }
 }
 
 // -----| Token -> Expression lists |-----
 
-const EQUALITY_TOKENS: &[scanner::Token] = &[scanner::Token::BangEqual, scanner::Token::EqualEqual];
+// These are functions rather than constant slices so that they never have to construct tokens, and
+// so that they keep working if any of these tokens ever carry data.
 
-const COMPARISON_TOKENS: &[scanner::Token] = &[
-    scanner::Token::Greater,
-    scanner::Token::GreaterEqual,
-    scanner::Token::Less,
-    scanner::Token::LessEqual,
-];
+fn is_equality_token(token: &scanner::Token) -> bool {
+    matches!(
+        token,
+        scanner::Token::BangEqual | scanner::Token::EqualEqual
+    )
+}
 
-const TERM_TOKENS: &[scanner::Token] = &[scanner::Token::Minus, scanner::Token::Plus];
+fn is_comparison_token(token: &scanner::Token) -> bool {
+    matches!(
+        token,
+        scanner::Token::Greater
+            | scanner::Token::GreaterEqual
+            | scanner::Token::Less
+            | scanner::Token::LessEqual
+    )
+}
 
-const FACTOR_TOKENS: &[scanner::Token] = &[scanner::Token::Slash, scanner::Token::Star];
+fn is_shift_token(token: &scanner::Token) -> bool {
+    matches!(
+        token,
+        scanner::Token::LessLess | scanner::Token::GreaterGreater
+    )
+}
 
-const UNARY_TOKENS: &[scanner::Token] = &[scanner::Token::Bang, scanner::Token::Minus];
+fn is_term_token(token: &scanner::Token) -> bool {
+    matches!(token, scanner::Token::Minus | scanner::Token::Plus)
+}
+
+fn is_factor_token(token: &scanner::Token) -> bool {
+    matches!(
+        token,
+        scanner::Token::Slash | scanner::Token::Star | scanner::Token::Percent
+    )
+}
+
+fn is_unary_token(token: &scanner::Token) -> bool {
+    matches!(
+        token,
+        scanner::Token::Bang | scanner::Token::Minus | scanner::Token::Tilde
+    )
+}
 
 const TERNARY_TEST_TOKEN: scanner::Token = scanner::Token::QuestionMark;
 
 const TERNARY_BRANCH_TOKEN: scanner::Token = scanner::Token::Colon;
 
+const ASSIGNMENT_TOKEN: scanner::Token = scanner::Token::Equal;
+
+const PROPERTY_ACCESS_TOKEN: scanner::Token = scanner::Token::Dot;
+
+const CALL_TOKEN: scanner::Token = scanner::Token::LeftParen;
+
+// Following the book, which keeps this low enough for a bytecode implementation to fit the count in
+// a single byte.
+const MAX_ARGUMENTS: usize = 255;
+
 // -----| Token Exemplars |-----
 
 // TODO: Find out a more rustish way of handling the case where you need to compare the type of enum
@@ -133,26 +423,142 @@ const WHITESPACE_EXEMPLAR: scanner::Token = scanner::Token::Whitespace(Whitespac
 
 // -----| Parsing |-----
 
+/// The statements of a source, along with the expressions they refer to.
+pub struct Program {
+    pub statements: Vec<Stmt>,
+    pub expressions: ExprArena,
+}
+
+pub enum ReplInput {
+    Expression(ExprId, ExprArena),
+    Statements(Program),
+    /// The input stopped partway through something, like an unclosed block, and needs more lines.
+    Incomplete,
+}
+
 pub struct Parser {
     tokens: Vec<scanner::SourceToken>,
     /// The actual index we use to iterate throuh the tokens.
     index: usize,
     // cursor: source_file::SourceSpan, // Should this be used?
+    expressions: ExprArena,
     error_log: errors::ErrorLog,
+    /// How many errors had been logged when a rule first hit the end of the tokens while still
+    /// expecting more, if one has.
+    errors_before_running_out: Option<usize>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<scanner::SourceToken>) -> Self {
+        // Every expression consumes at least one token, so this is enough room that the arena
+        // never has to grow.
+        let expressions = ExprArena::with_capacity(tokens.len());
         Parser {
             tokens,
             index: 0,
             // cursor: source_file::SourceSpan::new(),
+            expressions,
             error_log: errors::ErrorLog::new(),
+            errors_before_running_out: None,
         }
     }
     // --- Drivers ---
+    pub fn parse(&mut self) -> Program {
+        self.strip_whitespace();
+        self.parse_program()
+    }
+    /// Parses the tokens as a single expression, for evaluating expressions on their own rather
+    /// than whole programs. Anything left over after the expression is an error, as is there not
+    /// being an expression at all. The expression's nodes come with it, since the id alone means
+    /// nothing without them.
+    pub fn parse_expression(&mut self) -> Result<(ExprId, ExprArena), errors::Error> {
+        self.strip_whitespace();
+        if self.peek_next_token().is_none() {
+            // There's no previous token to point at, so this points at the Eof instead.
+            return Err(errors::Error::parsing(
+                self.tokens[self.index].location_span,
+                "Expected expression, found end of input",
+            )
+            .with_code(ErrorCode::ExpectedExpression));
+        }
+        let expression = self.expression()?;
+        if let Some(source_token) = self.peek_next_token() {
+            return Err(errors::Error::parsing(
+                source_token.location_span,
+                format!(
+                    "Expected end of expression, instead found '{}'",
+                    source_token.token
+                ),
+            )
+            .with_code(ErrorCode::TrailingInput));
+        }
+        Ok((expression, std::mem::take(&mut self.expressions)))
+    }
+    /// Parses input typed at the prompt. Input that's nothing but a single expression is handed
+    /// back as one so that its value can be echoed, input that ends partway through a statement is
+    /// reported as incomplete, and anything else is parsed like `parse()`.
+    pub fn parse_repl_input(&mut self) -> ReplInput {
+        self.strip_whitespace();
+        let has_semicolon = self
+            .tokens
+            .iter()
+            .any(|source_token| source_token.token == scanner::Token::Semicolon);
+        if !has_semicolon && self.peek_next_token().is_some() {
+            if let Ok(expression) = self.expression() {
+                if self.peek_next_token().is_none() {
+                    return ReplInput::Expression(
+                        expression,
+                        std::mem::take(&mut self.expressions),
+                    );
+                }
+            }
+            self.reset();
+        }
+        let program = self.parse_program();
+        if !self.only_ran_out_of_tokens() {
+            return ReplInput::Statements(program);
+        }
+        if !has_semicolon {
+            // This lets declarations like `var x = 1` be typed at the prompt without a semicolon.
+            self.reset();
+            let eof_location = self.tokens[self.tokens.len() - 1].location_span;
+            self.tokens.insert(
+                self.tokens.len() - 1,
+                scanner::SourceToken {
+                    token: scanner::Token::Semicolon,
+                    location_span: eof_location,
+                },
+            );
+            let program = self.parse_program();
+            if self.error_log.error_count() == 0 {
+                return ReplInput::Statements(program);
+            }
+        }
+        // Nothing's actually wrong yet.
+        self.reset();
+        ReplInput::Incomplete
+    }
+    // Throws away whatever an attempt at parsing produced, it'll be produced again if it still
+    // applies.
+    fn reset(&mut self) {
+        self.index = 0;
+        self.expressions = ExprArena::new();
+        self.error_log = errors::ErrorLog::new();
+        self.errors_before_running_out = None;
+    }
+    // If running out of tokens is the only thing wrong, then the input was fine so far, just
+    // unfinished. Anything logged after running out is only fallout from it, like the enclosing
+    // blocks going unclosed.
+    fn only_ran_out_of_tokens(&self) -> bool {
+        self.errors_before_running_out == Some(0)
+    }
+    fn note_running_out_of_tokens(&mut self) {
+        if self.errors_before_running_out.is_none() {
+            self.errors_before_running_out = Some(self.error_log.error_count());
+        }
+    }
     // TODO: Clean this up so that the parser doesn't need to strip its own whitespace?
-    pub fn parse(&mut self) -> Vec<Stmt> {
+    fn strip_whitespace(&mut self) {
         // The tokens provided to the parser may contain whitespace.
         // TODO: I have no idea if this is the best way to filter this vector.
         self.tokens = self
@@ -160,7 +566,8 @@ impl Parser {
             .drain(..)
             .filter(|source_token| !enum_variant_equal(&source_token.token, &WHITESPACE_EXEMPLAR))
             .collect();
-        // Begin parsing statements
+    }
+    fn parse_program(&mut self) -> Program {
         let mut statements: Vec<Stmt> = Vec::new();
         while let Some(parse_result) = self.parse_next_statement() {
             match parse_result {
@@ -168,10 +575,13 @@ impl Parser {
                 Err(error) => self.error_log.push(error),
             }
         }
-        statements
+        Program {
+            statements,
+            expressions: std::mem::take(&mut self.expressions),
+        }
     }
     fn parse_next_statement(&mut self) -> Option<Result<Stmt, errors::Error>> {
-        if let Some(_) = self.peek_next_token() {
+        if self.peek_next_token().is_some() {
             Some(self.declaration())
         } else {
             None
@@ -193,9 +603,37 @@ impl Parser {
             .get(self.index)
             .expect("`peek_next_token` Consumed all tokens without encountering EOF");
         if token.token == scanner::Token::Eof {
-            return None;
+            None
         } else {
-            return Some(token.clone());
+            Some(token.clone())
+        }
+    }
+    // Unlike `peek_next_token()`, running off the end isn't a panic, since the next token may well
+    // be the Eof.
+    fn peek_next_token_twice(&self) -> Option<scanner::SourceToken> {
+        match self.tokens.get(self.index + 1) {
+            Some(token) if token.token != scanner::Token::Eof => Some(token.clone()),
+            _ => None,
+        }
+    }
+    // With the next token being a `{`, is it the start of a map rather than a block? A block can
+    // never start with a key and a colon, and an empty one is never followed by a semicolon, so
+    // either of those settles it. Anything else is a block, which means `{ a: 1 }` has to come
+    // first for a statement to start with a map.
+    fn at_map_literal(&self) -> bool {
+        let token_after = |offset: usize| {
+            self.tokens
+                .get(self.index + offset)
+                .map(|source_token| &source_token.token)
+        };
+        match token_after(1) {
+            Some(
+                scanner::Token::String(_)
+                | scanner::Token::RawString(_)
+                | scanner::Token::Identifier(_),
+            ) => token_after(2) == Some(&scanner::Token::Colon),
+            Some(scanner::Token::RightBrace) => token_after(2) == Some(&scanner::Token::Semicolon),
+            _ => false,
         }
     }
     fn match_then_consume(&mut self, token: scanner::Token, target: scanner::Token) -> bool {
@@ -206,8 +644,8 @@ impl Parser {
             false
         }
     }
-    // TODO: ~~Reconcile these two~~ Actually only the second should be used. There's only one
-    // instance of a function actually unwraping the Option.
+    // TODO: Reaching the end of the tokens should be an error rather than a panic, and Eof
+    // shouldn't be special cased as `None`.
     fn deprecated_advance_token_index(&mut self) -> Option<scanner::SourceToken> {
         if let Some(token) = self.tokens.get(self.index) {
             self.index += 1;
@@ -219,21 +657,6 @@ impl Parser {
         }
         panic!("`advance_next_token` Consumed all tokens without encountering EOF");
     }
-    fn advance_token_index(&mut self) -> Result<scanner::SourceToken, errors::Error> {
-        if let Some(token) = self.tokens.get(self.index) {
-            self.index += 1;
-            // TODO Some kind of error for reaching Eof?
-            return Ok(token.clone());
-        }
-        Err(errors::Error {
-            kind: errors::ErrorKind::Parsing,
-            description: errors::ErrorDescription {
-                subject: None,
-                location: None,
-                description: String::from("Consumed all tokens without encountering EOF"),
-            },
-        })
-    }
     fn consume_next_token(
         &mut self,
         expected_token: scanner::Token,
@@ -243,26 +666,34 @@ impl Parser {
             if enum_variant_equal(&next_token.token, &expected_token) {
                 return Ok(next_token);
             }
-            return Err(errors::Error {
-                kind: errors::ErrorKind::Parsing,
-                description: errors::ErrorDescription {
-                    subject: None,
-                    location: Some(next_token.location_span),
-                    description: format!(
-                        "Expected '{}' after expression, instead found '{}'",
-                        expected_token, next_token.token
-                    ),
-                },
-            });
+            return Err(errors::Error::parsing(
+                next_token.location_span,
+                format!(
+                    "Expected '{}' after expression, instead found '{}'",
+                    expected_token, next_token.token
+                ),
+            )
+            .with_code(ErrorCode::ExpectedToken));
         };
-        Err(errors::Error {
-            kind: errors::ErrorKind::Parsing,
-            description: errors::ErrorDescription {
-                subject: None,
-                location: None,
-                description: format!("Reached end of file while expecting '{}'", expected_token),
-            },
-        })
+        self.note_running_out_of_tokens();
+        Err(errors::Error::new(
+            errors::ErrorKind::Parsing,
+            None,
+            format!("Reached end of file while expecting '{}'", expected_token),
+        )
+        .with_code(ErrorCode::ExpectedToken)
+        .as_end_of_input())
+    }
+    fn consume_identifier(
+        &mut self,
+    ) -> Result<(scanner::Identifier, source_file::SourceSpan), errors::Error> {
+        // TODO: Find out a way to make this a constant, see `var_declaration()`.
+        let identifier_exemplar = scanner::Token::Identifier(String::from("example"));
+        let source_token = self.consume_next_token(identifier_exemplar)?;
+        match source_token.token {
+            scanner::Token::Identifier(name) => Ok((name, source_token.location_span)),
+            _ => unreachable!("`consume_next_token()` only hands back what it was asked for"),
+        }
     }
     // Maybe would be better to use a cursor?
     fn previous_token(&self) -> scanner::SourceToken {
@@ -273,20 +704,63 @@ impl Parser {
     }
     // TODO: This one will take some thinking. The idea is to run the token index to the next
     // statement boundary, and begin parsing again.
+    // Note that this stops short of Eof rather than consuming it, otherwise the next attempt at a
+    // statement would read past the end of the tokens.
     fn synchronize_to_statement_boundary(&mut self) {
-        while let Some(source_token) = self.deprecated_advance_token_index() {
-            if self.previous_token().token == scanner::Token::Semicolon
+        while let Some(source_token) = self.peek_next_token() {
+            self.deprecated_advance_token_index();
+            if source_token.token == scanner::Token::Semicolon
                 || STATEMENT_BEGINNING_TOKENS.contains(&source_token.token)
             {
                 break;
             }
         }
     }
+    // --- Lints ---
+    // An assignment at the top of a condition is legal, but is almost always a typo for "==". Any
+    // parentheses beyond the ones the syntax requires are taken as an explicit opt-in, the same way
+    // C compilers do it.
+    fn warn_on_assignment_condition(&mut self, condition: ExprId, required_parentheses: usize) {
+        let mut expr = &self.expressions.get(condition).expr;
+        let mut parentheses = 0;
+        while let Expr::Grouping(inner) = expr {
+            parentheses += 1;
+            expr = &self.expressions.get(*inner).expr;
+        }
+        if let Expr::Assign(assign) = expr {
+            if parentheses <= required_parentheses {
+                self.error_log.push(
+                    errors::Error::parsing(
+                        assign.equal_location,
+                        String::from("Assignment used as a condition, did you mean '=='?"),
+                    )
+                    .with_code(ErrorCode::AssignmentInCondition)
+                    .with_subject(assign.name.clone())
+                    .as_warning(),
+                );
+            }
+        }
+    }
     // --- Statement Rules ---
     fn declaration(&mut self) -> Result<Stmt, errors::Error> {
         if let Some(source_token) = self.peek_next_token() {
-            let res = if self.match_then_consume(source_token.token, scanner::Token::Var) {
+            let res = if self.match_then_consume(source_token.token.clone(), scanner::Token::Var) {
                 self.var_declaration()
+            } else if self.match_then_consume(source_token.token.clone(), scanner::Token::Class) {
+                self.class_declaration()
+            } else if source_token.token == scanner::Token::Fun
+                && matches!(
+                    self.peek_next_token_twice(),
+                    Some(scanner::SourceToken {
+                        token: scanner::Token::Identifier(_),
+                        ..
+                    })
+                )
+            {
+                // `fun name` can only be a declaration, anything else is left for when `fun` can
+                // start an expression.
+                self.deprecated_advance_token_index();
+                self.function_declaration()
             } else {
                 self.statement()
             };
@@ -304,30 +778,127 @@ impl Parser {
     fn var_declaration(&mut self) -> Result<Stmt, errors::Error> {
         // TODO: Find out a way to make this a constant. This is a real bummer, or find out if you
         // can pass in just the type of the enum without constructing it.
-        let IDENTIFIER_EXEMPLAR = scanner::Token::Identifier(String::from("example"));
+        let identifier_exemplar = scanner::Token::Identifier(String::from("example"));
         // Woof this deconstruction is a mouthful.
         if let scanner::SourceToken {
             token: scanner::Token::Identifier(name),
-            ..
-        } = self.consume_next_token(IDENTIFIER_EXEMPLAR)?
+            location_span: name_location,
+        } = self.consume_next_token(identifier_exemplar)?
         {
             let mut initializer = None;
-            let source_token = self.advance_token_index()?;
-            if self.match_then_consume(source_token.token, scanner::Token::Equal) {
-                initializer = Some(self.expression()?);
+            if let Some(source_token) = self.peek_next_token() {
+                if self.match_then_consume(source_token.token, scanner::Token::Equal) {
+                    initializer = Some(self.expression()?);
+                }
             }
             self.consume_next_token(scanner::Token::Semicolon)?;
-            return Ok(Stmt::Var(VarStmt { name, initializer }));
+            return Ok(Stmt::Var(VarStmt {
+                name,
+                name_location,
+                initializer,
+            }));
         };
         // TODO: Find out a better way to structure this. It would be nice if rust had type
         // narrowing from function returns.
         panic!("`consume_next_token` has to be broken for this to be reachable");
     }
+    fn class_declaration(&mut self) -> Result<Stmt, errors::Error> {
+        let (name, name_location) = self.consume_identifier()?;
+        let mut superclass = None;
+        if let Some(source_token) = self.peek_next_token() {
+            if self.match_then_consume(source_token.token, scanner::Token::Less) {
+                let (superclass_name, location) = self.consume_identifier()?;
+                superclass = Some(
+                    self.expressions
+                        .push(Expr::Variable(superclass_name), location),
+                );
+            }
+        }
+        self.consume_next_token(scanner::Token::LeftBrace)?;
+        let mut methods = Vec::new();
+        while let Some(source_token) = self.peek_next_token() {
+            if source_token.token == scanner::Token::RightBrace {
+                break;
+            }
+            methods.push(Rc::new(self.function()?));
+        }
+        self.consume_next_token(scanner::Token::RightBrace)?;
+        Ok(Stmt::Class(ClassStmt {
+            name,
+            name_location,
+            superclass,
+            methods,
+        }))
+    }
+    fn function_declaration(&mut self) -> Result<Stmt, errors::Error> {
+        Ok(Stmt::Function(Rc::new(self.function()?)))
+    }
+    // Everything after `fun`, which methods don't have. Too many parameters is logged rather than
+    // returned, the same as too many arguments.
+    fn function(&mut self) -> Result<FunctionStmt, errors::Error> {
+        let (name, name_location) = self.consume_identifier()?;
+        self.consume_next_token(scanner::Token::LeftParen)?;
+        let mut parameters = Vec::new();
+        if let Some(source_token) = self.peek_next_token() {
+            if source_token.token != scanner::Token::RightParen {
+                loop {
+                    let (name, location) = self.consume_identifier()?;
+                    if parameters.len() == MAX_ARGUMENTS {
+                        self.error_log.push(
+                            errors::Error::parsing(
+                                location,
+                                format!("Can't have more than {} parameters", MAX_ARGUMENTS),
+                            )
+                            .with_code(ErrorCode::TooManyArguments),
+                        );
+                    }
+                    parameters.push(Parameter { name, location });
+                    match self.peek_next_token() {
+                        Some(next_token) if next_token.token == scanner::Token::Comma => {
+                            self.deprecated_advance_token_index();
+                        }
+                        _ => break,
+                    }
+                }
+            }
+        }
+        self.consume_next_token(scanner::Token::RightParen)?;
+        self.consume_next_token(scanner::Token::LeftBrace)?;
+        let body = match self.block_statement()? {
+            Stmt::Block(block) => block.statements,
+            _ => unreachable!("`block_statement()` only ever makes blocks"),
+        };
+        Ok(FunctionStmt {
+            name,
+            name_location,
+            parameters,
+            body,
+        })
+    }
     fn statement(&mut self) -> Result<Stmt, errors::Error> {
         if let Some(source_token) = self.peek_next_token() {
-            if self.match_then_consume(source_token.token, scanner::Token::Print) {
+            if self.match_then_consume(source_token.token.clone(), scanner::Token::For) {
+                return self.for_statement();
+            }
+            if self.match_then_consume(source_token.token.clone(), scanner::Token::If) {
+                return self.if_statement();
+            }
+            if self.match_then_consume(source_token.token.clone(), scanner::Token::Print) {
                 return self.print_statement();
             }
+            if self.match_then_consume(source_token.token.clone(), scanner::Token::While) {
+                return self.while_statement();
+            }
+            if self.match_then_consume(source_token.token.clone(), scanner::Token::Continue) {
+                return self.continue_statement(source_token);
+            }
+            if self.match_then_consume(source_token.token.clone(), scanner::Token::Return) {
+                return self.return_statement(source_token);
+            }
+            if source_token.token == scanner::Token::LeftBrace && !self.at_map_literal() {
+                self.deprecated_advance_token_index();
+                return self.block_statement();
+            }
         }
         // Note, it seems absurd to let control fall through into `expression_statement()` after we
         // *know* that there isn't a token to consume, but the correct error *will* propagate when
@@ -336,11 +907,125 @@ impl Parser {
         // This is also how it works in the book, for whatever that's worth.
         self.expression_statement()
     }
+    // The dangling else goes to the nearest if, since that's the one that'll be looking for it.
+    fn if_statement(&mut self) -> Result<Stmt, errors::Error> {
+        self.consume_next_token(scanner::Token::LeftParen)?;
+        let condition = self.expression()?;
+        self.consume_next_token(scanner::Token::RightParen)?;
+        self.warn_on_assignment_condition(condition, 0);
+        let then_branch = Box::new(self.statement()?);
+        let mut else_branch = None;
+        if let Some(source_token) = self.peek_next_token() {
+            if self.match_then_consume(source_token.token, scanner::Token::Else) {
+                else_branch = Some(Box::new(self.statement()?));
+            }
+        }
+        Ok(Stmt::If(IfStmt {
+            condition,
+            then_branch,
+            else_branch,
+        }))
+    }
+    // `for (initializer; condition; increment) body` becomes
+    // `{ initializer; while (condition) body }`, with the increment kept on the while so that it
+    // still runs when the body is cut short. A missing condition loops forever.
+    fn for_statement(&mut self) -> Result<Stmt, errors::Error> {
+        let left_paren = self.consume_next_token(scanner::Token::LeftParen)?;
+        let initializer = match self.peek_next_token() {
+            Some(source_token) if source_token.token == scanner::Token::Semicolon => {
+                self.deprecated_advance_token_index();
+                None
+            }
+            Some(source_token) if source_token.token == scanner::Token::Var => {
+                self.deprecated_advance_token_index();
+                Some(self.var_declaration()?)
+            }
+            _ => Some(self.expression_statement()?),
+        };
+        let condition = match self.peek_next_token() {
+            Some(source_token) if source_token.token == scanner::Token::Semicolon => {
+                self.expressions.push(
+                    Expr::Literal(LiteralKind::Boolean(true)),
+                    left_paren.location_span,
+                )
+            }
+            _ => {
+                let condition = self.expression()?;
+                self.warn_on_assignment_condition(condition, 0);
+                condition
+            }
+        };
+        self.consume_next_token(scanner::Token::Semicolon)?;
+        let increment = match self.peek_next_token() {
+            Some(source_token) if source_token.token == scanner::Token::RightParen => None,
+            _ => Some(self.expression()?),
+        };
+        self.consume_next_token(scanner::Token::RightParen)?;
+        let body = Box::new(self.statement()?);
+        let loop_statement = Stmt::While(WhileStmt {
+            condition,
+            body,
+            increment,
+        });
+        Ok(match initializer {
+            Some(initializer) => Stmt::Block(BlockStmt {
+                statements: vec![initializer, loop_statement],
+            }),
+            None => loop_statement,
+        })
+    }
+    fn while_statement(&mut self) -> Result<Stmt, errors::Error> {
+        self.consume_next_token(scanner::Token::LeftParen)?;
+        let condition = self.expression()?;
+        self.consume_next_token(scanner::Token::RightParen)?;
+        self.warn_on_assignment_condition(condition, 0);
+        let body = Box::new(self.statement()?);
+        Ok(Stmt::While(WhileStmt {
+            condition,
+            body,
+            increment: None,
+        }))
+    }
     fn print_statement(&mut self) -> Result<Stmt, errors::Error> {
         let expression = self.expression()?;
         self.consume_next_token(scanner::Token::Semicolon)?;
         Ok(Stmt::Print(PrintStmt { expression }))
     }
+    // Whether there's a loop to continue is left to the resolver.
+    fn continue_statement(&mut self, keyword: scanner::SourceToken) -> Result<Stmt, errors::Error> {
+        self.consume_next_token(scanner::Token::Semicolon)?;
+        Ok(Stmt::Continue(ContinueStmt {
+            location: keyword.location_span,
+        }))
+    }
+    // Like `continue`, whether there's a function to return from is left to the resolver.
+    fn return_statement(&mut self, keyword: scanner::SourceToken) -> Result<Stmt, errors::Error> {
+        let value = match self.peek_next_token() {
+            Some(source_token) if source_token.token == scanner::Token::Semicolon => None,
+            _ => Some(self.expression()?),
+        };
+        self.consume_next_token(scanner::Token::Semicolon)?;
+        Ok(Stmt::Return(ReturnStmt {
+            location: keyword.location_span,
+            value,
+        }))
+    }
+    fn block_statement(&mut self) -> Result<Stmt, errors::Error> {
+        let mut statements = Vec::new();
+        while let Some(source_token) = self.peek_next_token() {
+            if source_token.token == scanner::Token::RightBrace {
+                break;
+            }
+            // Errors are logged here rather than propagated so that the rest of the block still
+            // gets parsed.
+            match self.declaration() {
+                Ok(statement) => statements.push(statement),
+                Err(error) => self.error_log.push(error),
+            }
+        }
+        self.consume_next_token(scanner::Token::RightBrace)?;
+        Ok(Stmt::Block(BlockStmt { statements }))
+    }
     fn expression_statement(&mut self) -> Result<Stmt, errors::Error> {
         let expression = self.expression()?;
         self.consume_next_token(scanner::Token::Semicolon)?;
@@ -350,153 +1035,519 @@ impl Parser {
     // TODO:? Make a helper function for binaries that just takes a list of the tokens necesary and
     // the next function to match? Might look a bit weird. Also, it may be slightly faster to have
     // them as separate functions. Also, it may become convenient that they are separate later.
-    fn expression(&mut self) -> Result<Expr, errors::Error> {
-        self.ternary()
+    fn expression(&mut self) -> Result<ExprId, errors::Error> {
+        self.assignment()
     }
-    fn ternary(&mut self) -> Result<Expr, errors::Error> {
-        let mut expr = self.equality()?;
+    // Unlike the binary rules, we don't know that we're looking at an assignment until we've
+    // already parsed the target as an expression, so we reinterpret it after the fact. Note that
+    // this leaves the target's own node in the arena, unreferenced.
+    fn assignment(&mut self) -> Result<ExprId, errors::Error> {
+        let expr = self.ternary()?;
+        if let Some(source_token) = self.peek_next_token() {
+            if source_token.token == ASSIGNMENT_TOKEN {
+                self.deprecated_advance_token_index();
+                let value = self.assignment()?;
+                let location =
+                    source_file::SourceSpan::merge(self.location_of(expr), self.location_of(value));
+                if let Expr::Variable(name) = &self.expressions.get(expr).expr {
+                    let assign = Expr::Assign(AssignExpr {
+                        name: name.clone(),
+                        value,
+                        equal_location: source_token.location_span,
+                    });
+                    return Ok(self.expressions.push(assign, location));
+                }
+                if let Expr::Get(get) = &self.expressions.get(expr).expr {
+                    let set = Expr::Set(SetExpr {
+                        object: get.object,
+                        name: get.name.clone(),
+                        name_location: get.name_location,
+                        value,
+                    });
+                    return Ok(self.expressions.push(set, location));
+                }
+                // The whole target is what's wrong, but the `=` is pointed at too, since it's what
+                // made it a target in the first place.
+                return Err(errors::Error::parsing(
+                    self.location_of(expr),
+                    "Invalid assignment target",
+                )
+                .with_code(ErrorCode::InvalidAssignmentTarget)
+                .with_label(source_token.location_span, "assigned here")
+                .with_note("only variables and properties can be assigned to"));
+            }
+        }
+        Ok(expr)
+    }
+    fn ternary(&mut self) -> Result<ExprId, errors::Error> {
+        let mut expr = self.logic_or()?;
         while let Some(source_token) = self.peek_next_token() {
             if source_token.token == TERNARY_TEST_TOKEN {
                 self.deprecated_advance_token_index();
-                let left_result = self.equality()?;
+                // Assignment binds looser than the ternary, so the condition can only be one if
+                // it's been parenthesized.
+                self.warn_on_assignment_condition(expr, 1);
+                let left_result = self.logic_or()?;
                 self.consume_next_token(TERNARY_BRANCH_TOKEN)?;
-                let right_result = self.equality()?;
-                expr = Expr::Ternary(TernaryExpr {
-                    condition: Box::new(expr),
-                    left_result: Box::new(left_result),
-                    right_result: Box::new(right_result),
-                })
+                let right_result = self.logic_or()?;
+                let location = source_file::SourceSpan::merge(
+                    self.location_of(expr),
+                    self.location_of(right_result),
+                );
+                expr = self.expressions.push(
+                    Expr::Ternary(TernaryExpr {
+                        condition: expr,
+                        left_result,
+                        right_result,
+                    }),
+                    location,
+                );
             } else {
                 break;
             }
         }
         Ok(expr)
     }
-    fn equality(&mut self) -> Result<Expr, errors::Error> {
+    fn logic_or(&mut self) -> Result<ExprId, errors::Error> {
+        let mut expr = self.logic_and()?;
+        while let Some(source_token) = self.peek_next_token() {
+            if source_token.token == scanner::Token::Or {
+                self.deprecated_advance_token_index();
+                let right = self.logic_and()?;
+                expr = self.push_logical(expr, scanner::Token::Or, right);
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+    fn logic_and(&mut self) -> Result<ExprId, errors::Error> {
+        let mut expr = self.equality()?;
+        while let Some(source_token) = self.peek_next_token() {
+            if source_token.token == scanner::Token::And {
+                self.deprecated_advance_token_index();
+                let right = self.equality()?;
+                expr = self.push_logical(expr, scanner::Token::And, right);
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+    fn equality(&mut self) -> Result<ExprId, errors::Error> {
         let mut expr = self.comparison()?;
         while let Some(source_token) = self.peek_next_token() {
-            if EQUALITY_TOKENS.contains(&source_token.token) {
+            if is_equality_token(&source_token.token) {
                 self.deprecated_advance_token_index();
                 let operator = source_token.token.clone();
                 let right = self.comparison()?;
-                expr = Expr::Binary(BinaryExpr {
-                    left: Box::new(expr),
-                    operator,
-                    right: Box::new(right),
-                })
+                expr = self.push_binary(expr, operator, right);
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+    fn comparison(&mut self) -> Result<ExprId, errors::Error> {
+        let mut expr = self.bitwise_or()?;
+        while let Some(source_token) = self.peek_next_token() {
+            if is_comparison_token(&source_token.token) {
+                self.deprecated_advance_token_index();
+                let operator = source_token.token.clone();
+                let right = self.bitwise_or()?;
+                expr = self.push_binary(expr, operator, right);
             } else {
                 break;
             }
         }
         Ok(expr)
     }
-    fn comparison(&mut self) -> Result<Expr, errors::Error> {
+    // The bitwise operators all bind tighter than comparison, unlike C, where `a & b == c` is
+    // famously `a & (b == c)`.
+    fn bitwise_or(&mut self) -> Result<ExprId, errors::Error> {
+        let mut expr = self.bitwise_xor()?;
+        while let Some(source_token) = self.peek_next_token() {
+            if source_token.token == scanner::Token::Pipe {
+                self.deprecated_advance_token_index();
+                let right = self.bitwise_xor()?;
+                expr = self.push_binary(expr, scanner::Token::Pipe, right);
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+    fn bitwise_xor(&mut self) -> Result<ExprId, errors::Error> {
+        let mut expr = self.bitwise_and()?;
+        while let Some(source_token) = self.peek_next_token() {
+            if source_token.token == scanner::Token::Caret {
+                self.deprecated_advance_token_index();
+                let right = self.bitwise_and()?;
+                expr = self.push_binary(expr, scanner::Token::Caret, right);
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+    fn bitwise_and(&mut self) -> Result<ExprId, errors::Error> {
+        let mut expr = self.shift()?;
+        while let Some(source_token) = self.peek_next_token() {
+            if source_token.token == scanner::Token::Ampersand {
+                self.deprecated_advance_token_index();
+                let right = self.shift()?;
+                expr = self.push_binary(expr, scanner::Token::Ampersand, right);
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+    fn shift(&mut self) -> Result<ExprId, errors::Error> {
         let mut expr = self.term()?;
         while let Some(source_token) = self.peek_next_token() {
-            if COMPARISON_TOKENS.contains(&source_token.token) {
+            if is_shift_token(&source_token.token) {
                 self.deprecated_advance_token_index();
                 let operator = source_token.token.clone();
                 let right = self.term()?;
-                expr = Expr::Binary(BinaryExpr {
-                    left: Box::new(expr),
-                    operator,
-                    right: Box::new(right),
-                })
+                expr = self.push_binary(expr, operator, right);
             } else {
                 break;
             }
         }
         Ok(expr)
     }
-    fn term(&mut self) -> Result<Expr, errors::Error> {
+    fn term(&mut self) -> Result<ExprId, errors::Error> {
         let mut expr = self.factor()?;
         while let Some(source_token) = self.peek_next_token() {
-            if TERM_TOKENS.contains(&source_token.token) {
+            if is_term_token(&source_token.token) {
                 self.deprecated_advance_token_index();
                 let operator = source_token.token.clone();
                 let right = self.factor()?;
-                expr = Expr::Binary(BinaryExpr {
-                    left: Box::new(expr),
-                    operator,
-                    right: Box::new(right),
-                })
+                expr = self.push_binary(expr, operator, right);
             } else {
                 break;
             }
         }
         Ok(expr)
     }
-    fn factor(&mut self) -> Result<Expr, errors::Error> {
+    fn factor(&mut self) -> Result<ExprId, errors::Error> {
         let mut expr = self.unary()?;
         while let Some(source_token) = self.peek_next_token() {
-            if FACTOR_TOKENS.contains(&source_token.token) {
+            if is_factor_token(&source_token.token) {
                 self.deprecated_advance_token_index();
                 let operator = source_token.token.clone();
                 let right = self.unary()?;
-                expr = Expr::Binary(BinaryExpr {
-                    left: Box::new(expr),
-                    operator,
-                    right: Box::new(right),
-                })
+                expr = self.push_binary(expr, operator, right);
             } else {
                 break;
             }
         }
         Ok(expr)
     }
-    fn unary(&mut self) -> Result<Expr, errors::Error> {
+    fn unary(&mut self) -> Result<ExprId, errors::Error> {
         if let Some(source_token) = self.peek_next_token() {
-            if UNARY_TOKENS.contains(&source_token.token) {
+            // Binds like the other unary operators, so `typeof a + b` is `(typeof a) + b`.
+            if source_token.token == scanner::Token::Typeof {
+                self.deprecated_advance_token_index();
+                let operand = self.unary()?;
+                let location = source_file::SourceSpan::merge(
+                    source_token.location_span,
+                    self.location_of(operand),
+                );
+                return Ok(self.expressions.push(Expr::Typeof(operand), location));
+            }
+            if is_unary_token(&source_token.token) {
                 self.deprecated_advance_token_index();
                 let operator = source_token.token.clone();
                 let right = self.unary()?;
-                return Ok(Expr::Unary(UnaryExpr {
-                    operator,
-                    right: Box::new(right),
-                }));
+                let location = source_file::SourceSpan::merge(
+                    source_token.location_span,
+                    self.location_of(right),
+                );
+                return Ok(self
+                    .expressions
+                    .push(Expr::Unary(UnaryExpr { operator, right }), location));
             }
         }
         // Note, See the note above in `statement()` regarding calling another function after we
         // know that we are out of tokens.
-        self.primary()
+        self.call()
+    }
+    fn call(&mut self) -> Result<ExprId, errors::Error> {
+        let mut expr = self.primary()?;
+        while let Some(source_token) = self.peek_next_token() {
+            if source_token.token == CALL_TOKEN {
+                self.deprecated_advance_token_index();
+                expr = self.finish_call(expr)?;
+            } else if source_token.token == PROPERTY_ACCESS_TOKEN {
+                self.deprecated_advance_token_index();
+                // TODO: Find out a way to make this a constant, see `var_declaration()`.
+                let identifier_exemplar = scanner::Token::Identifier(String::from("example"));
+                if let scanner::SourceToken {
+                    token: scanner::Token::Identifier(name),
+                    location_span: name_location,
+                } = self.consume_next_token(identifier_exemplar)?
+                {
+                    let location =
+                        source_file::SourceSpan::merge(self.location_of(expr), name_location);
+                    expr = self.expressions.push(
+                        Expr::Get(GetExpr {
+                            object: expr,
+                            name,
+                            name_location,
+                        }),
+                        location,
+                    );
+                }
+            } else {
+                break;
+            }
+        }
+        Ok(expr)
+    }
+    // The same as arguments, but without a limit on how many there can be. A trailing comma is
+    // allowed, since lists are often written one element per line.
+    fn finish_list(
+        &mut self,
+        left_bracket: source_file::SourceSpan,
+    ) -> Result<ExprId, errors::Error> {
+        let mut elements = Vec::new();
+        while let Some(source_token) = self.peek_next_token() {
+            if source_token.token == scanner::Token::RightBracket {
+                break;
+            }
+            elements.push(self.expression()?);
+            match self.peek_next_token() {
+                Some(next_token) if next_token.token == scanner::Token::Comma => {
+                    self.deprecated_advance_token_index();
+                }
+                _ => break,
+            }
+        }
+        let right_bracket = self.consume_next_token(scanner::Token::RightBracket)?;
+        let location = source_file::SourceSpan::merge(left_bracket, right_bracket.location_span);
+        Ok(self
+            .expressions
+            .push(Expr::List(ListExpr { elements }), location))
+    }
+    // Picks up after the first element and its comma, which is where a grouping turns out to be a
+    // tuple. A trailing comma is allowed, and is the only way to write a tuple of one.
+    fn finish_tuple(
+        &mut self,
+        left_paren: source_file::SourceSpan,
+        first: ExprId,
+    ) -> Result<ExprId, errors::Error> {
+        let mut elements = vec![first];
+        while let Some(source_token) = self.peek_next_token() {
+            if source_token.token == scanner::Token::RightParen {
+                break;
+            }
+            elements.push(self.expression()?);
+            match self.peek_next_token() {
+                Some(next_token) if next_token.token == scanner::Token::Comma => {
+                    self.deprecated_advance_token_index();
+                }
+                _ => break,
+            }
+        }
+        let right_paren = self.consume_next_token(scanner::Token::RightParen)?;
+        let location = source_file::SourceSpan::merge(left_paren, right_paren.location_span);
+        Ok(self
+            .expressions
+            .push(Expr::Tuple(TupleExpr { elements }), location))
+    }
+    // A statement starting with `{` is only a map if `at_map_literal()` says so, otherwise it's a
+    // block. If a key appears twice the later value wins, the same as setting it twice.
+    fn finish_map(&mut self, left_brace: source_file::SourceSpan) -> Result<ExprId, errors::Error> {
+        let mut entries = Vec::new();
+        while let Some(source_token) = self.peek_next_token() {
+            let key = match source_token.token {
+                scanner::Token::RightBrace => break,
+                scanner::Token::String(key)
+                | scanner::Token::RawString(key)
+                | scanner::Token::Identifier(key) => key,
+                other => {
+                    return Err(errors::Error::parsing(
+                        source_token.location_span,
+                        format!("Expected a string or name as a map key, found '{}'", other),
+                    )
+                    .with_code(ErrorCode::ExpectedToken));
+                }
+            };
+            self.deprecated_advance_token_index();
+            self.consume_next_token(scanner::Token::Colon)?;
+            entries.push((key, self.expression()?));
+            match self.peek_next_token() {
+                Some(next_token) if next_token.token == scanner::Token::Comma => {
+                    self.deprecated_advance_token_index();
+                }
+                _ => break,
+            }
+        }
+        let right_brace = self.consume_next_token(scanner::Token::RightBrace)?;
+        let location = source_file::SourceSpan::merge(left_brace, right_brace.location_span);
+        Ok(self
+            .expressions
+            .push(Expr::Map(MapExpr { entries }), location))
     }
-    fn primary(&mut self) -> Result<Expr, errors::Error> {
+    // Too many arguments is logged rather than returned, the parser isn't actually confused.
+    fn finish_call(&mut self, callee: ExprId) -> Result<ExprId, errors::Error> {
+        let mut arguments = Vec::new();
+        if let Some(source_token) = self.peek_next_token() {
+            if source_token.token != scanner::Token::RightParen {
+                loop {
+                    let argument = self.expression()?;
+                    if arguments.len() == MAX_ARGUMENTS {
+                        self.error_log.push(
+                            errors::Error::parsing(
+                                self.location_of(argument),
+                                format!("Can't have more than {} arguments", MAX_ARGUMENTS),
+                            )
+                            .with_code(ErrorCode::TooManyArguments),
+                        );
+                    }
+                    arguments.push(argument);
+                    match self.peek_next_token() {
+                        Some(next_token) if next_token.token == scanner::Token::Comma => {
+                            self.deprecated_advance_token_index();
+                        }
+                        _ => break,
+                    }
+                }
+            }
+        }
+        let closing_paren = self.consume_next_token(scanner::Token::RightParen)?;
+        let location =
+            source_file::SourceSpan::merge(self.location_of(callee), closing_paren.location_span);
+        Ok(self
+            .expressions
+            .push(Expr::Call(CallExpr { callee, arguments }), location))
+    }
+    fn primary(&mut self) -> Result<ExprId, errors::Error> {
         if let Some(source_token) = self.peek_next_token() {
             self.deprecated_advance_token_index();
-            match source_token.token {
-                scanner::Token::False => Ok(Expr::Literal(LiteralKind::Boolean(false))),
-                scanner::Token::True => Ok(Expr::Literal(LiteralKind::Boolean(true))),
-                scanner::Token::Nil => Ok(Expr::Literal(LiteralKind::Nil)),
-                scanner::Token::Number(value) => Ok(Expr::Literal(LiteralKind::Number(value))),
-                scanner::Token::String(value) => Ok(Expr::Literal(LiteralKind::String(value))),
+            let expr = match source_token.token {
+                scanner::Token::False => Expr::Literal(LiteralKind::Boolean(false)),
+                scanner::Token::True => Expr::Literal(LiteralKind::Boolean(true)),
+                scanner::Token::Nil => Expr::Literal(LiteralKind::Nil),
+                scanner::Token::Number(value) => Expr::Literal(LiteralKind::Number(value)),
+                scanner::Token::String(value) | scanner::Token::RawString(value) => {
+                    Expr::Literal(LiteralKind::String(value))
+                }
+                scanner::Token::Identifier(name) => Expr::Variable(name),
+                // Whether there's a class for these to refer to is left to the resolver.
+                scanner::Token::This => Expr::This(ThisExpr),
+                scanner::Token::Super => {
+                    self.consume_next_token(scanner::Token::Dot)?;
+                    let (method, method_location) = self.consume_identifier()?;
+                    let location =
+                        source_file::SourceSpan::merge(source_token.location_span, method_location);
+                    return Ok(self.expressions.push(
+                        Expr::Super(SuperExpr {
+                            keyword_location: source_token.location_span,
+                            method,
+                            method_location,
+                        }),
+                        location,
+                    ));
+                }
+                scanner::Token::LeftBracket => return self.finish_list(source_token.location_span),
+                scanner::Token::LeftBrace => return self.finish_map(source_token.location_span),
                 scanner::Token::LeftParen => {
+                    if let Some(next_token) = self.peek_next_token() {
+                        if next_token.token == scanner::Token::RightParen {
+                            self.deprecated_advance_token_index();
+                            let location = source_file::SourceSpan::merge(
+                                source_token.location_span,
+                                next_token.location_span,
+                            );
+                            return Ok(self.expressions.push(
+                                Expr::Tuple(TupleExpr {
+                                    elements: Vec::new(),
+                                }),
+                                location,
+                            ));
+                        }
+                    }
                     let expr = self.expression()?;
-                    self.consume_next_token(scanner::Token::RightParen)?;
-                    Ok(Expr::Grouping(Box::new(expr)))
+                    if let Some(next_token) = self.peek_next_token() {
+                        if next_token.token == scanner::Token::Comma {
+                            self.deprecated_advance_token_index();
+                            return self.finish_tuple(source_token.location_span, expr);
+                        }
+                    }
+                    let right_paren = self.consume_next_token(scanner::Token::RightParen)?;
+                    let location = source_file::SourceSpan::merge(
+                        source_token.location_span,
+                        right_paren.location_span,
+                    );
+                    return Ok(self.expressions.push(Expr::Grouping(expr), location));
                 }
-                _ => Err(errors::Error {
-                    kind: errors::ErrorKind::Parsing,
-                    description: errors::ErrorDescription {
-                        subject: None,
-                        location: Some(source_token.location_span),
-                        description: format!(
+                // Keywords are called out, since otherwise it isn't obvious why something like
+                // `class` can't be used as a variable.
+                _ if source_token.token.is_keyword() => {
+                    return Err(errors::Error::parsing(
+                        source_token.location_span,
+                        format!(
+                            "Expected value or expression, found keyword '{}'",
+                            source_token.token
+                        ),
+                    )
+                    .with_code(ErrorCode::ExpectedExpression));
+                }
+                _ => {
+                    return Err(errors::Error::parsing(
+                        source_token.location_span,
+                        format!(
                             "Expected value or expression, found '{}'",
                             source_token.token
-                        ), // TODO: Better wording?
-                    },
-                }),
-            }
+                        ),
+                    )
+                    .with_code(ErrorCode::ExpectedExpression));
+                }
+            };
+            Ok(self.expressions.push(expr, source_token.location_span))
         } else {
-            Err(errors::Error {
-                kind: errors::ErrorKind::Parsing,
-                description: errors::ErrorDescription {
-                    subject: None,
-                    location: Some(self.previous_token().location_span),
-                    description: String::from("Ran out of tokens while satisfying expression rule"),
-                },
-            })
+            self.note_running_out_of_tokens();
+            Err(errors::Error::parsing(
+                self.previous_token().location_span,
+                "Ran out of tokens while satisfying expression rule",
+            )
+            .with_code(ErrorCode::ExpectedExpression)
+            .as_end_of_input())
         }
     }
+    // --- Expression Construction ---
+    // The spans of compound expressions run from the start of their first piece to the end of
+    // their last, see `SourceSpan::merge()`.
+    fn location_of(&self, expr: ExprId) -> source_file::SourceSpan {
+        self.expressions.get(expr).location
+    }
+    fn push_binary(&mut self, left: ExprId, operator: scanner::Token, right: ExprId) -> ExprId {
+        let location =
+            source_file::SourceSpan::merge(self.location_of(left), self.location_of(right));
+        self.expressions.push(
+            Expr::Binary(BinaryExpr {
+                left,
+                operator,
+                right,
+            }),
+            location,
+        )
+    }
+    fn push_logical(&mut self, left: ExprId, operator: scanner::Token, right: ExprId) -> ExprId {
+        let location =
+            source_file::SourceSpan::merge(self.location_of(left), self.location_of(right));
+        self.expressions.push(
+            Expr::Logical(LogicalExpr {
+                left,
+                operator,
+                right,
+            }),
+            location,
+        )
+    }
 }
 
 // TODO: I think this can actually be done generically in errors.rs, and handled simply by importing.
@@ -505,3 +1556,259 @@ impl errors::ErrorLoggable for Parser {
         &self.error_log
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> (Program, errors::ErrorLog) {
+        let scanner = scanner::Scanner::from_source(source.to_string());
+        let mut parser = Parser::new(scanner.tokens());
+        let program = parser.parse();
+        (program, parser.error_log)
+    }
+
+    fn error_codes(source: &str) -> Vec<Option<ErrorCode>> {
+        parse(source).1.iter().map(|error| error.code).collect()
+    }
+
+    #[test]
+    fn parses_by_precedence() {
+        let (program, log) = parse("print 1 + 2 * 3 == 7 and !false;");
+        assert!(log.is_empty());
+        assert_eq!(
+            program.to_string(),
+            "Print Statement: (and (== (+ 1 (* 2 3)) 7) (! false))"
+        );
+    }
+
+    #[test]
+    fn literals_display_as_print_shows_them() {
+        assert_eq!(LiteralKind::Nil.to_string(), "nil");
+        assert_eq!(LiteralKind::Boolean(true).to_string(), "true");
+        assert_eq!(LiteralKind::Boolean(false).to_string(), "false");
+        assert_eq!(LiteralKind::Number(2.0).to_string(), "2");
+        assert_eq!(LiteralKind::String(String::from("s")).to_string(), "s");
+    }
+
+    #[test]
+    fn parses_a_lone_expression() {
+        let scanner = scanner::Scanner::from_source(String::from("1 + 2"));
+        let (expression, expressions) = Parser::new(scanner.tokens()).parse_expression().unwrap();
+        assert!(matches!(expressions.get(expression).expr, Expr::Binary(_)));
+    }
+
+    #[test]
+    fn reports_a_missing_lone_expression() {
+        for source in ["", "  \n"] {
+            let scanner = scanner::Scanner::from_source(source.to_string());
+            let error = Parser::new(scanner.tokens())
+                .parse_expression()
+                .unwrap_err();
+            assert_eq!(error.code, Some(ErrorCode::ExpectedExpression));
+        }
+    }
+
+    #[test]
+    fn reports_input_after_a_lone_expression() {
+        let scanner = scanner::Scanner::from_source(String::from("1 2"));
+        let error = Parser::new(scanner.tokens())
+            .parse_expression()
+            .unwrap_err();
+        assert_eq!(error.code, Some(ErrorCode::TrailingInput));
+    }
+
+    #[test]
+    fn parses_function_declarations() {
+        let (program, log) = parse("fun add(a, b) { return a + b; } fun nothing() { return; }");
+        assert!(log.is_empty());
+        assert_eq!(
+            program.to_string(),
+            "Function Statement: add(a, b) {
+  Return Statement: (+ (var a) (var b))
+}
+Function Statement: nothing() {
+  Return Statement
+}"
+        );
+    }
+
+    #[test]
+    fn parses_maps_at_the_start_of_a_statement() {
+        let (program, log) = parse("{\"a\": 1}; {b: 2, c: 3}; {}; {} { print 1; }");
+        assert!(log.is_empty());
+        assert_eq!(
+            program.to_string(),
+            "Expression Statement: (map (\"a\" 1))
+Expression Statement: (map (\"b\" 2) (\"c\" 3))
+Expression Statement: (map)
+Block Statement: {
+}
+Block Statement: {
+  Print Statement: 1
+}"
+        );
+    }
+
+    #[test]
+    fn prints_long_logical_chains() {
+        let chain = vec!["a"; 100_000].join(" or ");
+        let (program, log) = parse(&format!("{};", chain));
+        assert!(log.is_empty());
+        let printed = program.to_string();
+        let expected = format!(
+            "Expression Statement: {}(var a){}",
+            "(or ".repeat(99_999),
+            " (var a))".repeat(99_999)
+        );
+        assert_eq!(printed, expected);
+    }
+
+    #[test]
+    fn prints_variables_assignments_and_calls() {
+        let (program, log) = parse("print a; a = b = 1; f(a, \"s\")();");
+        assert!(log.is_empty());
+        assert_eq!(
+            program.to_string(),
+            "Print Statement: (var a)
+Expression Statement: (assign a (assign b 1))
+Expression Statement: (call (call (var f) (var a) s))"
+        );
+    }
+
+    // A lone expression as the printer shows it.
+    fn printed(source: &str) -> String {
+        let scanner = scanner::Scanner::from_source(source.to_string());
+        let (expression, expressions) = Parser::new(scanner.tokens()).parse_expression().unwrap();
+        crate::ast_printer::expr_to_ast_string(expression, &expressions)
+    }
+
+    #[test]
+    fn prints_assignments() {
+        assert_eq!(printed("a = b = 1"), "(assign a (assign b 1))");
+    }
+
+    #[test]
+    fn prints_logical_operators() {
+        assert_eq!(
+            printed("a or b and !c"),
+            "(or (var a) (and (var b) (! (var c))))"
+        );
+    }
+
+    #[test]
+    fn prints_calls() {
+        assert_eq!(printed("f()"), "(call (var f))");
+        assert_eq!(
+            printed("f(1, \"s\")(g)"),
+            "(call (call (var f) 1 s) (var g))"
+        );
+    }
+
+    #[test]
+    fn prints_property_gets() {
+        assert_eq!(printed("a.b.c"), "(. (. (var a) b) c)");
+    }
+
+    #[test]
+    fn prints_property_sets() {
+        assert_eq!(printed("a.b.c = 1"), "(assign (. (. (var a) b) c) 1)");
+    }
+
+    #[test]
+    fn prints_this() {
+        assert_eq!(printed("this.x"), "(. (this) x)");
+    }
+
+    #[test]
+    fn prints_super() {
+        assert_eq!(printed("super.m(1)"), "(call (super m) 1)");
+    }
+
+    #[test]
+    fn parses_class_declarations() {
+        let (program, log) = parse("class B < A { init(x) { this.x = super.make(x); } }");
+        assert!(log.is_empty());
+        assert_eq!(
+            program.to_string(),
+            "Class Statement: B < (var A) {
+  Method: init(x) {
+    Expression Statement: (assign (. (this) x) (call (super make) (var x)))
+  }
+}"
+        );
+    }
+
+    #[test]
+    fn reports_a_missing_operand() {
+        assert_eq!(
+            error_codes("var a = 1 +;"),
+            vec![Some(ErrorCode::ExpectedExpression)]
+        );
+    }
+
+    #[test]
+    fn reports_a_missing_semicolon() {
+        assert_eq!(
+            error_codes("print 1 print 2;"),
+            vec![Some(ErrorCode::ExpectedToken)]
+        );
+    }
+
+    #[test]
+    fn reports_invalid_assignment_targets() {
+        assert_eq!(
+            error_codes("1 = a; a + b = c;"),
+            vec![Some(ErrorCode::InvalidAssignmentTarget); 2]
+        );
+    }
+
+    #[test]
+    fn carries_on_after_an_error() {
+        let (program, log) = parse("var = 1; print 2; print ;");
+        assert_eq!(log.error_count(), 2);
+        assert_eq!(program.to_string(), "Print Statement: 2");
+    }
+
+    #[test]
+    fn warns_about_assignments_as_conditions() {
+        let (_, log) = parse("if (a = b) print a; if ((a = b)) print a;");
+        assert_eq!(log.error_count(), 0);
+        assert_eq!(log.warning_count(), 1);
+    }
+
+    #[test]
+    fn tells_tuples_from_groupings() {
+        let (program, _) = parse("(1); (1,); ();");
+        let kinds: Vec<&str> = program
+            .statements
+            .iter()
+            .map(|statement| match statement {
+                Stmt::Expression(statement) => {
+                    match &program.expressions.get(statement.expression).expr {
+                        Expr::Grouping(_) => "grouping",
+                        Expr::Tuple(_) => "tuple",
+                        _ => "other",
+                    }
+                }
+                _ => "statement",
+            })
+            .collect();
+        assert_eq!(kinds, vec!["grouping", "tuple", "tuple"]);
+    }
+
+    #[test]
+    fn desugars_for_into_while() {
+        let (program, log) = parse("for (var i = 0; i < 3; i = i + 1) print i;");
+        assert!(log.is_empty());
+        assert_eq!(
+            program.to_string(),
+            "Block Statement: {
+  Variable Statement: i = 0
+  While Statement: (< (var i) 3)
+    Body: Print Statement: (var i)
+    Increment: (assign i (+ (var i) 1))
+}"
+        );
+    }
+}
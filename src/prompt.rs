@@ -0,0 +1,92 @@
+use std::env;
+use std::io;
+use std::io::IsTerminal;
+use std::io::Write;
+use std::path::PathBuf;
+
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+// Where history is kept if `RLOX_HISTORY_VARIABLE` isn't set, relative to the home directory.
+const DEFAULT_HISTORY_FILE_NAME: &str = ".rlox_history";
+const RLOX_HISTORY_VARIABLE: &str = "RLOX_HISTORY";
+
+// -----| Prompt |-----
+
+pub enum Prompt {
+    /// Line editing and history, for when there's a person typing at the other end.
+    Editor {
+        editor: Box<DefaultEditor>,
+        history_path: Option<PathBuf>,
+    },
+    /// Plain line reads, for when input is piped in or the editor couldn't be set up.
+    Raw,
+}
+
+impl Prompt {
+    pub fn new() -> Self {
+        if !io::stdin().is_terminal() {
+            return Prompt::Raw;
+        }
+        match DefaultEditor::new() {
+            Ok(mut editor) => {
+                let history_path = history_path();
+                if let Some(path) = &history_path {
+                    // There won't be a file the first time around, which is fine.
+                    let _ = editor.load_history(path);
+                }
+                Prompt::Editor {
+                    editor: Box::new(editor),
+                    history_path,
+                }
+            }
+            Err(_) => Prompt::Raw,
+        }
+    }
+    /// Reads a single line, without its newline. Returns `None` once there's no more input.
+    pub fn read_line(&mut self, prompt: &str) -> Option<String> {
+        match self {
+            Prompt::Editor {
+                editor,
+                history_path,
+            } => match editor.readline(prompt) {
+                Ok(line) => {
+                    if !line.trim().is_empty() {
+                        let _ = editor.add_history_entry(line.as_str());
+                        // Saved after every line, since an error ends the process without
+                        // giving us a chance to save on the way out.
+                        if let Some(path) = history_path {
+                            let _ = editor.save_history(path);
+                        }
+                    }
+                    Some(line)
+                }
+                Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => None,
+                Err(error) => panic!("Failed to read user input: {}", error),
+            },
+            Prompt::Raw => {
+                print_flush(prompt);
+                let mut line = String::new();
+                let read = io::stdin()
+                    .read_line(&mut line)
+                    .expect("Failed to read user input");
+                if read == 0 {
+                    return None;
+                }
+                Some(line.trim_end_matches(['\n', '\r']).to_string())
+            }
+        }
+    }
+}
+
+fn print_flush(str: &str) {
+    print!("{}", str);
+    io::stdout().flush().expect("Failed to flush output");
+}
+
+fn history_path() -> Option<PathBuf> {
+    if let Ok(path) = env::var(RLOX_HISTORY_VARIABLE) {
+        return Some(PathBuf::from(path));
+    }
+    env::home_dir().map(|home| home.join(DEFAULT_HISTORY_FILE_NAME))
+}
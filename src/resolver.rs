@@ -0,0 +1,611 @@
+use std::collections::HashMap;
+
+use crate::error_codes::ErrorCode;
+use crate::errors;
+use crate::parser::{ClassStmt, Expr, ExprArena, ExprId, FunctionStmt, Stmt};
+use crate::scanner::Identifier;
+use crate::source_file;
+
+// -----| Resolver |-----
+
+/// Walks a parsed program before it runs, checking how variables are used wherever that can be
+/// known ahead of time. Local scopes are checked strictly. Globals can be redeclared (so that the
+/// prompt stays forgiving) and used before they're declared (so that later on, functions can refer
+/// to each other in any order), and could be used by anything typed at the prompt later, so the
+/// most there is to say about them is a warning when one is redeclared.
+pub struct Resolver<'a> {
+    expressions: &'a ExprArena,
+    /// Innermost scope last. The global scope isn't in here, it's never popped.
+    scopes: Vec<HashMap<Identifier, Local>>,
+    /// Where each global was first declared.
+    globals: HashMap<Identifier, source_file::SourceSpan>,
+    /// How many scopes out from where it's used each local variable is, by the expression that
+    /// reads or assigns it. Globals aren't in here.
+    locals: HashMap<ExprId, usize>,
+    /// How many loops the statement being resolved is inside of, for checking `continue`. Only
+    /// loops in the innermost function count, `continue` can't jump out of a function.
+    loop_depth: usize,
+    /// What kind of function the statement being resolved is inside of, for checking `return`.
+    function_type: FunctionType,
+    /// What kind of class the statement being resolved is inside of, for checking `this` and
+    /// `super`.
+    class_type: ClassType,
+    /// Where the name of the innermost class is, if there is one.
+    class_location: Option<source_file::SourceSpan>,
+    error_log: errors::ErrorLog,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionType {
+    /// Top-level code.
+    None,
+    Function,
+    Method,
+    /// The `init` method, which always gives back the instance, whatever it returns.
+    Initializer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    /// A class with a superclass, for `super` to refer to.
+    Subclass,
+}
+
+struct Local {
+    kind: LocalKind,
+    /// Where the variable was declared.
+    location: source_file::SourceSpan,
+    /// Whether its initializer has finished yet.
+    defined: bool,
+    /// Whether it's ever read. Assigning to it doesn't count, since a variable that's only ever
+    /// written to may as well not be there.
+    read: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LocalKind {
+    Variable,
+    Parameter,
+    Function,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(expressions: &'a ExprArena) -> Self {
+        Resolver {
+            expressions,
+            scopes: Vec::new(),
+            globals: HashMap::new(),
+            locals: HashMap::new(),
+            loop_depth: 0,
+            function_type: FunctionType::None,
+            class_type: ClassType::None,
+            class_location: None,
+            error_log: errors::ErrorLog::new(),
+        }
+    }
+    pub fn resolve(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            self.resolve_statement(statement);
+        }
+    }
+    pub fn locals(&self) -> &HashMap<ExprId, usize> {
+        &self.locals
+    }
+    // --- Statements ---
+    fn resolve_statement(&mut self, statement: &Stmt) {
+        match statement {
+            Stmt::Expression(statement) => self.resolve_expression(statement.expression),
+            Stmt::Print(statement) => self.resolve_expression(statement.expression),
+            // The name is declared before the initializer is resolved, and only defined after, so
+            // that the initializer can't refer to the variable it's initializing.
+            Stmt::Var(statement) => {
+                self.declare(
+                    &statement.name,
+                    statement.name_location,
+                    LocalKind::Variable,
+                );
+                if let Some(initializer) = statement.initializer {
+                    self.resolve_expression(initializer);
+                }
+                self.define(&statement.name);
+            }
+            Stmt::Block(statement) => {
+                self.scopes.push(HashMap::new());
+                self.resolve(&statement.statements);
+                self.end_scope();
+            }
+            Stmt::If(statement) => {
+                self.resolve_expression(statement.condition);
+                self.resolve_statement(&statement.then_branch);
+                if let Some(else_branch) = &statement.else_branch {
+                    self.resolve_statement(else_branch);
+                }
+            }
+            Stmt::While(statement) => {
+                self.resolve_expression(statement.condition);
+                self.loop_depth += 1;
+                self.resolve_statement(&statement.body);
+                self.loop_depth -= 1;
+                if let Some(increment) = statement.increment {
+                    self.resolve_expression(increment);
+                }
+            }
+            Stmt::Continue(statement) => {
+                if self.loop_depth == 0 {
+                    self.error_log.push(
+                        errors::Error::resolution(
+                            statement.location,
+                            "Can't use 'continue' outside of a loop",
+                        )
+                        .with_code(ErrorCode::ContinueOutsideLoop),
+                    );
+                }
+            }
+            // Defined straight away, unlike a variable, so that the function can call itself.
+            Stmt::Function(function) => {
+                self.declare(&function.name, function.name_location, LocalKind::Function);
+                self.define(&function.name);
+                self.resolve_function(function, FunctionType::Function);
+            }
+            Stmt::Return(statement) => {
+                if self.function_type == FunctionType::None {
+                    self.error_log.push(
+                        errors::Error::resolution(
+                            statement.location,
+                            "Can't return from top-level code",
+                        )
+                        .with_code(ErrorCode::ReturnOutsideFunction),
+                    );
+                }
+                if let Some(value) = statement.value {
+                    if self.function_type == FunctionType::Initializer {
+                        self.error_log.push(
+                            errors::Error::resolution(
+                                statement.location,
+                                "Can't return a value from an initializer",
+                            )
+                            .with_code(ErrorCode::ReturnFromInitializer)
+                            .with_label(
+                                self.expressions.get(value).location,
+                                "the instance is returned instead of this",
+                            ),
+                        );
+                    }
+                    self.resolve_expression(value);
+                }
+            }
+            Stmt::Class(class) => self.resolve_class(class),
+        }
+    }
+    // Methods are resolved inside a scope holding `this`, which is inside one holding `super` if
+    // there's a superclass, the same way they're run.
+    fn resolve_class(&mut self, class: &ClassStmt) {
+        self.declare(&class.name, class.name_location, LocalKind::Variable);
+        self.define(&class.name);
+        let enclosing_class_type = std::mem::replace(&mut self.class_type, ClassType::Class);
+        let enclosing_class_location = self.class_location.replace(class.name_location);
+        if let Some(superclass) = class.superclass {
+            if let Expr::Variable(superclass_name) = &self.expressions.get(superclass).expr {
+                if *superclass_name == class.name {
+                    self.error_log.push(
+                        construct_resolution_error(
+                            "A class can't inherit from itself",
+                            superclass_name,
+                            self.expressions.get(superclass).location,
+                        )
+                        .with_code(ErrorCode::InheritsFromItself),
+                    );
+                }
+            }
+            self.class_type = ClassType::Subclass;
+            self.resolve_expression(superclass);
+            self.begin_keyword_scope("super");
+        }
+        self.begin_keyword_scope("this");
+        for method in class.methods.iter() {
+            let function_type = if method.name == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.resolve_function(method, function_type);
+        }
+        self.scopes.pop();
+        if class.superclass.is_some() {
+            self.scopes.pop();
+        }
+        self.class_type = enclosing_class_type;
+        self.class_location = enclosing_class_location;
+    }
+    // `this` and `super` are defined by the interpreter rather than declared anywhere, and it
+    // doesn't matter whether they're used.
+    fn begin_keyword_scope(&mut self, keyword: &str) {
+        let mut scope = HashMap::new();
+        scope.insert(
+            keyword.to_string(),
+            Local {
+                kind: LocalKind::Variable,
+                location: source_file::SourceSpan::new(),
+                defined: true,
+                read: true,
+            },
+        );
+        self.scopes.push(scope);
+    }
+    fn resolve_function(&mut self, function: &FunctionStmt, function_type: FunctionType) {
+        let enclosing_function_type = std::mem::replace(&mut self.function_type, function_type);
+        let enclosing_loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        self.scopes.push(HashMap::new());
+        for parameter in function.parameters.iter() {
+            self.declare(&parameter.name, parameter.location, LocalKind::Parameter);
+            self.define(&parameter.name);
+        }
+        self.resolve(&function.body);
+        self.end_scope();
+        self.function_type = enclosing_function_type;
+        self.loop_depth = enclosing_loop_depth;
+    }
+    // --- Expressions ---
+    fn resolve_expression(&mut self, expr: ExprId) {
+        match &self.expressions.get(expr).expr {
+            // Walked with a loop for the same reason the interpreter does, long chains like
+            // `a + b + c + ...` are as deep as they are long. The same goes for logical chains.
+            Expr::Binary(binary) => {
+                let mut rights = vec![binary.right];
+                let mut leftmost = binary.left;
+                while let Expr::Binary(inner) = &self.expressions.get(leftmost).expr {
+                    rights.push(inner.right);
+                    leftmost = inner.left;
+                }
+                self.resolve_expression(leftmost);
+                for right in rights.into_iter().rev() {
+                    self.resolve_expression(right);
+                }
+            }
+            Expr::Ternary(ternary) => {
+                self.resolve_expression(ternary.condition);
+                self.resolve_expression(ternary.left_result);
+                self.resolve_expression(ternary.right_result);
+            }
+            Expr::Logical(logical) => {
+                let mut rights = vec![logical.right];
+                let mut leftmost = logical.left;
+                while let Expr::Logical(inner) = &self.expressions.get(leftmost).expr {
+                    rights.push(inner.right);
+                    leftmost = inner.left;
+                }
+                self.resolve_expression(leftmost);
+                for right in rights.into_iter().rev() {
+                    self.resolve_expression(right);
+                }
+            }
+            Expr::Grouping(inner) => self.resolve_expression(*inner),
+            Expr::Unary(unary) => self.resolve_expression(unary.right),
+            Expr::Typeof(operand) => self.resolve_expression(*operand),
+            Expr::Literal(_) => {}
+            Expr::List(list) => {
+                for element in list.elements.iter() {
+                    self.resolve_expression(*element);
+                }
+            }
+            Expr::Tuple(tuple) => {
+                for element in tuple.elements.iter() {
+                    self.resolve_expression(*element);
+                }
+            }
+            Expr::Map(map) => {
+                for (_, value) in map.entries.iter() {
+                    self.resolve_expression(*value);
+                }
+            }
+            Expr::Variable(name) => {
+                let in_own_initializer = self
+                    .scopes
+                    .last()
+                    .and_then(|scope| scope.get(name))
+                    .is_some_and(|local| !local.defined);
+                if in_own_initializer {
+                    self.error_log.push(
+                        construct_resolution_error(
+                            "Can't read local variable in its own initializer",
+                            name,
+                            self.expressions.get(expr).location,
+                        )
+                        .with_code(ErrorCode::ReadInOwnInitializer),
+                    );
+                }
+                self.mark_read(name);
+                self.resolve_local(expr, name);
+            }
+            Expr::Assign(assign) => {
+                self.resolve_expression(assign.value);
+                self.resolve_local(expr, &assign.name);
+            }
+            Expr::Get(get) => self.resolve_expression(get.object),
+            Expr::Set(set) => {
+                self.resolve_expression(set.value);
+                self.resolve_expression(set.object);
+            }
+            Expr::Call(call) => {
+                self.resolve_expression(call.callee);
+                for argument in call.arguments.iter() {
+                    self.resolve_expression(*argument);
+                }
+            }
+            Expr::This(_) => {
+                if self.class_type == ClassType::None {
+                    self.error_log.push(
+                        errors::Error::resolution(
+                            self.expressions.get(expr).location,
+                            "Can't use 'this' outside of a class",
+                        )
+                        .with_code(ErrorCode::OutsideClass),
+                    );
+                }
+                self.resolve_local(expr, &String::from("this"));
+            }
+            Expr::Super(super_expr) => {
+                let location = super_expr.keyword_location;
+                match self.class_type {
+                    ClassType::None => self.error_log.push(
+                        errors::Error::resolution(location, "Can't use 'super' outside of a class")
+                            .with_code(ErrorCode::OutsideClass),
+                    ),
+                    ClassType::Class => {
+                        let mut error = errors::Error::resolution(
+                            location,
+                            "Can't use 'super' in a class with no superclass",
+                        )
+                        .with_code(ErrorCode::NoSuperclass);
+                        if let Some(class_location) = self.class_location {
+                            error = error.with_label(
+                                class_location,
+                                "this class doesn't inherit from anything",
+                            );
+                        }
+                        self.error_log.push(error);
+                    }
+                    ClassType::Subclass => {}
+                }
+                self.resolve_local(expr, &String::from("super"));
+            }
+        }
+    }
+    // --- Scopes ---
+    // Redeclaring a global is allowed, but it's still most likely a mistake in a script, where the
+    // first declaration is right there to be seen (unlike at the prompt, where each line is
+    // resolved on its own).
+    fn declare(&mut self, name: &Identifier, location: source_file::SourceSpan, kind: LocalKind) {
+        if self.scopes.is_empty() {
+            if let Some(first_location) = self.globals.get(name) {
+                self.error_log.push(
+                    errors::Error::resolution(location, "Global variable is already declared")
+                        .with_code(ErrorCode::RedeclaredGlobal)
+                        .with_subject(name.to_string())
+                        .with_label(*first_location, "first declared here")
+                        .as_warning(),
+                );
+            } else {
+                self.globals.insert(name.clone(), location);
+            }
+        }
+        if let Some(scope) = self.scopes.last_mut() {
+            if let Some(first) = scope.get(name) {
+                self.error_log.push(
+                    construct_resolution_error(
+                        "Already a variable with this name in this scope",
+                        name,
+                        location,
+                    )
+                    .with_code(ErrorCode::DuplicateLocal)
+                    .with_label(first.location, "first declared here")
+                    .with_note("to shadow a variable, declare the new one in an inner block"),
+                );
+            }
+            scope.insert(
+                name.clone(),
+                Local {
+                    kind,
+                    location,
+                    defined: false,
+                    read: false,
+                },
+            );
+        }
+    }
+    fn define(&mut self, name: &Identifier) {
+        if let Some(local) = self.scopes.last_mut().and_then(|scope| scope.get_mut(name)) {
+            local.defined = true;
+        }
+    }
+    // Anything not found in a local scope is assumed to be a global, which may not be declared yet.
+    fn resolve_local(&mut self, expr: ExprId, name: &Identifier) {
+        if let Some(depth) = self
+            .scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(name))
+        {
+            self.locals.insert(expr, depth);
+        }
+    }
+    // Only the innermost variable with the name is the one being read, any it shadows aren't.
+    fn mark_read(&mut self, name: &Identifier) {
+        if let Some(local) = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name))
+        {
+            local.read = true;
+        }
+    }
+    // Any variable, parameter or local function that's never read is warned about, unless its name
+    // starts with an underscore to say that's on purpose.
+    fn end_scope(&mut self) {
+        if let Some(scope) = self.scopes.pop() {
+            let mut unread: Vec<(&Identifier, &Local)> = scope
+                .iter()
+                .filter(|(name, local)| !local.read && !name.starts_with('_'))
+                .collect();
+            // Hash maps are unordered, so this keeps the warnings in source order.
+            unread.sort_by_key(|(_, local)| local.location.start);
+            for (name, local) in unread {
+                let description = match local.kind {
+                    LocalKind::Variable => "Local variable is never read",
+                    LocalKind::Parameter => "Parameter is never read",
+                    LocalKind::Function => "Local function is never used",
+                };
+                self.error_log.push(
+                    errors::Error::resolution(local.location, description)
+                        .with_code(ErrorCode::UnreadLocal)
+                        .with_subject(name.to_string())
+                        .as_warning(),
+                );
+            }
+        }
+    }
+}
+
+impl errors::ErrorLoggable for Resolver<'_> {
+    fn error_log(&self) -> &errors::ErrorLog {
+        &self.error_log
+    }
+}
+
+fn construct_resolution_error(
+    description: &str,
+    name: &Identifier,
+    location: source_file::SourceSpan,
+) -> errors::Error {
+    errors::Error::resolution(location, String::from(description)).with_subject(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error_codes::ErrorCode;
+    use crate::errors::Severity;
+
+    // The codes of every error (not warning) the source gets, in source order.
+    fn error_codes(source: &str) -> Vec<ErrorCode> {
+        let (_, log) = crate::parse_source(source.to_string());
+        log.errors
+            .iter()
+            .filter(|error| error.severity == Severity::Error)
+            .filter_map(|error| error.code)
+            .collect()
+    }
+
+    #[test]
+    fn continue_inside_a_loop_is_fine() {
+        assert!(error_codes("while (false) { { continue; } }").is_empty());
+        assert!(error_codes("for (;;) continue;").is_empty());
+    }
+
+    #[test]
+    fn continue_cant_leave_a_function() {
+        assert_eq!(
+            error_codes("while (true) { fun f() { continue; } f(); }"),
+            vec![ErrorCode::ContinueOutsideLoop]
+        );
+    }
+
+    #[test]
+    fn return_outside_a_function_is_an_error() {
+        assert!(error_codes("fun f() { { return 1; } }").is_empty());
+        assert_eq!(
+            error_codes("return; { return 1; }"),
+            vec![ErrorCode::ReturnOutsideFunction; 2]
+        );
+    }
+
+    #[test]
+    fn return_a_value_from_an_initializer_is_an_error() {
+        assert!(error_codes("class A { init() { return; } m() { return 1; } }").is_empty());
+        assert_eq!(
+            error_codes("class A { init() { if (true) return 1; } }"),
+            vec![ErrorCode::ReturnFromInitializer]
+        );
+    }
+
+    #[test]
+    fn this_outside_a_class_is_an_error() {
+        assert!(error_codes("class A { m() { fun f() { return this; } return f; } }").is_empty());
+        assert_eq!(
+            error_codes("print this; fun f() { return this; }"),
+            vec![ErrorCode::OutsideClass; 2]
+        );
+    }
+
+    #[test]
+    fn super_needs_a_superclass() {
+        assert!(error_codes("class A {} class B < A { m() { return super.m; } }").is_empty());
+        assert_eq!(
+            error_codes("super.m; fun f() { super.m(); }"),
+            vec![ErrorCode::OutsideClass; 2]
+        );
+        assert_eq!(
+            error_codes("class A { m() { super.m(); } }"),
+            vec![ErrorCode::NoSuperclass]
+        );
+    }
+
+    #[test]
+    fn classes_cant_inherit_from_themselves() {
+        assert_eq!(
+            error_codes("class A < A {}"),
+            vec![ErrorCode::InheritsFromItself]
+        );
+    }
+
+    #[test]
+    fn misplaced_keywords_are_all_reported_at_once() {
+        let source = "return;
+                      class A { init() { return this; } m() { return super.m; } }
+                      print this;";
+        assert_eq!(
+            error_codes(source),
+            vec![
+                ErrorCode::ReturnOutsideFunction,
+                ErrorCode::ReturnFromInitializer,
+                ErrorCode::NoSuperclass,
+                ErrorCode::OutsideClass,
+            ]
+        );
+    }
+
+    #[test]
+    fn diagnostics_point_at_the_keyword() {
+        let (_, log) = crate::parse_source(String::from("class A { m() { super.m(); } }"));
+        let error = &log.errors[0];
+        let location = error.description.location.unwrap();
+        assert_eq!((location.start.column, location.end.column), (17, 22));
+        assert_eq!(error.labels[0].location.start.column, 7);
+    }
+
+    #[test]
+    fn warns_about_unused_parameters_and_local_functions() {
+        let (_, log) = crate::parse_source(String::from(
+            "fun f(a, _b) { fun g() {} } { fun h() {} h(); }",
+        ));
+        let warnings: Vec<&str> = log
+            .errors
+            .iter()
+            .map(|warning| warning.description.description.as_str())
+            .collect();
+        assert_eq!(
+            warnings,
+            vec!["Parameter is never read", "Local function is never used"]
+        );
+    }
+
+    #[test]
+    fn continue_outside_a_loop_is_an_error() {
+        assert_eq!(
+            error_codes("continue; { continue; } if (true) continue;"),
+            vec![ErrorCode::ContinueOutsideLoop; 3]
+        );
+    }
+}
@@ -1,11 +1,13 @@
 use std::fmt;
+use std::io;
 use unicode_segmentation::UnicodeSegmentation;
 
+use crate::error_codes::ErrorCode;
 use crate::errors;
 // use crate::language_utilities::enum_variant_equal;
 use crate::source_file;
 
-const USE_EXTENDED_UNICODE: bool = true;
+pub const USE_EXTENDED_UNICODE: bool = true;
 
 // -----| Symbols |-----
 
@@ -20,13 +22,15 @@ pub enum WhitespaceKind {
     Newline,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum Token {
     // Single-character tokens
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
     Minus,
@@ -34,8 +38,13 @@ pub enum Token {
     Semicolon,
     Slash,
     Star,
+    Percent,
     QuestionMark,
     Colon,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
     // One or two character tokens
     Bang,
     BangEqual,
@@ -45,13 +54,18 @@ pub enum Token {
     GreaterEqual,
     Less,
     LessEqual,
+    LessLess,
+    GreaterGreater,
     // Literals
     Identifier(Identifier), // Note if this ever changes then other representations of identifiers will need to also.
     String(String),
+    /// Scanned from `r"..."`, or `r#"..."#` when the string itself has quotes in it.
+    RawString(String),
     Number(f64),
     // Keywords
     And,
     Class,
+    Continue,
     Else,
     False,
     Fun,
@@ -64,6 +78,7 @@ pub enum Token {
     Super,
     This,
     True,
+    Typeof,
     Var,
     While,
     // Meta
@@ -79,6 +94,8 @@ impl fmt::Display for Token {
             Token::RightParen => String::from(")"),
             Token::LeftBrace => String::from("{"),
             Token::RightBrace => String::from("}"),
+            Token::LeftBracket => String::from("["),
+            Token::RightBracket => String::from("]"),
             Token::Comma => String::from(","),
             Token::Dot => String::from("."),
             Token::Minus => String::from("-"),
@@ -86,8 +103,13 @@ impl fmt::Display for Token {
             Token::Semicolon => String::from(";"),
             Token::Slash => String::from("/"),
             Token::Star => String::from("*"),
+            Token::Percent => String::from("%"),
             Token::QuestionMark => String::from("?"),
             Token::Colon => String::from(":"),
+            Token::Ampersand => String::from("&"),
+            Token::Pipe => String::from("|"),
+            Token::Caret => String::from("^"),
+            Token::Tilde => String::from("~"),
             Token::Bang => String::from("!"),
             Token::BangEqual => String::from("!="),
             Token::Equal => String::from("="),
@@ -96,11 +118,15 @@ impl fmt::Display for Token {
             Token::GreaterEqual => String::from(">="),
             Token::Less => String::from("<"),
             Token::LessEqual => String::from("<="),
+            Token::LessLess => String::from("<<"),
+            Token::GreaterGreater => String::from(">>"),
             Token::Identifier(identifier) => format!("identifier \"{}\"", identifier),
             Token::String(string) => format!("string \"{}\"", string),
+            Token::RawString(string) => format!("raw string \"{}\"", string),
             Token::Number(number) => format!("number \"{}\"", number),
             Token::And => String::from("and"),
             Token::Class => String::from("class"),
+            Token::Continue => String::from("continue"),
             Token::Else => String::from("else"),
             Token::False => String::from("false"),
             Token::Fun => String::from("fun"),
@@ -113,6 +139,7 @@ impl fmt::Display for Token {
             Token::Super => String::from("super"),
             Token::This => String::from("this"),
             Token::True => String::from("true"),
+            Token::Typeof => String::from("typeof"),
             Token::Var => String::from("var"),
             Token::While => String::from("while"),
             Token::Comment(comment) => format!("comment \"{}\"", comment),
@@ -123,10 +150,99 @@ impl fmt::Display for Token {
     }
 }
 
+// Two tokens are equal when they'd have been scanned from the same source, so numbers are compared
+// by their bits rather than as floats. That way a NaN is equal to itself, the same as any other
+// token.
+impl PartialEq for Token {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Token::Identifier(a), Token::Identifier(b)) => a == b,
+            (Token::String(a), Token::String(b)) => a == b,
+            (Token::RawString(a), Token::RawString(b)) => a == b,
+            (Token::Number(a), Token::Number(b)) => a.total_cmp(b).is_eq(),
+            (Token::Comment(a), Token::Comment(b)) => a == b,
+            (Token::Whitespace(a), Token::Whitespace(b)) => a == b,
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+impl Eq for Token {}
+
+// The categories below are by how the tokens are spelled, not how they're used, so `true`, `false`
+// and `nil` count as keywords rather than literals, and `and`/`or` as keywords rather than
+// operators.
+impl Token {
+    pub fn is_keyword(&self) -> bool {
+        self.to_keyword_str().is_some()
+    }
+    /// How a keyword is spelled in source, the reverse of `match_keyword()`. Nothing for anything
+    /// else, even `Infinity`, which scans to a number.
+    pub fn to_keyword_str(&self) -> Option<&'static str> {
+        match self {
+            Token::And => Some("and"),
+            Token::Class => Some("class"),
+            Token::Continue => Some("continue"),
+            Token::Else => Some("else"),
+            Token::False => Some("false"),
+            Token::Fun => Some("fun"),
+            Token::For => Some("for"),
+            Token::If => Some("if"),
+            Token::Nil => Some("nil"),
+            Token::Or => Some("or"),
+            Token::Print => Some("print"),
+            Token::Return => Some("return"),
+            Token::Super => Some("super"),
+            Token::This => Some("this"),
+            Token::True => Some("true"),
+            Token::Typeof => Some("typeof"),
+            Token::Var => Some("var"),
+            Token::While => Some("while"),
+            _ => None,
+        }
+    }
+    /// Anything that can go between or in front of operands. Brackets and other punctuation that
+    /// only holds things together (like `,` and `;`) doesn't count.
+    pub fn is_operator(&self) -> bool {
+        matches!(
+            self,
+            Token::Dot
+                | Token::Minus
+                | Token::Plus
+                | Token::Slash
+                | Token::Star
+                | Token::Percent
+                | Token::QuestionMark
+                | Token::Colon
+                | Token::Ampersand
+                | Token::Pipe
+                | Token::Caret
+                | Token::Tilde
+                | Token::Bang
+                | Token::BangEqual
+                | Token::Equal
+                | Token::EqualEqual
+                | Token::Greater
+                | Token::GreaterEqual
+                | Token::Less
+                | Token::LessEqual
+                | Token::LessLess
+                | Token::GreaterGreater
+        )
+    }
+    pub fn is_literal(&self) -> bool {
+        matches!(
+            self,
+            Token::String(_) | Token::RawString(_) | Token::Number(_)
+        )
+    }
+}
+
 fn match_keyword(symbol: &str) -> Option<Token> {
     match symbol {
         "and" => Some(Token::And),
         "class" => Some(Token::Class),
+        "continue" => Some(Token::Continue),
         "else" => Some(Token::Else),
         "false" => Some(Token::False),
         "for" => Some(Token::For),
@@ -139,8 +255,12 @@ fn match_keyword(symbol: &str) -> Option<Token> {
         "super" => Some(Token::Super),
         "this" => Some(Token::This),
         "true" => Some(Token::True),
+        "typeof" => Some(Token::Typeof),
         "var" => Some(Token::Var),
         "while" => Some(Token::While),
+        // Not a keyword as such, just a number that can't be written with digits. This way
+        // anything the interpreter prints as `Infinity` can be read back in.
+        "Infinity" => Some(Token::Number(f64::INFINITY)),
         _ => None,
     }
 }
@@ -171,16 +291,48 @@ fn is_digit(symbol: &str) -> bool {
     grapheme_to_char(symbol).is_ascii_digit()
 }
 
+// Any unicode letter will do. Since symbols are whole graphemes, a letter with combining marks
+// (like an "e" followed by an acute accent) is judged by its base letter and kept in one piece.
+// Emoji aren't letters, so they're still rejected.
 fn is_alpha(symbol: &str) -> bool {
     let as_char = grapheme_to_char(symbol);
-    as_char.is_ascii_alphabetic() || as_char == '_'
+    as_char.is_alphabetic() || as_char == '_'
 }
 
 fn is_alpha_numeric(symbol: &str) -> bool {
     is_alpha(symbol) || is_digit(symbol)
 }
 
+// Symbols are graphemes, and a windows line ending is a single grapheme.
+fn is_newline(symbol: &str) -> bool {
+    symbol == "\n" || symbol == "\r\n"
+}
+
+// Numbers can be broken up for readability, like `1_000_000`, and the separators are ignored. A
+// number has to start with a digit though, so `_123` is still an identifier.
+const DIGIT_SEPARATOR: &str = "_";
+
+// Separators have to sit between two digits, so not at either end of a number, next to the decimal
+// point (`1_.0`), or doubled up.
+fn has_valid_separators(number: &str) -> bool {
+    let characters: Vec<char> = number.chars().collect();
+    characters.iter().enumerate().all(|(index, character)| {
+        *character != '_'
+            || (index > 0
+                && characters[index - 1].is_ascii_alphanumeric()
+                && characters
+                    .get(index + 1)
+                    .is_some_and(|next| next.is_ascii_alphanumeric()))
+    })
+}
+
+// Nothing in a scanner is tied to the thread it was made on, and it should stay that way, so that
+// scanning can happen in the background (or in parallel, one file per thread) later on.
+static_assertions::assert_impl_all!(Scanner: Send, Sync);
+
 /// The main object through which the source is consumed and transformed into a token sequence.
+/// Either all at once with `from_source()`, or one token at a time by iterating over a scanner
+/// made with `new()`.
 pub struct Scanner {
     /// UTF8 Graphemes
     source: Vec<String>,
@@ -188,43 +340,72 @@ pub struct Scanner {
     /// The subset of the source currently being investigated
     cursor: source_file::SourceSpan,
     error_log: errors::ErrorLog,
+    /// Whether iteration has handed out the Eof token yet.
+    reached_eof: bool,
 }
 
 impl Scanner {
     // --- Constructors ---
-    pub fn new() -> Self {
+    // Note that while tokens are only scanned as they're asked for, the source is still split into
+    // graphemes up front.
+    pub fn new(source: String) -> Self {
         Scanner {
-            source: Vec::new(), // TODO: Use a struct created in `source_file.rs`
+            // TODO: Use a struct created in `source_file.rs`
+            source: source
+                .graphemes(USE_EXTENDED_UNICODE)
+                .map(String::from)
+                .collect(),
             tokens: Vec::new(),
             cursor: source_file::SourceSpan::new(),
             error_log: errors::ErrorLog::new(),
+            reached_eof: false,
         }
     }
     pub fn from_source(source: String) -> Self {
-        let mut ret = Scanner::new();
-        ret.tokenize(source);
+        let mut ret = Scanner::new(source);
+        ret.tokenize();
         ret
     }
+    /// Reads the whole of `reader` before scanning it.
+    pub fn from_reader<R: io::Read>(mut reader: R) -> Result<Self, io::Error> {
+        let mut source = String::new();
+        reader.read_to_string(&mut source)?;
+        Ok(Scanner::from_source(source))
+    }
+    /// Reads a whole string as a single number literal, exactly as it would be scanned in source
+    /// (so `Infinity` counts, and anything added to number literals later will too). Surrounding
+    /// whitespace and a leading `-` are allowed, since those are what people actually type.
+    pub fn parse_number(source: &str) -> Option<f64> {
+        let source = source.trim();
+        let (sign, digits) = match source.strip_prefix('-') {
+            Some(digits) => (-1.0, digits),
+            None => (1.0, source),
+        };
+        let mut scanner = Scanner::new(digits.to_string());
+        match (scanner.next(), scanner.next()) {
+            (Some(Ok(number)), Some(Ok(eof))) => match (number.token, eof.token) {
+                (Token::Number(value), Token::Eof) => Some(sign * value),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
     // --- Accessors ---
     pub fn tokens(&self) -> Vec<SourceToken> {
         self.tokens.clone()
     }
+    /// The whole of the source, put back together, for showing diagnostics against.
+    pub fn source(&self) -> String {
+        self.source.concat()
+    }
     // --- Responsibilities ---
-    fn tokenize(&mut self, raw_source: String) {
-        self.source = raw_source
-            .graphemes(USE_EXTENDED_UNICODE)
-            .map(|grapheme| String::from(grapheme))
-            .collect();
-        while let Some(scan_result) = self.scan_next_token() {
+    fn tokenize(&mut self) {
+        while let Some(scan_result) = self.next() {
             match scan_result {
                 Ok(token) => self.tokens.push(token),
                 Err(error) => self.error_log.push(error),
             }
         }
-        self.tokens.push(SourceToken {
-            token: Token::Eof,
-            location_span: self.cursor,
-        })
     }
     // Note that this is the only function that will ever "close" the scanning cursor. All other
     // actions only advance it.
@@ -235,14 +416,21 @@ impl Scanner {
                 ")" => Ok(Token::RightParen),
                 "{" => Ok(Token::LeftBrace),
                 "}" => Ok(Token::RightBrace),
+                "[" => Ok(Token::LeftBracket),
+                "]" => Ok(Token::RightBracket),
                 "," => Ok(Token::Comma),
                 "." => Ok(Token::Dot),
                 "-" => Ok(Token::Minus),
                 "+" => Ok(Token::Plus),
                 ";" => Ok(Token::Semicolon),
                 "*" => Ok(Token::Star),
+                "%" => Ok(Token::Percent),
                 "?" => Ok(Token::QuestionMark),
                 ":" => Ok(Token::Colon),
+                "&" => Ok(Token::Ampersand),
+                "|" => Ok(Token::Pipe),
+                "^" => Ok(Token::Caret),
+                "~" => Ok(Token::Tilde),
                 "!" => {
                     if self.match_next_symbol("=") {
                         Ok(Token::BangEqual)
@@ -260,6 +448,10 @@ impl Scanner {
                 "<" => {
                     if self.match_next_symbol("=") {
                         Ok(Token::LessEqual)
+                    } else if self.at_heredoc() {
+                        self.consume_heredoc()
+                    } else if self.match_next_symbol("<") {
+                        Ok(Token::LessLess)
                     } else {
                         Ok(Token::Less)
                     }
@@ -267,6 +459,8 @@ impl Scanner {
                 ">" => {
                     if self.match_next_symbol("=") {
                         Ok(Token::GreaterEqual)
+                    } else if self.match_next_symbol(">") {
+                        Ok(Token::GreaterGreater)
                     } else {
                         Ok(Token::Greater)
                     }
@@ -294,15 +488,11 @@ impl Scanner {
                 "\n" => Ok(Token::Whitespace(WhitespaceKind::Newline)),
                 "\"" => self.consume_string(),
                 digit if is_digit(digit) => self.consume_number(),
+                "r" if self.at_raw_string() => self.consume_raw_string(),
                 identifier if is_alpha(identifier) => self.consume_identifier(),
-                _ => Err(errors::Error {
-                    kind: errors::ErrorKind::Scanning,
-                    description: errors::ErrorDescription {
-                        subject: Some(String::from(symbol)),
-                        location: Some(self.cursor),
-                        description: String::from("Unexpected character"),
-                    },
-                }),
+                _ => Err(errors::Error::scanning(self.cursor, "Unexpected character")
+                    .with_code(ErrorCode::UnexpectedCharacter)
+                    .with_subject(symbol)),
             };
             let ret = match scan_result {
                 Ok(token) => {
@@ -339,18 +529,14 @@ impl Scanner {
         false
     }
     fn peek_next_symbol(&self) -> Option<Symbol> {
-        if let Some(curr) = self.source.get(self.cursor.end.index) {
-            Some(curr.to_string())
-        } else {
-            None
-        }
+        self.source
+            .get(self.cursor.end.index)
+            .map(|curr| curr.to_string())
     }
     fn peek_next_symbol_twice(&self) -> Option<Symbol> {
-        if let Some(curr) = self.source.get(self.cursor.end.index + 1) {
-            Some(curr.to_string())
-        } else {
-            None
-        }
+        self.source
+            .get(self.cursor.end.index + 1)
+            .map(|curr| curr.to_string())
     }
     fn consume_string(&mut self) -> Result<Token, errors::Error> {
         while let Some(symbol) = self.peek_next_symbol() {
@@ -363,26 +549,148 @@ impl Scanner {
             }
         }
         let error_string = self.source_substring(self.cursor);
-        Err(errors::Error {
-            kind: errors::ErrorKind::Scanning,
-            description: errors::ErrorDescription {
-                subject: Some(error_string),
-                location: Some(self.cursor),
-                description: String::from("Unterminated String"),
-            },
-        })
+        Err(errors::Error::scanning(self.cursor, "Unterminated String")
+            .with_code(ErrorCode::UnterminatedString)
+            .with_subject(error_string))
+    }
+    // Just after an "r", is this the start of a raw string rather than an identifier? That's any
+    // number of "#"s (including none) followed by a quote.
+    fn at_raw_string(&self) -> bool {
+        let mut index = self.cursor.end.index;
+        while self.source.get(index).is_some_and(|symbol| symbol == "#") {
+            index += 1;
+        }
+        self.source.get(index).is_some_and(|symbol| symbol == "\"")
+    }
+    // Note that ordinary strings don't have escapes yet either, so for now the only difference is
+    // that the "#"s let a raw string contain quotes. It only ends at a quote followed by as many
+    // "#"s as it started with, so `r#"say "hi""#` is `say "hi"`.
+    fn consume_raw_string(&mut self) -> Result<Token, errors::Error> {
+        let mut hashes = 0;
+        while self.match_next_symbol("#") {
+            hashes += 1;
+        }
+        self.consume_next_symbol();
+        let mut content = String::new();
+        while let Some(symbol) = self.consume_next_symbol() {
+            if symbol == "\"" {
+                let closing_hashes = (0..hashes)
+                    .take_while(|offset| {
+                        self.source
+                            .get(self.cursor.end.index + offset)
+                            .is_some_and(|symbol| symbol == "#")
+                    })
+                    .count();
+                if closing_hashes == hashes {
+                    for _ in 0..hashes {
+                        self.consume_next_symbol();
+                    }
+                    return Ok(Token::RawString(content));
+                }
+            }
+            content.push_str(&symbol);
+        }
+        Err(
+            errors::Error::scanning(self.cursor, "Unterminated raw string")
+                .with_code(ErrorCode::UnterminatedRawString)
+                .with_subject(self.source_substring(self.cursor)),
+        )
+    }
+    // Just after a "<", is this the start of a heredoc? That's two more "<"s and then the delimiter.
+    fn at_heredoc(&self) -> bool {
+        let index = self.cursor.end.index;
+        self.source.get(index).is_some_and(|symbol| symbol == "<")
+            && self
+                .source
+                .get(index + 1)
+                .is_some_and(|symbol| symbol == "<")
+            && self
+                .source
+                .get(index + 2)
+                .is_some_and(|symbol| is_alpha(symbol))
+    }
+    // For long strings over many lines, like:
+    //
+    // var poem = <<<END
+    // Roses are red,
+    //   violets are blue.
+    // END
+    // ;
+    //
+    // Everything between the line with the delimiter and the line that's only the delimiter is kept
+    // exactly as written, except for the newline just before the closing delimiter. The closing
+    // line can't have anything else on it (not even indentation), so that there's never any doubt
+    // about where the string ends, which means the rest of the statement goes on the next line.
+    fn consume_heredoc(&mut self) -> Result<Token, errors::Error> {
+        self.consume_next_symbol();
+        self.consume_next_symbol();
+        let mut delimiter = String::new();
+        while let Some(symbol) = self.peek_next_symbol() {
+            if !is_alpha_numeric(&symbol) {
+                break;
+            }
+            delimiter.push_str(&symbol);
+            self.consume_next_symbol();
+        }
+        if !self.match_newline() {
+            return Err(self
+                .construct_heredoc_error("Expected a new line after heredoc delimiter")
+                .with_code(ErrorCode::HeredocDelimiter));
+        }
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            while let Some(symbol) = self.peek_next_symbol() {
+                if is_newline(&symbol) {
+                    break;
+                }
+                line.push_str(&symbol);
+                self.consume_next_symbol();
+            }
+            if line == delimiter {
+                return Ok(Token::String(lines.join("\n")));
+            }
+            lines.push(line);
+            if !self.match_newline() {
+                return Err(self
+                    .construct_heredoc_error("Unterminated heredoc")
+                    .with_code(ErrorCode::UnterminatedHeredoc));
+            }
+        }
+    }
+    fn match_newline(&mut self) -> bool {
+        match self.peek_next_symbol() {
+            Some(symbol) if is_newline(&symbol) => {
+                self.consume_next_symbol();
+                true
+            }
+            _ => false,
+        }
+    }
+    fn construct_heredoc_error(&self, description: &str) -> errors::Error {
+        errors::Error::scanning(self.cursor, String::from(description))
     }
     fn source_substring(&self, cursor: source_file::SourceSpan) -> String {
         self.source[cursor.start.index..cursor.end.index].join("")
     }
     // TODO: This function is crunchy as hell, also refactor peeking? I think this technically
     // allows numbers like "10."
-    // TODO: Something seems fishy that this doesn't return any errors...
     fn consume_number(&mut self) -> Result<Token, errors::Error> {
+        if self.source_substring(self.cursor) == "0" {
+            if self.match_next_symbol("x") {
+                return self.consume_radix_number(16, "hexadecimal");
+            }
+            if self.match_next_symbol("o") {
+                return self.consume_radix_number(8, "octal");
+            }
+            if self.match_next_symbol("b") {
+                return self.consume_radix_number(2, "binary");
+            }
+        }
         // Consume all digits until you run out.
         // TODO: Duplicated code.
         while let Some(symbol) = self.peek_next_symbol() {
-            if is_digit(&symbol) {
+            if is_digit(&symbol) || symbol == DIGIT_SEPARATOR {
                 self.consume_next_symbol();
             } else {
                 break;
@@ -397,7 +705,7 @@ impl Scanner {
                         self.consume_next_symbol();
                         // TODO: Duplicated Code
                         while let Some(symbol) = self.peek_next_symbol() {
-                            if is_digit(&symbol) {
+                            if is_digit(&symbol) || symbol == DIGIT_SEPARATOR {
                                 self.consume_next_symbol();
                             } else {
                                 break;
@@ -407,12 +715,57 @@ impl Scanner {
                 }
             }
         }
-        let value = self
-            .source_substring(self.cursor)
+        let text = self.source_substring(self.cursor);
+        if !has_valid_separators(&text) {
+            return Err(self.construct_number_error(String::from(
+                "Digit separators can only go between digits",
+            )));
+        }
+        let value = text
+            .replace(DIGIT_SEPARATOR, "")
             .parse::<f64>()
             .expect("Internal error parsing float!");
         Ok(Token::Number(value))
     }
+    // For `0x1F`, `0o17` and `0b1010`, once the prefix has been consumed. Everything alphanumeric
+    // after the prefix is taken as part of the number, so that a typo like `0x1G` is reported as one
+    // bad literal, rather than quietly becoming `0x1` followed by the identifier `G`.
+    fn consume_radix_number(&mut self, radix: u32, name: &str) -> Result<Token, errors::Error> {
+        let mut digits = String::new();
+        while let Some(symbol) = self.peek_next_symbol() {
+            if is_alpha_numeric(&symbol) {
+                digits.push_str(&symbol);
+                self.consume_next_symbol();
+            } else {
+                break;
+            }
+        }
+        if digits.is_empty() {
+            return Err(self.construct_number_error(format!("Expected digits in {} literal", name)));
+        }
+        if !has_valid_separators(&digits) {
+            return Err(self.construct_number_error(String::from(
+                "Digit separators can only go between digits",
+            )));
+        }
+        let digits = digits.replace(DIGIT_SEPARATOR, "");
+        if let Some(digit) = digits.chars().find(|digit| !digit.is_digit(radix)) {
+            return Err(self
+                .construct_number_error(format!("Invalid digit '{}' in {} literal", digit, name)));
+        }
+        match i64::from_str_radix(&digits, radix) {
+            Ok(value) => Ok(Token::Number(value as f64)),
+            Err(_) => {
+                Err(self
+                    .construct_number_error(format!("Number is too large for a {} literal", name)))
+            }
+        }
+    }
+    fn construct_number_error(&self, description: String) -> errors::Error {
+        errors::Error::scanning(self.cursor, description)
+            .with_code(ErrorCode::InvalidNumber)
+            .with_subject(self.source_substring(self.cursor))
+    }
     // TODO: Another one that doesn't return errors??
     fn consume_identifier(&mut self) -> Result<Token, errors::Error> {
         while let Some(symbol) = self.peek_next_symbol() {
@@ -431,8 +784,179 @@ impl Scanner {
     }
 }
 
+/// Errors are handed out in line with the tokens rather than logged. The last token is always Eof.
+impl Iterator for Scanner {
+    type Item = Result<SourceToken, errors::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(scan_result) = self.scan_next_token() {
+            return Some(scan_result);
+        }
+        if self.reached_eof {
+            return None;
+        }
+        self.reached_eof = true;
+        Some(Ok(SourceToken {
+            token: Token::Eof,
+            location_span: self.cursor,
+        }))
+    }
+}
+
 impl errors::ErrorLoggable for Scanner {
     fn error_log(&self) -> &errors::ErrorLog {
         &self.error_log
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Everything but whitespace and the Eof at the end.
+    fn tokens(source: &str) -> Vec<Token> {
+        Scanner::from_source(source.to_string())
+            .tokens()
+            .into_iter()
+            .map(|source_token| source_token.token)
+            .filter(|token| !matches!(token, Token::Whitespace(_) | Token::Eof))
+            .collect()
+    }
+
+    fn error_codes(source: &str) -> Vec<Option<ErrorCode>> {
+        let scanner = Scanner::from_source(source.to_string());
+        scanner.error_log.iter().map(|error| error.code).collect()
+    }
+
+    // --- Numbers ---
+
+    #[test]
+    fn scans_numbers_in_every_base() {
+        assert_eq!(
+            tokens("0x1F 0o17 0b1010 0xfF 12.5"),
+            vec![
+                Token::Number(31.0),
+                Token::Number(15.0),
+                Token::Number(10.0),
+                Token::Number(255.0),
+                Token::Number(12.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_digits_outside_the_base() {
+        for source in ["0x1G", "0o18", "0b102", "0x"] {
+            assert_eq!(
+                error_codes(source),
+                vec![Some(ErrorCode::InvalidNumber)],
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn ignores_digit_separators() {
+        assert_eq!(tokens("1_000_000"), vec![Token::Number(1_000_000.0)]);
+        assert_eq!(tokens("0xFF_FF"), vec![Token::Number(65535.0)]);
+        assert_eq!(tokens("1_000.000_1"), vec![Token::Number(1000.0001)]);
+    }
+
+    #[test]
+    fn a_leading_underscore_is_an_identifier() {
+        assert_eq!(
+            tokens("_123"),
+            vec![Token::Identifier(String::from("_123"))]
+        );
+    }
+
+    #[test]
+    fn rejects_separators_that_are_not_between_digits() {
+        for source in ["1_.0", "1__0", "1_"] {
+            assert_eq!(
+                error_codes(source),
+                vec![Some(ErrorCode::InvalidNumber)],
+                "{}",
+                source
+            );
+        }
+    }
+
+    // --- Identifiers ---
+
+    #[test]
+    fn identifiers_can_use_any_letter() {
+        for name in ["héllo", "变量", "ñandú", "Ωmega"] {
+            assert_eq!(tokens(name), vec![Token::Identifier(name.to_string())]);
+        }
+    }
+
+    // "e" followed by a combining acute accent is one grapheme, and so one letter.
+    #[test]
+    fn combining_marks_stay_part_of_the_identifier() {
+        let name = "cafe\u{301}";
+        assert_eq!(tokens(name), vec![Token::Identifier(name.to_string())]);
+        assert_eq!(error_codes(name), vec![]);
+    }
+
+    #[test]
+    fn emoji_are_not_identifiers() {
+        for source in ["😀", "a😀"] {
+            assert_eq!(
+                error_codes(source),
+                vec![Some(ErrorCode::UnexpectedCharacter)],
+                "{}",
+                source
+            );
+        }
+    }
+
+    // --- Strings ---
+
+    #[test]
+    fn raw_strings_keep_backslashes() {
+        assert_eq!(
+            tokens(r#"r"C:\new\table""#),
+            vec![Token::RawString(String::from(r"C:\new\table"))]
+        );
+    }
+
+    #[test]
+    fn raw_strings_can_span_lines() {
+        assert_eq!(
+            tokens("r\"one\ntwo\""),
+            vec![Token::RawString(String::from("one\ntwo"))]
+        );
+    }
+
+    #[test]
+    fn raw_strings_with_hashes_can_hold_quotes() {
+        assert_eq!(
+            tokens(r####"r#"say "hi""# r##"a "# b"##"####),
+            vec![
+                Token::RawString(String::from(r#"say "hi""#)),
+                Token::RawString(String::from(r##"a "# b"##)),
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_raw_strings_are_errors() {
+        assert_eq!(
+            error_codes(r##"r#"never closed""##),
+            vec![Some(ErrorCode::UnterminatedRawString)]
+        );
+    }
+
+    #[test]
+    fn heredocs_run_to_their_delimiter() {
+        assert_eq!(
+            tokens("<<<END\nline one\n  line two\nEND\n;"),
+            vec![
+                Token::String(String::from("line one\n  line two")),
+                Token::Semicolon,
+            ]
+        );
+    }
+}
@@ -0,0 +1,266 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use serde_json::{Number, Value};
+
+use crate::error_codes::ErrorCode;
+use crate::errors;
+use crate::parser::LiteralKind;
+use crate::value;
+
+// -----| JSON |-----
+//
+// Note that JSON only has the one kind of number, same as lox, so numbers go back and forth as
+// f64s. Which means that integers beyond 2^53 can't all be represented, and will come back as the
+// nearest f64 rather than exactly. Going the other way, whole numbers are written as floats
+// (`1.0`, not `1`), and JSON has no way to write NaN or the infinities at all, so those become
+// `null`.
+
+pub fn literal_to_json(literal: &LiteralKind) -> Value {
+    match literal {
+        LiteralKind::Number(number) => Number::from_f64(*number)
+            .map(Value::Number)
+            .unwrap_or(Value::Null),
+        LiteralKind::String(string) => Value::String(string.clone()),
+        LiteralKind::Boolean(boolean) => Value::Bool(*boolean),
+        LiteralKind::Nil => Value::Null,
+    }
+}
+
+/// Arrays and objects have nothing to become as literals, they're lists and maps, which only
+/// exist as values. So they're errors here, see `json_to_value()` for those.
+pub fn json_to_literal(json: Value) -> Result<LiteralKind, errors::Error> {
+    match json {
+        Value::Null => Ok(LiteralKind::Nil),
+        Value::Bool(boolean) => Ok(LiteralKind::Boolean(boolean)),
+        // Every JSON number has an f64 approximation, `as_f64` only fails with serde_json's
+        // arbitrary precision feature.
+        Value::Number(number) => match number.as_f64() {
+            Some(value) => Ok(LiteralKind::Number(value)),
+            None => Err(construct_serialization_error(format!(
+                "JSON number can't be represented as a lox number: {}",
+                number
+            ))),
+        },
+        Value::String(string) => Ok(LiteralKind::String(string)),
+        Value::Array(_) => Err(construct_serialization_error(String::from(
+            "JSON arrays can't be converted to lox literals",
+        ))),
+        Value::Object(_) => Err(construct_serialization_error(String::from(
+            "JSON objects can't be converted to lox literals",
+        ))),
+    }
+}
+
+/// Lists and tuples become arrays and maps become objects. JSON has no tuples, so they come back
+/// from `json_to_value()` as lists. Anything that isn't data, like functions and
+/// classes, is an error, and so is a list or map that contains itself, since JSON can't.
+pub fn value_to_json(value: &value::Value) -> Result<Value, errors::Error> {
+    value_to_json_within(value, &mut Vec::new())
+}
+
+// The lists and maps further out, the same way `stringify()` keeps track of them.
+fn value_to_json_within(
+    value: &value::Value,
+    enclosing: &mut Vec<*const ()>,
+) -> Result<Value, errors::Error> {
+    let pointer = match value {
+        value::Value::List(list) => Some(Rc::as_ptr(list) as *const ()),
+        value::Value::Map(map) => Some(Rc::as_ptr(map) as *const ()),
+        _ => None,
+    };
+    if let Some(pointer) = pointer {
+        if enclosing.contains(&pointer) {
+            return Err(construct_serialization_error(format!(
+                "A {} that contains itself can't be converted to JSON",
+                value.type_name()
+            )));
+        }
+        enclosing.push(pointer);
+    }
+    let json = match value {
+        value::Value::Number(number) => Ok(literal_to_json(&LiteralKind::Number(*number))),
+        value::Value::String(string) => Ok(Value::String(string.clone())),
+        value::Value::Boolean(boolean) => Ok(Value::Bool(*boolean)),
+        value::Value::Nil => Ok(Value::Null),
+        value::Value::List(list) => list
+            .borrow()
+            .iter()
+            .map(|element| value_to_json_within(element, enclosing))
+            .collect(),
+        value::Value::Tuple(elements) => elements
+            .iter()
+            .map(|element| value_to_json_within(element, enclosing))
+            .collect(),
+        value::Value::Map(map) => map
+            .borrow()
+            .iter()
+            .map(|(key, value)| Ok((key.clone(), value_to_json_within(value, enclosing)?)))
+            .collect(),
+        other => Err(construct_serialization_error(format!(
+            "A {} can't be converted to JSON",
+            other.type_name()
+        ))),
+    };
+    if pointer.is_some() {
+        enclosing.pop();
+    }
+    json
+}
+
+/// Like `json_to_literal()`, but arrays become lists and objects become maps.
+pub fn json_to_value(json: Value) -> Result<value::Value, errors::Error> {
+    match json {
+        Value::Array(elements) => {
+            let elements = elements
+                .into_iter()
+                .map(json_to_value)
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(value::Value::List(Rc::new(RefCell::new(elements))))
+        }
+        Value::Object(entries) => {
+            let entries = entries
+                .into_iter()
+                .map(|(key, value)| Ok((key, json_to_value(value)?)))
+                .collect::<Result<HashMap<_, _>, errors::Error>>()?;
+            Ok(value::Value::Map(Rc::new(RefCell::new(entries))))
+        }
+        other => json_to_literal(other).map(value::Value::from),
+    }
+}
+
+fn construct_serialization_error(description: String) -> errors::Error {
+    errors::Error::new(errors::ErrorKind::Runtime, None, description)
+        .with_code(ErrorCode::Conversion)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn list(elements: Vec<value::Value>) -> value::Value {
+        value::Value::List(Rc::new(RefCell::new(elements)))
+    }
+
+    // --- Lists ---
+
+    #[test]
+    fn arrays_become_lists() {
+        let converted = json_to_value(json!([1.5, "two", [true, null]])).unwrap();
+        assert_eq!(
+            converted.to_string(),
+            list(vec![
+                value::Value::Number(1.5),
+                value::Value::String(String::from("two")),
+                list(vec![value::Value::Boolean(true), value::Value::Nil]),
+            ])
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn lists_become_arrays() {
+        let original = list(vec![
+            value::Value::Number(1.0),
+            list(vec![value::Value::String(String::from("a"))]),
+        ]);
+        assert_eq!(value_to_json(&original).unwrap(), json!([1.0, ["a"]]));
+    }
+
+    #[test]
+    fn arrays_survive_a_round_trip() {
+        let original = json!([[], [1.0, "x"], false]);
+        let converted = json_to_value(original.clone()).unwrap();
+        assert_eq!(value_to_json(&converted).unwrap(), original);
+    }
+
+    // --- Tuples ---
+
+    #[test]
+    fn tuples_become_arrays_and_come_back_as_lists() {
+        let tuple = value::Value::Tuple(Rc::from(vec![
+            value::Value::Number(1.0),
+            list(vec![value::Value::Nil]),
+        ]));
+        let json = value_to_json(&tuple).unwrap();
+        assert_eq!(json, json!([1.0, [null]]));
+        assert_eq!(json_to_value(json).unwrap().type_name(), "list");
+    }
+
+    // --- Maps ---
+
+    #[test]
+    fn objects_become_maps() {
+        let converted = json_to_value(json!({"a": 1.0, "b": {"c": [null]}})).unwrap();
+        let value::Value::Map(map) = &converted else {
+            panic!("expected a map, got {}", converted);
+        };
+        let map = map.borrow();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["a"], value::Value::Number(1.0));
+        assert_eq!(map["b"].type_name(), "map");
+    }
+
+    #[test]
+    fn maps_become_objects() {
+        let entries = HashMap::from([
+            (
+                String::from("name"),
+                value::Value::String(String::from("lox")),
+            ),
+            (
+                String::from("tags"),
+                list(vec![value::Value::Boolean(true)]),
+            ),
+        ]);
+        let original = value::Value::Map(Rc::new(RefCell::new(entries)));
+        assert_eq!(
+            value_to_json(&original).unwrap(),
+            json!({"name": "lox", "tags": [true]})
+        );
+    }
+
+    #[test]
+    fn objects_survive_a_round_trip() {
+        let original = json!({"empty": {}, "list": [{"x": 2.0}], "nothing": null});
+        let converted = json_to_value(original.clone()).unwrap();
+        assert_eq!(value_to_json(&converted).unwrap(), original);
+    }
+
+    // --- Not data ---
+
+    #[test]
+    fn lists_and_maps_that_contain_themselves_are_errors() {
+        let outer = list(vec![]);
+        let value::Value::List(elements) = &outer else {
+            unreachable!()
+        };
+        elements.borrow_mut().push(list(vec![outer.clone()]));
+        let error = value_to_json(&outer).unwrap_err();
+        assert!(
+            error.to_string().contains("list that contains itself"),
+            "{}",
+            error
+        );
+        elements.borrow_mut().clear();
+
+        // The same list twice side by side is fine, it only can't be inside itself.
+        let shared = list(vec![value::Value::Nil]);
+        let twice = list(vec![shared.clone(), shared]);
+        assert_eq!(value_to_json(&twice).unwrap(), json!([[null], [null]]));
+    }
+
+    #[test]
+    fn functions_are_not_data() {
+        let native = value::Value::Native(value::NativeFunction {
+            name: "nothing",
+            arity: 0,
+            max_arity: 0,
+            body: |_, _| Ok(value::Value::Nil),
+        });
+        let error = value_to_json(&native).unwrap_err();
+        assert_eq!(error.code, Some(ErrorCode::Conversion));
+    }
+}
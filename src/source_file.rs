@@ -1,4 +1,51 @@
-// TODO: Make a struct that actually contains the source.
+use std::cmp::Ordering;
+use std::fs;
+use std::io;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::scanner::USE_EXTENDED_UNICODE;
+
+// -----| Files |-----
+
+/// Source code along with where it came from.
+// TODO: The scanner still takes its own copy of the source. It'd be nice if it borrowed it from here
+// instead, but that means lifetimes on every token.
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    /// Source typed at the prompt or handed over by an embedder doesn't have a name.
+    pub name: Option<String>,
+    pub source: String,
+}
+
+impl SourceFile {
+    pub fn read(path: &str) -> io::Result<Self> {
+        Ok(SourceFile {
+            name: Some(path.to_string()),
+            source: fs::read_to_string(path)?,
+        })
+    }
+    pub fn unnamed(source: String) -> Self {
+        SourceFile { name: None, source }
+    }
+    /// The text of a line, numbered from 1 the same as locations are, without its line ending. The
+    /// line after a trailing newline is there, just empty, since that's where the end of the file
+    /// is.
+    pub fn line(&self, number: usize) -> Option<&str> {
+        self.source
+            .split('\n')
+            .nth(number.checked_sub(1)?)
+            .map(|line| line.trim_end_matches('\r'))
+    }
+    /// Locations count graphemes, so this finds how many bytes in they are. The end of the source
+    /// is right after the last byte, and anything past it isn't anywhere.
+    pub fn byte_offset(&self, location: SourceLocation) -> Option<usize> {
+        self.source
+            .grapheme_indices(USE_EXTENDED_UNICODE)
+            .map(|(offset, _)| offset)
+            .chain(std::iter::once(self.source.len()))
+            .nth(location.index)
+    }
+}
 
 // -----| Locations |-----
 
@@ -11,6 +58,27 @@ pub struct SourceLocation {
     pub index: usize,
 }
 
+// Locations are ordered by where they are in the source, which the index alone decides.
+impl PartialEq for SourceLocation {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl Eq for SourceLocation {}
+
+impl PartialOrd for SourceLocation {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SourceLocation {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.index.cmp(&other.index)
+    }
+}
+
 // This maybe too intimately tied to scanning...
 impl SourceLocation {
     pub fn new() -> Self {
@@ -38,6 +106,12 @@ impl SourceLocation {
     }
 }
 
+impl Default for SourceLocation {
+    fn default() -> Self {
+        SourceLocation::new()
+    }
+}
+
 /// SourceLocations represent one to many symbols in linear sequence in source.
 #[derive(Debug, Clone, Copy)]
 pub struct SourceSpan {
@@ -57,4 +131,23 @@ impl SourceSpan {
     pub fn close(&mut self) {
         self.start = self.end;
     }
+    /// Whether the location falls inside the span. Since the end is exclusive, a location right at
+    /// the end is just past it, and an empty span contains nothing at all.
+    pub fn contains(self, location: SourceLocation) -> bool {
+        self.start <= location && location < self.end
+    }
+    /// The smallest span covering both, including anything in between. The order they're given in
+    /// doesn't matter.
+    pub fn merge(a: SourceSpan, b: SourceSpan) -> SourceSpan {
+        SourceSpan {
+            start: a.start.min(b.start),
+            end: a.end.max(b.end),
+        }
+    }
+}
+
+impl Default for SourceSpan {
+    fn default() -> Self {
+        SourceSpan::new()
+    }
 }
@@ -0,0 +1,368 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::environment::ScopeId;
+use crate::errors;
+use crate::interpreter::{self, Interpreter};
+use crate::parser::{ExprArena, FunctionStmt, LiteralKind};
+use crate::scanner::Identifier;
+
+// -----| Values |-----
+
+/// Everything an expression can evaluate to. Most values start out as literals in the source, but
+/// not all of them, natives for instance only ever exist at runtime.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    String(String),
+    Boolean(bool),
+    Nil,
+    Native(NativeFunction),
+    Function(Function),
+    Class(Rc<Class>),
+    /// Shared the same way as lists, an instance is the same instance whatever holds it.
+    Instance(Rc<RefCell<Instance>>),
+    /// Shared rather than copied, so that changing a list through one variable changes it for
+    /// everything else that holds it too.
+    List(List),
+    /// Shared the same way as lists.
+    Map(Map),
+    /// Never changed once made, so sharing one is the same as copying it.
+    Tuple(Tuple),
+}
+
+pub type List = Rc<RefCell<Vec<Value>>>;
+pub type Map = Rc<RefCell<HashMap<String, Value>>>;
+pub type Tuple = Rc<[Value]>;
+
+impl From<LiteralKind> for Value {
+    fn from(literal: LiteralKind) -> Self {
+        match literal {
+            LiteralKind::Number(number) => Value::Number(number),
+            LiteralKind::String(string) => Value::String(string),
+            LiteralKind::Boolean(boolean) => Value::Boolean(boolean),
+            LiteralKind::Nil => Value::Nil,
+        }
+    }
+}
+
+// Exactly as `print` shows it, which is up to `stringify()`.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", interpreter::stringify(self))
+    }
+}
+
+impl Value {
+    /// What the value is called in error messages and by `type()`, so that both always agree.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Boolean(_) => "boolean",
+            Value::Nil => "nil",
+            Value::Native(_) | Value::Function(_) => "function",
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Tuple(_) => "tuple",
+        }
+    }
+    /// What `==` means in lox. Values of different types are never equal, there's no coercion of
+    /// the kind javascript does, so `0 == false` and `"1" == 1` are both false. Otherwise:
+    ///
+    /// - nil only equals nil
+    /// - booleans and strings compare by value (strings by content, not identity)
+    /// - numbers follow IEEE, deliberately, so `0 == -0` is true and NaN doesn't equal anything,
+    ///   not even itself. That makes `x != x` a NaN check, the same as in most other languages
+    /// - functions compare by identity. Natives are each defined once, so that's their name. Lox
+    ///   functions are the same declaration run in the same scope, so that running one declaration
+    ///   twice makes two different functions
+    /// - classes, instances, lists and maps compare by identity too, so two lists with the same
+    ///   elements aren't equal unless they're the same list
+    /// - tuples can't change, so they're equal when all their elements are, in order
+    ///
+    /// Every pairing is spelled out rather than relying on `PartialEq`, so that each new kind of
+    /// value has to decide for itself.
+    pub fn lox_eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Native(a), Value::Native(b)) => a.name == b.name,
+            (Value::Function(a), Value::Function(b)) => a == b,
+            (Value::Class(a), Value::Class(b)) => Rc::ptr_eq(a, b),
+            (Value::Instance(a), Value::Instance(b)) => Rc::ptr_eq(a, b),
+            (Value::List(a), Value::List(b)) => Rc::ptr_eq(a, b),
+            (Value::Map(a), Value::Map(b)) => Rc::ptr_eq(a, b),
+            (Value::Tuple(a), Value::Tuple(b)) => {
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| a.lox_eq(b))
+            }
+            (
+                Value::Nil
+                | Value::Boolean(_)
+                | Value::Number(_)
+                | Value::String(_)
+                | Value::Native(_)
+                | Value::Function(_)
+                | Value::Class(_)
+                | Value::Instance(_)
+                | Value::List(_)
+                | Value::Map(_)
+                | Value::Tuple(_),
+                _,
+            ) => false,
+        }
+    }
+    /// Whether the value is, or might hold, a function that closes over a scope, which is all the
+    /// garbage collector cares about.
+    pub fn can_reach_scopes(&self) -> bool {
+        match self {
+            Value::Function(_)
+            | Value::Class(_)
+            | Value::Instance(_)
+            | Value::List(_)
+            | Value::Map(_)
+            | Value::Tuple(_) => true,
+            Value::Number(_)
+            | Value::String(_)
+            | Value::Boolean(_)
+            | Value::Nil
+            | Value::Native(_) => false,
+        }
+    }
+    /// Going back the other way only works for values that could have been written in the source.
+    pub fn to_literal(&self) -> Option<LiteralKind> {
+        match self {
+            Value::Number(number) => Some(LiteralKind::Number(*number)),
+            Value::String(string) => Some(LiteralKind::String(string.clone())),
+            Value::Boolean(boolean) => Some(LiteralKind::Boolean(*boolean)),
+            Value::Nil => Some(LiteralKind::Nil),
+            Value::Native(_)
+            | Value::Function(_)
+            | Value::Class(_)
+            | Value::Instance(_)
+            | Value::List(_)
+            | Value::Map(_)
+            | Value::Tuple(_) => None,
+        }
+    }
+}
+
+// -----| Callables |-----
+
+pub trait Callable {
+    /// What the callable is called in stack traces.
+    fn name(&self) -> &str;
+    /// How many arguments have to be passed.
+    fn arity(&self) -> usize;
+    /// How many arguments can be passed, for callables with optional trailing arguments.
+    fn max_arity(&self) -> usize {
+        self.arity()
+    }
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, errors::Error>;
+}
+
+// Any errors a native returns are unlocated, the caller knows better where the call was.
+pub type NativeBody = fn(&mut Interpreter, Vec<Value>) -> Result<Value, errors::Error>;
+
+/// A function implemented in rust rather than lox.
+#[derive(Clone)]
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: usize,
+    pub max_arity: usize,
+    pub body: NativeBody,
+}
+
+impl Callable for NativeFunction {
+    fn name(&self) -> &str {
+        self.name
+    }
+    fn arity(&self) -> usize {
+        self.arity
+    }
+    fn max_arity(&self) -> usize {
+        self.max_arity
+    }
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, errors::Error> {
+        (self.body)(interpreter, arguments)
+    }
+}
+
+// Comparing function pointers isn't reliable, but every native has its own name.
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+/// A function declared in lox, or a method.
+#[derive(Clone)]
+pub struct Function {
+    pub declaration: Rc<FunctionStmt>,
+    /// What the declaration's ids refer to, which could be from an earlier line at the prompt than
+    /// the one that's running.
+    pub expressions: ExprArena,
+    /// The scope the function was declared in, which it can still see from wherever it's called.
+    pub closure: ScopeId,
+    /// What `this` is, for a method that's been looked up on an instance.
+    pub this: Option<Rc<RefCell<Instance>>>,
+    /// Whether this is a class's `init` method, which always gives back `this`.
+    pub is_initializer: bool,
+}
+
+impl Function {
+    /// The same method, but with `this` as the instance it was looked up on.
+    pub fn bind(&self, instance: Rc<RefCell<Instance>>) -> Function {
+        Function {
+            this: Some(instance),
+            ..self.clone()
+        }
+    }
+}
+
+impl Callable for Function {
+    fn name(&self) -> &str {
+        &self.declaration.name
+    }
+    fn arity(&self) -> usize {
+        self.declaration.parameters.len()
+    }
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, errors::Error> {
+        interpreter.call_function(self, arguments)
+    }
+}
+
+// Each lookup of a method binds it afresh, so a bound method equals any other binding of the same
+// method to the same instance.
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        let same_this = match (&self.this, &other.this) {
+            (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+        Rc::ptr_eq(&self.declaration, &other.declaration)
+            && self.closure == other.closure
+            && same_this
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.declaration.name)
+    }
+}
+
+/// A class declared in lox. Calling it makes a new instance, which is handed to `init` first if the
+/// class has one.
+pub struct Class {
+    pub name: Identifier,
+    pub superclass: Option<Rc<Class>>,
+    pub methods: HashMap<Identifier, Function>,
+}
+
+impl Class {
+    /// Methods are looked for on the class itself first, then on each superclass in turn.
+    pub fn find_method(&self, name: &str) -> Option<&Function> {
+        self.methods.get(name).or_else(|| {
+            self.superclass
+                .as_ref()
+                .and_then(|superclass| superclass.find_method(name))
+        })
+    }
+}
+
+impl Callable for Rc<Class> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn arity(&self) -> usize {
+        self.find_method("init")
+            .map_or(0, |initializer| initializer.arity())
+    }
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, errors::Error> {
+        let instance = Rc::new(RefCell::new(Instance {
+            class: Rc::clone(self),
+            fields: HashMap::new(),
+        }));
+        if let Some(initializer) = self.find_method("init") {
+            interpreter.call_function(&initializer.bind(Rc::clone(&instance)), arguments)?;
+        }
+        Ok(Value::Instance(instance))
+    }
+}
+
+// Classes are only ever equal to themselves.
+impl PartialEq for Class {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl fmt::Debug for Class {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<class {}>", self.name)
+    }
+}
+
+pub struct Instance {
+    pub class: Rc<Class>,
+    /// Fields spring into being the first time they're assigned.
+    pub fields: HashMap<Identifier, Value>,
+}
+
+impl Instance {
+    /// A field if there is one by the name, otherwise a method bound to the instance. Fields
+    /// shadow methods.
+    pub fn get(instance: &Rc<RefCell<Instance>>, name: &str) -> Option<Value> {
+        let borrowed = instance.borrow();
+        if let Some(value) = borrowed.fields.get(name) {
+            return Some(value.clone());
+        }
+        borrowed
+            .class
+            .find_method(name)
+            .map(|method| Value::Function(method.bind(Rc::clone(instance))))
+    }
+}
+
+impl PartialEq for Instance {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+// Fields aren't shown, since an instance can hold itself.
+impl fmt::Debug for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{} instance>", self.class.name)
+    }
+}
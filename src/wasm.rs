@@ -0,0 +1,135 @@
+use std::io::{self, BufRead, Read, Write};
+
+use wasm_bindgen::prelude::*;
+
+use crate::errors::ErrorLog;
+use crate::interpreter::{Interpreter, OutputBuffer};
+use crate::source_file::SourceFile;
+
+// -----| Playground |-----
+
+/// Runs a whole program and returns everything it printed, followed by any errors and warnings
+/// rendered against the source the way the command line renders them (without a file name, since
+/// there isn't one). There's nothing to read from, so `readLine()` always gives nil.
+#[wasm_bindgen]
+pub fn wasm_run(source: &str) -> String {
+    let output = OutputBuffer::default();
+    let interpreter = Interpreter::new()
+        .with_input(Box::new(io::empty()))
+        .with_writer(Box::new(output.clone()));
+    let log = run(source, interpreter);
+    let mut result = output.contents();
+    result.push_str(&render(&log));
+    result
+}
+
+/// Runs a whole program talking to the page as it goes: everything printed is handed to the
+/// page's `rloxWrite(text)` straight away, and `readLine()` asks the page's `rloxReadLine()` for
+/// each line, which gives back `undefined` once there aren't any more. Errors and warnings are
+/// written the same way once the program's done. Returns whether it ran without errors.
+#[wasm_bindgen]
+pub fn wasm_run_interactive(source: &str) -> bool {
+    let interpreter = Interpreter::new()
+        .with_input(Box::new(PageInput::default()))
+        .with_writer(Box::new(PageOutput));
+    let log = run(source, interpreter);
+    if log.error_count() > 0 || log.warning_count() > 0 {
+        page_write(&render(&log));
+    }
+    log.error_count() == 0
+}
+
+fn run(source: &str, mut interpreter: Interpreter) -> ErrorLog {
+    let (program, mut log) = crate::parse_source(source.to_string());
+    if log.error_count() == 0 {
+        if let Err(error) = interpreter.interpret(&program) {
+            log.push(error);
+        }
+    }
+    log.source = Some(SourceFile::unnamed(source.to_string()));
+    log
+}
+
+// The same errors, excerpts and summary that `errors::print_error_log()` sends to stderr.
+fn render(log: &ErrorLog) -> String {
+    let mut result = String::new();
+    if !log.is_empty() {
+        result.push_str(&format!("{}\n", log));
+    }
+    if let Some(summary) = log.summary() {
+        result.push_str(&format!("{}\n", summary));
+    }
+    result
+}
+
+// --- Page IO ---
+
+/// Stdout for `wasm_run_interactive()`. Each write is passed along whole, and the interpreter
+/// writes whole strings at a time, so nothing gets split in the middle of a character.
+struct PageOutput;
+
+impl Write for PageOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        page_write(&String::from_utf8_lossy(buf));
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Stdin for `wasm_run_interactive()`. Lines are only asked for once the last one's been used up,
+/// so the page isn't asked for input the script never reads.
+#[derive(Default)]
+struct PageInput {
+    line: Vec<u8>,
+    position: usize,
+    finished: bool,
+}
+
+impl Read for PageInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        self.consume(count);
+        Ok(count)
+    }
+}
+
+impl BufRead for PageInput {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.position == self.line.len() && !self.finished {
+            match page_read_line() {
+                Some(line) => {
+                    self.line = format!("{}\n", line).into_bytes();
+                    self.position = 0;
+                }
+                None => self.finished = true,
+            }
+        }
+        Ok(&self.line[self.position..])
+    }
+    fn consume(&mut self, amount: usize) {
+        self.position = (self.position + amount).min(self.line.len());
+    }
+}
+
+// --- Imports ---
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = Date, js_name = now)]
+    pub fn milliseconds_since_epoch() -> f64;
+}
+
+// Not limited to wasm32 like `Date.now()` above, so that they're still checked when the feature's
+// built for the host. They'd panic if they were ever called there, but only the exports use them.
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_name = rloxWrite)]
+    fn page_write(text: &str);
+    #[wasm_bindgen(js_name = rloxReadLine)]
+    fn page_read_line() -> Option<String>;
+}
@@ -0,0 +1,281 @@
+mod common;
+
+use std::process::Command;
+
+use common::{run_script, stderr, stdout};
+
+// --- Running scripts ---
+
+#[test]
+fn prints_a_scripts_output() {
+    let output = run_script(
+        "prints.lox",
+        "var a = 1;\nprint a + 2;\nprint \"done\";\n",
+        &[],
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "3\ndone\n");
+    assert_eq!(stderr(&output), "");
+}
+
+// --- Exit codes ---
+
+#[test]
+fn syntax_errors_exit_with_65() {
+    let output = run_script("bad_syntax.lox", "print (1 + ;\n", &[]);
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn scanning_errors_alone_exit_with_65() {
+    let output = run_script("bad_character.lox", "print 1; @\n", &[]);
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn resolution_errors_exit_with_65() {
+    let output = run_script("bad_return.lox", "print 1;\nreturn 2;\n", &[]);
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn runtime_errors_exit_with_70() {
+    let output = run_script(
+        "divide_by_nil.lox",
+        "print 1;\nprint 1 / nil;\nprint 2;\n",
+        &[],
+    );
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(stdout(&output), "1\n");
+}
+
+#[test]
+fn missing_files_are_reported_rather_than_panicking() {
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox_treewalk"))
+        .args(["--color", "never", "does_not_exist.lox"])
+        .output()
+        .expect("Failed to run the interpreter");
+    assert_eq!(output.status.code(), Some(66));
+    let stderr = stderr(&output);
+    assert!(
+        stderr.starts_with("error[E3010]: Couldn't read file 'does_not_exist.lox'"),
+        "{}",
+        stderr
+    );
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
+
+#[test]
+fn exit_sets_the_exit_code() {
+    let output = run_script("exits.lox", "print 1;\nexit(3);\nprint 2;\n", &[]);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(stdout(&output), "1\n");
+    assert_eq!(stderr(&output), "");
+}
+
+#[test]
+fn exit_codes_out_of_range_are_runtime_errors() {
+    let output = run_script("exits_badly.lox", "exit(256);\n", &[]);
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn runaway_recursion_exits_with_70() {
+    let output = run_script(
+        "recursion.lox",
+        "fun countDown(n) { return countDown(n - 1); }\nprint countDown(10);\n",
+        &[],
+    );
+    assert_eq!(output.status.code(), Some(70));
+    assert!(stderr(&output).contains("[E3008]"), "{}", stderr(&output));
+}
+
+#[test]
+fn stack_overflows_point_at_the_call_that_went_too_deep() {
+    let output = run_script("stack_overflow.lox", "fun f() {\n  f();\n}\nf();\n", &[]);
+    assert_eq!(output.status.code(), Some(70));
+    let stderr = stderr(&output);
+    assert!(
+        stderr.starts_with(
+            "stack_overflow.lox:2:3: error[E3008]: Stack overflow: maximum call depth 2000 \
+             exceeded"
+        ),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("in f, called from stack_overflow.lox:2:3"));
+    assert!(stderr.contains("... and "));
+}
+
+// --- Asserts ---
+
+#[test]
+fn passing_asserts_do_nothing() {
+    let output = run_script(
+        "passes.lox",
+        "assert(true);\nassert(1 == 1, \"maths\");\nprint \"ok\";\n",
+        &[],
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "ok\n");
+}
+
+#[test]
+fn failing_asserts_exit_with_70_and_the_message() {
+    let output = run_script(
+        "fails.lox",
+        "print 1;\nassert(1 > 2, \"one is bigger\");\nprint 2;\n",
+        &[],
+    );
+    assert_eq!(output.status.code(), Some(70));
+    assert_eq!(stdout(&output), "1\n");
+    assert!(
+        stderr(&output).contains("fails.lox:2:1: error[E3012]: Assertion failed: one is bigger"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn asserting_nil_fails() {
+    let output = run_script("nil.lox", "assert(nil);\n", &[]);
+    assert_eq!(output.status.code(), Some(70));
+    assert!(
+        stderr(&output).contains("error[E3012]: Assertion failed"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+// The acceptance scripts under `scripts/`, each of which prints "ok" if all of its asserts pass.
+#[test]
+fn math_natives_pass_their_asserts() {
+    let output = run_script("math.lox", include_str!("scripts/math.lox"), &[]);
+    assert_eq!(stderr(&output), "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "ok\n");
+}
+
+// --- Diagnostics ---
+
+#[test]
+fn diagnostics_go_to_stderr_only() {
+    let output = run_script("to_stderr.lox", "print \"out\";\nprint nil + 1;\n", &[]);
+    assert_eq!(stdout(&output), "out\n");
+    assert!(stderr(&output).contains("to_stderr.lox:2:7"));
+}
+
+#[test]
+fn static_errors_print_nothing_to_stdout() {
+    let output = run_script("static_errors.lox", "print \"out\";\nvar = 1;\n", &[]);
+    assert_eq!(stdout(&output), "");
+    assert!(!stderr(&output).is_empty());
+}
+
+#[test]
+fn renders_errors_against_the_file() {
+    let output = run_script("rendered.lox", "var a = 1;\nprint a +;\n", &[]);
+    assert_eq!(
+        stderr(&output),
+        "rendered.lox:2:10: error[E1001]: Expected value or expression, found ';'
+  |
+2 | print a +;
+  |          ^
+1 error emitted
+"
+    );
+}
+
+#[test]
+fn reports_scanning_and_parsing_errors_in_source_order() {
+    let source = "var = 1;\nprint @;\n";
+    let output = run_script("combined_order.lox", source, &[]);
+    let stderr = stderr(&output);
+    let lines: Vec<&str> = stderr
+        .lines()
+        .filter(|line| line.starts_with("combined_order.lox"))
+        .collect();
+    assert_eq!(
+        lines,
+        vec![
+            "combined_order.lox:1:5: error[E1002]: Expected 'identifier \"example\"' after \
+             expression, instead found '='",
+            "combined_order.lox:2:7: error[E0001]: Unexpected character '@'",
+        ]
+    );
+    assert!(stderr.ends_with("2 errors emitted\n"));
+}
+
+// The unterminated string runs to the end of the file, so the parser runs out of tokens, but that's
+// only because of the string and isn't reported.
+#[test]
+fn reports_an_unterminated_string_and_a_missing_semicolon_in_order() {
+    let source = "print 1\nprint 2;\nprint \"abc;\nprint 3;\n";
+    let output = run_script("unterminated.lox", source, &[]);
+    assert_eq!(output.status.code(), Some(65));
+    let stderr = stderr(&output);
+    let lines: Vec<&str> = stderr
+        .lines()
+        .filter(|line| line.starts_with("unterminated.lox"))
+        .collect();
+    assert_eq!(
+        lines,
+        vec![
+            "unterminated.lox:2:1: error[E1002]: Expected ';' after expression, instead found \
+             'print'",
+            "unterminated.lox:3:7: error[E0002]: Unterminated String '\"abc;",
+        ]
+    );
+    assert!(stderr.ends_with("2 errors emitted\n"));
+}
+
+#[test]
+fn warnings_and_errors_are_counted_in_the_summary() {
+    let source = "{ var unused = 1; }\nvar a = 1;\nvar a = 2;\nprint ;\n";
+    let output = run_script("summary.lox", source, &[]);
+    assert!(
+        stderr(&output).ends_with("1 error, 2 warnings emitted\n"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn json_errors_are_one_object_per_line() {
+    let output = run_script(
+        "json_errors.lox",
+        "var a = 1;\nprint a +;\n",
+        &["--error-format", "json"],
+    );
+    assert_eq!(output.status.code(), Some(65));
+    let stderr = stderr(&output);
+    let lines: Vec<&str> = stderr.lines().collect();
+    assert_eq!(lines.len(), 1);
+    let error: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+    assert_eq!(error["kind"], "parsing");
+    assert_eq!(error["severity"], "error");
+    assert_eq!(error["code"], "E1001");
+    assert_eq!(error["file"], "json_errors.lox");
+    assert_eq!(error["line"], 2);
+    assert_eq!(error["column"], 10);
+    assert_eq!(error["end_line"], 2);
+    assert_eq!(error["end_column"], 11);
+    assert_eq!(error["offset"], 20);
+    assert_eq!(error["end_offset"], 21);
+}
+
+#[test]
+fn json_output_collects_everything() {
+    let output = run_script(
+        "json_output.lox",
+        "print 1;\nprint nil + 1;\n",
+        &["--output", "json"],
+    );
+    assert_eq!(output.status.code(), Some(70));
+    let report: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(report["output"], serde_json::json!(["1"]));
+    assert_eq!(report["errors"][0]["kind"], "runtime");
+    assert_eq!(report["exit_code"], 70);
+}
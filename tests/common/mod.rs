@@ -0,0 +1,52 @@
+// Shared by the integration tests, each of which runs the interpreter binary as a separate process.
+// Not every test file uses every helper.
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+const BINARY: &str = env!("CARGO_BIN_EXE_rlox_treewalk");
+
+/// Writes the source to a script named `name` and runs it, from the directory it's in so that
+/// diagnostics name it without a path. Errors are never colored, whatever the terminal.
+pub fn run_script(name: &str, source: &str, args: &[&str]) -> Output {
+    let directory = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    fs::write(directory.join(name), source).expect("Failed to write test script");
+    Command::new(BINARY)
+        .current_dir(&directory)
+        .args(["--color", "never"])
+        .args(args)
+        .arg(name)
+        .output()
+        .expect("Failed to run the interpreter")
+}
+
+/// Types each line of input at the prompt, then ends the input.
+pub fn run_prompt(input: &str) -> Output {
+    let mut child = Command::new(BINARY)
+        .args(["--color", "never"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run the interpreter");
+    child
+        .stdin
+        .take()
+        .expect("stdin was piped")
+        .write_all(input.as_bytes())
+        .expect("Failed to type at the prompt");
+    child
+        .wait_with_output()
+        .expect("Failed to wait for the prompt")
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
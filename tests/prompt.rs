@@ -0,0 +1,57 @@
+mod common;
+
+use common::{run_prompt, stderr, stdout};
+
+// Each line read prints a fresh prompt, so the output is prompts with echoes between them.
+fn echoes(output: &str) -> Vec<&str> {
+    output
+        .split("> ")
+        .map(str::trim_end)
+        .filter(|echo| !echo.is_empty())
+        .collect()
+}
+
+#[test]
+fn keeps_definitions_between_lines() {
+    let output = run_prompt("var x = 1;\nprint x;\nx = x + 1;\nx\n");
+    assert_eq!(echoes(&stdout(&output)), vec!["1", "2"]);
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn echoes_lone_expressions() {
+    let output = run_prompt("1 + 2\n\"a\" + \"b\"\n");
+    assert_eq!(echoes(&stdout(&output)), vec!["3", "ab"]);
+}
+
+#[test]
+fn carries_on_after_errors() {
+    let output = run_prompt("var x = 1;\nprint y;\nprint x +;\nprint x;\n");
+    assert_eq!(echoes(&stdout(&output)), vec!["1"]);
+    assert!(stderr(&output).contains("Undefined variable"));
+    assert!(stderr(&output).contains("Expected value or expression"));
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn carries_on_after_exit() {
+    let output = run_prompt("exit(3);\nprint \"still here\";\n");
+    assert_eq!(echoes(&stdout(&output)), vec!["still here"]);
+    assert!(stderr(&output).contains("Script requested exit with code 3"));
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn carries_on_after_a_stack_overflow() {
+    let output = run_prompt("fun f() { f(); }\nf();\nprint \"still here\";\n");
+    assert_eq!(echoes(&stdout(&output)), vec!["still here"]);
+    assert!(stderr(&output).contains("Stack overflow: maximum call depth 2000 exceeded"));
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn waits_for_unfinished_input() {
+    let output = run_prompt("{\nvar x = 2;\nprint x;\n}\n");
+    assert_eq!(stdout(&output).matches("... ").count(), 3);
+    assert!(stdout(&output).ends_with("2\n> "));
+}